@@ -0,0 +1,484 @@
+const PERSISTED_METHODS = new Set([
+    'put',
+    'delete',
+    'flush',
+    'flushIfDue',
+    'putBatch',
+    'putWithTTL',
+    'commitTransaction',
+    'vacuum',
+    'vacuumInto',
+    'restoreTo',
+    'runIdleCompaction',
+    'commitGroup',
+    'checkpoint',
+    'putIfAbsent',
+    'compareAndSwap',
+    'deleteIfEquals',
+    'getAndSet'
+]);
+
+const GROUPED_METHODS = new Set(['put', 'delete', 'putWithTTL']);
+
+const WRITE_METHODS = new Set([
+    'put',
+    'delete',
+    'putBatch',
+    'putWithTTL',
+    'commitTransaction',
+    'putIfAbsent',
+    'compareAndSwap',
+    'deleteIfEquals',
+    'getAndSet'
+]);
+
+const READ_ONLY = { readOnly: true };
+
+const COMPACTION_IDLE_DELAY_MS = 2000;
+const GROUP_COMMIT_WINDOW_MS = 5;
+const PERIODIC_FLUSH_MS = 1000;
+
+export function supportsSyncAccessHandle() {
+    return typeof FileSystemFileHandle !== 'undefined' &&
+        'createSyncAccessHandle' in FileSystemFileHandle.prototype;
+}
+
+export function createEngine() {
+    let wasm = null;
+    let db = null;
+    let buffered = false;
+    let queue = Promise.resolve();
+    let compactionTimer = null;
+    let compactionIdleDelay = COMPACTION_IDLE_DELAY_MS;
+    let groupCommitWindow = 0;
+    let group = null;
+    let durability = 'full';
+    let durabilityTimer = null;
+    let checkpointInterval = 0;
+    let checkpointTimer = null;
+    const transactions = new Map();
+    let transactionId = 0;
+
+    async function handleMethod(method, args) {
+        switch (method) {
+            case 'init':
+                return await initWasm(args.wasmUrl);
+            case 'open':
+                if (!wasm) {
+                    throw new Error('WASM not initialized. Call init first.');
+                }
+                buffered = false;
+                const compaction = args.compaction || {};
+                compactionIdleDelay = compaction.idleDelayMs ?? COMPACTION_IDLE_DELAY_MS;
+                const groupCommit = args.groupCommit;
+                groupCommitWindow = groupCommit ? (groupCommit.windowMs ?? GROUP_COMMIT_WINDOW_MS) : 0;
+                const checkpoint = args.checkpoint || {};
+                checkpointInterval = checkpoint.intervalMs ?? 0;
+                const options = {
+                    pageSize: args.pageSize,
+                    autoCompaction: compaction.auto,
+                    compactionThreshold: compaction.threshold,
+                    compactionMaxPages: compaction.maxPagesPerRun,
+                    compactionBudgetMs: compaction.budgetMs,
+                    groupCommit: groupCommit ? { maxBytes: groupCommit.maxBytes } : undefined,
+                    checkpointWalBytes: checkpoint.walBytes,
+                    checkpointIntervalMs: checkpoint.intervalMs,
+                    checkpointDirtyPages: checkpoint.dirtyPages,
+                    changeLog: args.changeLog,
+                    walArchive: args.walArchive,
+                    durability: args.durability,
+                    failOnCorruptWal: args.failOnCorruptWal
+                };
+                if (args.inMemory) {
+                    db = wasm.SikioDB.openInMemory(options);
+                } else if (supportsSyncAccessHandle()) {
+                    db = await wasm.SikioDB.openWithOptions(args.name, options);
+                } else {
+                    db = await wasm.SikioDB.openAsync(args.name, options);
+                    buffered = true;
+                }
+                applyDurability(args.durability ?? 'full');
+                return true;
+            case 'put':
+                if (!db) throw new Error('Database not opened');
+                const keyBytes = new Uint8Array(args.key);
+                const valueBytes = new Uint8Array(args.value);
+                await paged(() => db.put(keyBytes, valueBytes));
+                return true;
+            case 'get':
+                if (!db) throw new Error('Database not opened');
+                const getKeyBytes = new Uint8Array(args.key);
+                const result = await paged(() => db.get(getKeyBytes), READ_ONLY);
+                return result ? Array.from(result) : null;
+            case 'delete':
+                if (!db) throw new Error('Database not opened');
+                const delKeyBytes = new Uint8Array(args.key);
+                return await paged(() => db.delete(delKeyBytes));
+            case 'putIfAbsent':
+                if (!db) throw new Error('Database not opened');
+                return await paged(() => db.putIfAbsent(new Uint8Array(args.key), new Uint8Array(args.value)));
+            case 'compareAndSwap':
+                if (!db) throw new Error('Database not opened');
+                return await paged(() => db.compareAndSwap(
+                    new Uint8Array(args.key),
+                    args.expected ? new Uint8Array(args.expected) : undefined,
+                    new Uint8Array(args.value)
+                ));
+            case 'deleteIfEquals':
+                if (!db) throw new Error('Database not opened');
+                return await paged(() => db.deleteIfEquals(new Uint8Array(args.key), new Uint8Array(args.expected)));
+            case 'getAndSet':
+                if (!db) throw new Error('Database not opened');
+                const previous = await paged(() => db.getAndSet(new Uint8Array(args.key), new Uint8Array(args.value)));
+                return previous ? Array.from(previous) : null;
+            case 'flush':
+                if (!db) throw new Error('Database not opened');
+                await paged(() => db.flush());
+                return true;
+            case 'flushIfDue':
+                if (!db) return false;
+                return await paged(() => db.flushIfDue());
+            case 'commitGroup':
+                if (!db) return true;
+                await paged(() => db.commitGroup());
+                return true;
+            case 'checkpoint':
+                if (!db) throw new Error('Database not opened');
+                await paged(() => db.checkpoint());
+                return true;
+            case 'setDurability':
+                if (!db) throw new Error('Database not opened');
+                await paged(() => db.setDurability(args.durability));
+                applyDurability(args.durability);
+                return true;
+            case 'close':
+                applyDurability('full');
+                if (checkpointTimer) {
+                    clearTimeout(checkpointTimer);
+                    checkpointTimer = null;
+                }
+                for (const id of [...transactions.keys()]) {
+                    closeTransaction(id);
+                }
+                if (db) {
+                    await paged(() => db.close());
+                    if (buffered) {
+                        await db.persist();
+                    }
+                    db = null;
+                }
+                return true;
+            case 'putBatch':
+                if (!db) throw new Error('Database not opened');
+                return await paged(() => db.put_batch(args.data));
+            case 'scanPrefix':
+                if (!db) throw new Error('Database not opened');
+                const prefix = new Uint8Array(args.prefix);
+                const results = await paged(() => db.scan_prefix(prefix), READ_ONLY);
+                const pairs = [];
+                for (let i = 0; i < results.length; i += 2) {
+                    pairs.push({
+                        key: Array.from(results[i]),
+                        value: Array.from(results[i + 1])
+                    });
+                }
+                return pairs;
+            case 'putWithTTL':
+                if (!db) throw new Error('Database not opened');
+                await paged(() => db.putWithTTL(new Uint8Array(args.key), new Uint8Array(args.value), BigInt(args.ttl)));
+                return true;
+            case 'verifyIntegrity':
+                if (!db) throw new Error('Database not opened');
+                return await paged(() => db.verify_integrity(), READ_ONLY);
+            case 'estimateUsage':
+                if (!db) throw new Error('Database not opened');
+                return await db.estimateUsage();
+            case 'vacuum':
+                if (!db) throw new Error('Database not opened');
+                return await paged(() => db.vacuum(args.maxPages));
+            case 'vacuumInto': {
+                if (!db) throw new Error('Database not opened');
+                const swap = args.swap || false;
+                const target = await wasm.VacuumTarget.open(args.target, db.pageSize(), !buffered);
+                let stats;
+                try {
+                    stats = await paged(() => db.vacuumInto(target, swap), { target });
+                    if (!swap) {
+                        await target.persist();
+                    }
+                } finally {
+                    target.close();
+                    target.free();
+                }
+                if (swap) {
+                    await removeVacuumTarget(args.target);
+                }
+                return stats;
+            }
+            case 'restoreTo': {
+                if (!db) throw new Error('Database not opened');
+                const target = await wasm.VacuumTarget.open(args.target, db.pageSize(), !buffered);
+                try {
+                    const lsn = await paged(() => db.restoreTo(target, args.point), { readOnly: true, target });
+                    await target.persist();
+                    return Number(lsn);
+                } finally {
+                    target.close();
+                    target.free();
+                }
+            }
+            case 'runIdleCompaction':
+                if (!db) return null;
+                return await paged(() => db.runIdleCompaction());
+            case 'commitVersion':
+                if (!db) throw new Error('Database not opened');
+                return Number(db.commitVersion);
+            case 'compactionStats':
+                if (!db) throw new Error('Database not opened');
+                return db.compactionStats();
+            case 'recoveryReport':
+                if (!db) throw new Error('Database not opened');
+                return db.recoveryReport();
+            case 'changesSince':
+                if (!db) throw new Error('Database not opened');
+                const feed = db.changesSince(BigInt(args.lsn), args.limit || 1000);
+                return {
+                    changes: feed.changes.map(change => ({
+                        key: Array.from(change.key),
+                        op: change.op,
+                        value: change.value ? Array.from(change.value) : null,
+                        lsn: change.lsn
+                    })),
+                    resumeToken: feed.resumeToken
+                };
+            case 'scanRange':
+                if (!db) throw new Error('Database not opened');
+                const startKey = new Uint8Array(args.startKey);
+                const endKey = new Uint8Array(args.endKey);
+                const limit = args.limit || 1000;
+                const scanResults = await paged(() => db.scanRange(startKey, endKey, limit), READ_ONLY);
+                const scanPairs = [];
+                for (let i = 0; i < scanResults.length; i++) {
+                    const entry = scanResults[i];
+                    scanPairs.push({
+                        key: Array.from(entry.key),
+                        value: Array.from(entry.value)
+                    });
+                }
+                return scanPairs;
+            case 'beginTransaction':
+                if (!db) throw new Error('Database not opened');
+                transactions.set(++transactionId, db.beginWriteTxn());
+                return transactionId;
+            case 'txnGet': {
+                const txn = openTransaction(args);
+                const value = await paged(() => db.txnGet(txn, new Uint8Array(args.key)), READ_ONLY);
+                return value ? Array.from(value) : null;
+            }
+            case 'txnScan': {
+                const txn = openTransaction(args);
+                const pairs = await paged(
+                    () => db.txnScan(txn, new Uint8Array(args.startKey), new Uint8Array(args.endKey), args.limit || 1000),
+                    READ_ONLY
+                );
+                return Array.from(pairs, entry => ({
+                    key: Array.from(entry.key),
+                    value: Array.from(entry.value)
+                }));
+            }
+            case 'txnSavepoint':
+                return openTransaction(args).savepoint();
+            case 'txnRelease':
+                openTransaction(args).release(args.savepoint);
+                return true;
+            case 'txnRollbackTo': {
+                const txn = openTransaction(args);
+                txn.rollbackTo(args.savepoint);
+                txn.release(args.savepoint);
+                return true;
+            }
+            case 'abortTransaction':
+                transactions.get(args.id)?.abort();
+                closeTransaction(args.id);
+                return true;
+            case 'commitTransaction': {
+                const txn = openTransaction(args);
+                try {
+                    await paged(() => db.commitTxn(txn));
+                } finally {
+                    closeTransaction(args.id);
+                }
+                return true;
+            }
+            default:
+                throw new Error(`Unknown method: ${method}`);
+        }
+    }
+
+    // Without a sync access handle, pages are loaded on demand. A call that
+    // reads a page that is not loaded yet has its writes undone, waits for
+    // the missing pages and runs again. A vacuum target is undone with it.
+    async function paged(call, { readOnly = false, target = null } = {}) {
+        if (!buffered) {
+            return call();
+        }
+        for (;;) {
+            db.beginOp();
+            target?.beginOp();
+            let result;
+            let error = null;
+            try {
+                result = call();
+            } catch (e) {
+                error = e;
+            }
+            const done = db.endOp(readOnly);
+            target?.endOp(!done);
+            if (done) {
+                if (error) throw error;
+                return result;
+            }
+            do {
+                await db.load();
+            } while (!db.prepareRetry());
+        }
+    }
+
+    function openTransaction({ id, ops = [] }) {
+        if (!db) throw new Error('Database not opened');
+        const txn = transactions.get(id);
+        if (!txn) throw new Error(`Transaction ${id} is not open`);
+        for (const op of ops) {
+            if (op.type === 'put') {
+                txn.put(new Uint8Array(op.key), new Uint8Array(op.value));
+            } else if (op.type === 'delete') {
+                txn.delete(new Uint8Array(op.key));
+            }
+        }
+        return txn;
+    }
+
+    function closeTransaction(id) {
+        const txn = transactions.get(id);
+        if (txn) {
+            transactions.delete(id);
+            txn.free();
+        }
+        return txn;
+    }
+
+    async function removeVacuumTarget(name) {
+        const root = await navigator.storage.getDirectory();
+        const dir = await root.getDirectoryHandle(name);
+        for (const file of ['data.sdb', 'wal.sdb']) {
+            await dir.removeEntry(file);
+        }
+        try {
+            await root.removeEntry(name);
+        } catch (e) {
+            if (e.name !== 'InvalidModificationError') throw e;
+        }
+    }
+
+    async function initWasm(wasmUrl) {
+        const wasmModule = await import(wasmUrl.replace('.wasm', '.js'));
+        await wasmModule.default();
+        wasm = wasmModule;
+        return true;
+    }
+
+    async function run(method, args) {
+        const result = await handleMethod(method, args);
+        const grouped = groupCommitWindow > 0 && GROUPED_METHODS.has(method);
+        const deferred = WRITE_METHODS.has(method) && (grouped || durability !== 'full');
+        if (buffered && db && PERSISTED_METHODS.has(method) && !deferred) {
+            await db.persist();
+        }
+        scheduleCompaction();
+        if (WRITE_METHODS.has(method)) {
+            scheduleCheckpoint();
+        }
+        return result;
+    }
+
+    function scheduleCheckpoint() {
+        if (checkpointTimer || checkpointInterval <= 0 || !db) {
+            return;
+        }
+        checkpointTimer = setTimeout(() => {
+            checkpointTimer = null;
+            enqueue('checkpoint', {}).catch((e) => {
+                console.warn('Scheduled checkpoint failed:', e);
+            });
+        }, checkpointInterval);
+    }
+
+    function scheduleCompaction() {
+        if (compactionTimer) {
+            clearTimeout(compactionTimer);
+            compactionTimer = null;
+        }
+        if (!db || !db.compactionPending()) {
+            return;
+        }
+        compactionTimer = setTimeout(() => {
+            compactionTimer = null;
+            enqueue('runIdleCompaction', {}).catch((e) => {
+                console.warn('Idle compaction failed:', e);
+            });
+        }, compactionIdleDelay);
+    }
+
+    function applyDurability(mode) {
+        if (durabilityTimer) {
+            clearInterval(durabilityTimer);
+            durabilityTimer = null;
+        }
+        durability = typeof mode === 'string' ? mode : 'periodic';
+        if (durability !== 'periodic') {
+            return;
+        }
+        const interval = mode.periodicMs ?? PERIODIC_FLUSH_MS;
+        durabilityTimer = setInterval(() => {
+            enqueue('flushIfDue', {}).catch((e) => {
+                console.warn('Periodic flush failed:', e);
+            });
+        }, Math.max(1, interval / 2));
+    }
+
+    function joinGroup() {
+        if (!buffered && (!db || db.pendingCommitBytes === 0)) {
+            return Promise.resolve();
+        }
+        if (!group) {
+            let resolve, reject;
+            const promise = new Promise((res, rej) => {
+                resolve = res;
+                reject = rej;
+            });
+            group = { promise, resolve, reject };
+            setTimeout(() => {
+                const current = group;
+                group = null;
+                enqueue('commitGroup', {}).then(current.resolve, current.reject);
+            }, groupCommitWindow);
+        }
+        return group.promise;
+    }
+
+    function enqueue(method, args) {
+        const next = queue.then(() => run(method, args));
+        queue = next.catch(() => {});
+        return next;
+    }
+
+    return {
+        call(method, args = {}) {
+            const next = enqueue(method, args);
+            if (groupCommitWindow === 0 || durability !== 'full' || !GROUPED_METHODS.has(method)) {
+                return next;
+            }
+            return next.then((result) => joinGroup().then(() => result));
+        }
+    };
+}
@@ -79,6 +79,28 @@ export class IndexedDBFallback {
         });
     }
 
+    async update(key, apply) {
+        return new Promise((resolve, reject) => {
+            const tx = this.db.transaction(STORE_NAME, 'readwrite');
+            const store = tx.objectStore(STORE_NAME);
+            let result;
+
+            tx.oncomplete = () => resolve(result);
+            tx.onerror = () => reject(tx.error);
+
+            const request = store.get(key);
+            request.onsuccess = () => {
+                const change = apply(request.result ?? null);
+                result = change.result;
+                if (change.value === null) {
+                    store.delete(key);
+                } else if (change.value !== undefined) {
+                    store.put(change.value, key);
+                }
+            };
+        });
+    }
+
     async getMany(keys) {
         return new Promise((resolve, reject) => {
             const tx = this.db.transaction(STORE_NAME, 'readonly');
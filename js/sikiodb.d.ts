@@ -5,14 +5,92 @@ export interface KeyValuePair {
     value: Uint8Array;
 }
 
+export type Durability = 'full' | 'periodic' | 'none' | { periodicMs: number };
+
 export interface OpenOptions {
     wasmUrl?: string;
+    inMemory?: boolean;
+    mainThread?: boolean;
+    pageSize?: 4096 | 8192 | 16384 | 32768;
+    compaction?: {
+        auto?: boolean;
+        threshold?: number;
+        maxPagesPerRun?: number;
+        budgetMs?: number;
+        idleDelayMs?: number;
+    };
+    durability?: Durability;
+    changeLog?: boolean | {
+        maxChanges?: number;
+        maxBytes?: number;
+    };
+    walArchive?: boolean | {
+        retainSegments?: number;
+    };
+    checkpoint?: {
+        walBytes?: number;
+        intervalMs?: number;
+        dirtyPages?: number;
+    };
+    failOnCorruptWal?: boolean;
+    groupCommit?: boolean | {
+        windowMs?: number;
+        maxBytes?: number;
+    };
     compression?: boolean;
     encryption?: {
         key: string;
     };
 }
 
+export interface StorageUsage {
+    usage: number;
+    quota: number;
+    dataBytes: number | null;
+    walBytes: number | null;
+}
+
+export interface VacuumStats {
+    pagesScanned: number;
+    pagesMoved: number;
+    pagesFreed: number;
+    bytesReclaimed: number;
+}
+
+export interface CompactionProgress {
+    pending: boolean;
+    runs: number;
+    pagesMoved: number;
+    pagesFreed: number;
+    bytesReclaimed: number;
+    lastRun: VacuumStats | null;
+}
+
+export interface RecoveryReport {
+    transactionsReplayed: number;
+    opsReplayed: number;
+    opsDiscarded: number;
+    bytesDiscarded: number;
+    stoppedAt: number | null;
+    stopReason: string | null;
+    midLog: boolean;
+    freeListError: string | null;
+    replayedCommits: number[];
+    discardedKeys: Uint8Array[];
+}
+
+export interface ChangeEntry {
+    key: Uint8Array;
+    op: 'put' | 'delete';
+    value: Uint8Array | null;
+    lsn: number;
+}
+
+export interface ChangeBatch {
+    changes: ChangeEntry[];
+    resumeToken: number;
+}
+
 export interface SubscriptionEvent<T = any> {
     type: 'initial' | 'change';
     data: Map<string, T>;
@@ -29,6 +107,9 @@ export interface ChangeRecord<T = any> {
 export interface TransactionContext {
     put(key: DataInput, value: DataInput): void;
     delete(key: DataInput): void;
+    get(key: DataInput): Promise<Uint8Array | null>;
+    scan(startKey: DataInput, endKey: DataInput, limit?: number): Promise<KeyValuePair[]>;
+    transaction<T>(fn: (tx: TransactionContext) => Promise<T> | T): Promise<T>;
 }
 
 export type Operator =
@@ -58,10 +139,13 @@ export class SikioDB {
     static open(name: string, options?: OpenOptions): Promise<SikioDB>;
 
     put(key: DataInput, value: DataInput): Promise<void>;
-    putNoSync(key: DataInput, value: DataInput): Promise<void>;
     get(key: DataInput): Promise<Uint8Array | null>;
     delete(key: DataInput): Promise<boolean>;
     putWithTTL(key: DataInput, value: DataInput, ttlMs: number): Promise<void>;
+    putIfAbsent(key: DataInput, value: DataInput): Promise<boolean>;
+    compareAndSwap(key: DataInput, expected: DataInput | null, value: DataInput): Promise<boolean>;
+    deleteIfEquals(key: DataInput, expected: DataInput): Promise<boolean>;
+    getAndSet(key: DataInput, value: DataInput): Promise<Uint8Array | null>;
 
     flush(): Promise<void>;
     putBatch(entries: Array<{ key: string; value: string }>): Promise<number>;
@@ -79,11 +163,29 @@ export class SikioDB {
     ): () => void;
 
     transaction(fn: (tx: TransactionContext) => Promise<void> | void): Promise<boolean>;
+    transactionWithRetry(
+        fn: (tx: TransactionContext) => Promise<void> | void,
+        options?: { maxAttempts?: number; backoffMs?: number }
+    ): Promise<boolean>;
 
     export(): Promise<string>;
     import(data: string | Record<string, any>): Promise<number>;
 
     verifyIntegrity(): Promise<number[]>;
+    estimateUsage(): Promise<StorageUsage>;
+    vacuum(options?: { maxPages?: number }): Promise<VacuumStats>;
+    /**
+     * With `swap`, the live files are rewritten in place from the compacted copy, logging each
+     * overwritten page as a page image; they are not renamed, since open sync access handles block that.
+     */
+    vacuumInto(targetName: string, options?: { swap?: boolean }): Promise<VacuumStats>;
+    restoreTo(targetName: string, point: { lsn: number } | { timestamp: number }): Promise<number>;
+    compactionStats(): Promise<CompactionProgress>;
+    setDurability(durability: Durability): Promise<void>;
+    commitVersion(): Promise<number>;
+    recoveryReport(): Promise<RecoveryReport | null>;
+    checkpoint(): Promise<void>;
+    changesSince(lsn: number, limit?: number): Promise<ChangeBatch>;
     scanRange(startKey: DataInput, endKey: DataInput, limit?: number): Promise<KeyValuePair[]>;
     scanRangeStream(startKey: DataInput, endKey: DataInput, batchSize?: number): AsyncIterableIterator<KeyValuePair>;
 
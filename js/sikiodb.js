@@ -6,6 +6,7 @@ import { SubscriptionManager } from './subscriptions.js';
 export class SikioDB {
     constructor() {
         this.worker = null;
+        this._engine = null;
         this.pendingCalls = new Map();
         this.callId = 0;
         this._coordinator = null;
@@ -25,9 +26,21 @@ export class SikioDB {
 
             if (isOPFSAvailable()) {
                 try {
-                    await instance._initWorker(options.wasmUrl || '../pkg/sikiodb.js');
+                    await instance._initWorker(
+                        options.wasmUrl || '../pkg/sikiodb.js',
+                        options.mainThread || false
+                    );
                     await instance._call('open', {
                         name,
+                        inMemory: options.inMemory || false,
+                        pageSize: options.pageSize,
+                        compaction: options.compaction,
+                        groupCommit: options.groupCommit,
+                        durability: options.durability,
+                        checkpoint: options.checkpoint,
+                        changeLog: options.changeLog,
+                        walArchive: options.walArchive,
+                        failOnCorruptWal: options.failOnCorruptWal || false,
                         compression: options.compression || false,
                         encryptionKey: options.encryption?.key || null
                     });
@@ -37,6 +50,7 @@ export class SikioDB {
                         instance.worker.terminate();
                         instance.worker = null;
                     }
+                    instance._engine = null;
                     instance.pendingCalls.clear();
                     instance._fallback = new IndexedDBFallback(name);
                     await instance._fallback.open();
@@ -65,16 +79,24 @@ export class SikioDB {
 
         return instance;
     }
-    async _initWorker(wasmUrl) {
+    async _initWorker(wasmUrl, mainThread = false) {
+        if (mainThread || typeof Worker === 'undefined') {
+            const { createEngine } = await import('./engine.js');
+            this._engine = createEngine();
+            await this._call('init', { wasmUrl });
+            return;
+        }
         const workerUrl = new URL('./worker.js?v=' + Date.now(), import.meta.url);
         this.worker = new Worker(workerUrl, { type: 'module' });
         this.worker.onmessage = (e) => {
-            const { id, result, error } = e.data;
+            const { id, result, error, errorName } = e.data;
             const pending = this.pendingCalls.get(id);
             if (pending) {
                 this.pendingCalls.delete(id);
                 if (error) {
-                    pending.reject(new Error(error));
+                    const err = new Error(error);
+                    if (errorName) err.name = errorName;
+                    pending.reject(err);
                 } else {
                     pending.resolve(result);
                 }
@@ -91,6 +113,9 @@ export class SikioDB {
         await this._call('init', { wasmUrl });
     }
     _call(method, args = {}, transfer = []) {
+        if (this._engine) {
+            return this._engine.call(method, args);
+        }
         return new Promise((resolve, reject) => {
             const id = ++this.callId;
             this.pendingCalls.set(id, { resolve, reject });
@@ -163,19 +188,6 @@ export class SikioDB {
         const valueArray = this._toArray(value);
         return this._call('put', { key: keyArray, value: valueArray });
     }
-    async putNoSync(key, value) {
-        if (!this._isLeader && !this._fallback) {
-            return this._coordinator.proxyRequest('putNoSync', { key, value });
-        }
-
-        if (this._fallback) {
-            return this.put(key, value);
-        }
-
-        const keyArray = this._toArray(key);
-        const valueArray = this._toArray(value);
-        return this._call('putNoSync', { key: keyArray, value: valueArray });
-    }
     async get(key) {
         if (!this._isLeader && !this._fallback) {
             const result = await this._coordinator.proxyRequest('get', { key });
@@ -216,6 +228,80 @@ export class SikioDB {
         const keyArray = this._toArray(key);
         return this._call('delete', { key: keyArray });
     }
+    async putIfAbsent(key, value) {
+        return this.compareAndSwap(key, null, value);
+    }
+    async compareAndSwap(key, expected, value) {
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('compareAndSwap', { key, expected, value });
+        }
+
+        if (this._fallback) {
+            return this._fallback.update(this._toFallbackKey(key), (stored) => {
+                if (!this._sameValue(this._fallbackUserValue(stored), expected)) {
+                    return { result: false };
+                }
+                return { value: this._wrapRawValue(this._toArray(value)), result: true };
+            });
+        }
+
+        return this._call('compareAndSwap', {
+            key: this._toArray(key),
+            expected: expected == null ? null : this._toArray(expected),
+            value: this._toArray(value)
+        });
+    }
+    async deleteIfEquals(key, expected) {
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('deleteIfEquals', { key, expected });
+        }
+
+        if (this._fallback) {
+            return this._fallback.update(this._toFallbackKey(key), (stored) => {
+                const current = this._fallbackUserValue(stored);
+                if (current === null || !this._sameValue(current, expected)) {
+                    return { result: false };
+                }
+                return { value: null, result: true };
+            });
+        }
+
+        return this._call('deleteIfEquals', {
+            key: this._toArray(key),
+            expected: this._toArray(expected)
+        });
+    }
+    async getAndSet(key, value) {
+        if (!this._isLeader && !this._fallback) {
+            const result = await this._coordinator.proxyRequest('getAndSet', { key, value });
+            return result ? new Uint8Array(result) : null;
+        }
+
+        if (this._fallback) {
+            return this._fallback.update(this._toFallbackKey(key), (stored) => ({
+                value: this._wrapRawValue(this._toArray(value)),
+                result: this._fallbackUserValue(stored)
+            }));
+        }
+
+        const result = await this._call('getAndSet', {
+            key: this._toArray(key),
+            value: this._toArray(value)
+        });
+        return result ? new Uint8Array(result) : null;
+    }
+    _fallbackUserValue(stored) {
+        if (!stored) {
+            return null;
+        }
+        return this._unwrapStoredValue(stored instanceof Uint8Array ? stored : new Uint8Array(stored));
+    }
+    _sameValue(current, expected) {
+        if (current === null || expected == null) {
+            return current === null && expected == null;
+        }
+        return this._compareBytes(current, this._toArray(expected)) === 0;
+    }
     async putWithTTL(key, value, ttlMs) {
         if (!this._isLeader && !this._fallback) {
             return this._coordinator.proxyRequest('putWithTTL', { key, value, ttlMs });
@@ -419,6 +505,131 @@ export class SikioDB {
 
         return this._call('verifyIntegrity');
     }
+    async estimateUsage() {
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('estimateUsage', {});
+        }
+
+        if (this._fallback) {
+            const { usage = 0, quota = 0 } = await navigator.storage.estimate();
+            return { usage, quota, dataBytes: null, walBytes: null };
+        }
+
+        return this._call('estimateUsage');
+    }
+    async vacuum(options = {}) {
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('vacuum', options);
+        }
+
+        if (this._fallback) {
+            return { pagesScanned: 0, pagesMoved: 0, pagesFreed: 0, bytesReclaimed: 0 };
+        }
+
+        return this._call('vacuum', { maxPages: options.maxPages });
+    }
+    async vacuumInto(targetName, options = {}) {
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('vacuumInto', { targetName, ...options });
+        }
+
+        if (this._fallback) {
+            throw new Error('vacuumInto is not supported by the IndexedDB fallback');
+        }
+
+        return this._call('vacuumInto', { target: targetName, swap: options.swap || false });
+    }
+    async restoreTo(targetName, point) {
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('restoreTo', { targetName, point });
+        }
+
+        if (this._fallback) {
+            throw new Error('restoreTo is not supported by the IndexedDB fallback');
+        }
+
+        return this._call('restoreTo', { target: targetName, point });
+    }
+    async compactionStats() {
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('compactionStats', {});
+        }
+
+        if (this._fallback) {
+            return { pending: false, runs: 0, pagesMoved: 0, pagesFreed: 0, bytesReclaimed: 0, lastRun: null };
+        }
+
+        return this._call('compactionStats');
+    }
+    async changesSince(lsn, limit = 1000) {
+        if (!this._isLeader && !this._fallback) {
+            const result = await this._coordinator.proxyRequest('changesSince', { lsn, limit });
+            return this._toChangeBatch(result);
+        }
+
+        if (this._fallback) {
+            throw new Error('changesSince is not supported by the IndexedDB fallback');
+        }
+
+        const result = await this._call('changesSince', { lsn, limit });
+        return this._toChangeBatch(result);
+    }
+    _toChangeBatch(result) {
+        const toBytes = (bytes) => bytes instanceof Uint8Array ? bytes : new Uint8Array(Object.values(bytes));
+        return {
+            changes: result.changes.map(change => ({
+                key: toBytes(change.key),
+                op: change.op,
+                value: change.value ? toBytes(change.value) : null,
+                lsn: change.lsn
+            })),
+            resumeToken: result.resumeToken
+        };
+    }
+    async checkpoint() {
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('checkpoint', {});
+        }
+
+        if (this._fallback) {
+            return;
+        }
+
+        await this._call('checkpoint');
+    }
+    async recoveryReport() {
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('recoveryReport', {});
+        }
+
+        if (this._fallback) {
+            return null;
+        }
+
+        return this._call('recoveryReport');
+    }
+    async commitVersion() {
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('commitVersion', {});
+        }
+
+        if (this._fallback) {
+            return 0;
+        }
+
+        return this._call('commitVersion');
+    }
+    async setDurability(durability) {
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('setDurability', { durability });
+        }
+
+        if (this._fallback) {
+            return;
+        }
+
+        await this._call('setDurability', { durability });
+    }
     async close() {
         if (this._isLeader && !this._fallback) {
             await this._call('close');
@@ -427,6 +638,7 @@ export class SikioDB {
             this.worker.terminate();
             this.worker = null;
         }
+        this._engine = null;
         if (this._coordinator) {
             this._coordinator.destroy();
             this._coordinator = null;
@@ -454,12 +666,18 @@ export class SikioDB {
         switch (method) {
             case 'put':
                 return this.put(args.key, args.value);
-            case 'putNoSync':
-                return this.putNoSync(args.key, args.value);
             case 'get':
                 return this.get(args.key);
             case 'delete':
                 return this.delete(args.key);
+            case 'putIfAbsent':
+                return this.putIfAbsent(args.key, args.value);
+            case 'compareAndSwap':
+                return this.compareAndSwap(args.key, args.expected, args.value);
+            case 'deleteIfEquals':
+                return this.deleteIfEquals(args.key, args.expected);
+            case 'getAndSet':
+                return this.getAndSet(args.key, args.value);
             case 'putWithTTL':
                 return this.putWithTTL(args.key, args.value, args.ttlMs ?? args.ttl);
             case 'flush':
@@ -470,10 +688,30 @@ export class SikioDB {
                 return this.scanRange(args.startKey, args.endKey, args.limit);
             case 'verifyIntegrity':
                 return this.verifyIntegrity();
-            case 'commitTransaction':
-                return this._commitTransaction(args.ops);
+            case 'estimateUsage':
+                return this.estimateUsage();
+            case 'vacuum':
+                return this.vacuum(args);
+            case 'vacuumInto':
+                return this.vacuumInto(args.targetName, args);
+            case 'restoreTo':
+                return this.restoreTo(args.targetName, args.point);
+            case 'compactionStats':
+                return this.compactionStats();
+            case 'setDurability':
+                return this.setDurability(args.durability);
+            case 'commitVersion':
+                return this.commitVersion();
+            case 'recoveryReport':
+                return this.recoveryReport();
+            case 'checkpoint':
+                return this.checkpoint();
+            case 'changesSince':
+                return this.changesSince(args.lsn, args.limit);
             case 'setMany':
                 return this.setMany(args.entries);
+            case 'import':
+                return this.import(args.data);
             default:
                 if (this._fallback) {
                     return this._executeFallbackMethod(method, args);
@@ -541,22 +779,124 @@ export class SikioDB {
     }
 
     async transaction(fn) {
-        const encoder = new TextEncoder();
-        const ops = [];
+        if (this._fallback) {
+            return this._fallbackTransaction(fn);
+        }
+
+        const id = await this._leaderCall('beginTransaction', {});
+        let ops = [];
+        const takeOps = () => {
+            const sent = ops;
+            ops = [];
+            return sent;
+        };
 
         const tx = {
             put: (key, value) => {
-                ops.push({
-                    type: 'put',
-                    key: typeof key === 'string' ? Array.from(encoder.encode(key)) : Array.from(key),
-                    value: typeof value === 'string' ? Array.from(encoder.encode(value)) : Array.from(value)
-                });
+                ops.push({ type: 'put', key: Array.from(this._toArray(key)), value: Array.from(this._toArray(value)) });
             },
             delete: (key) => {
-                ops.push({
-                    type: 'delete',
-                    key: typeof key === 'string' ? Array.from(encoder.encode(key)) : Array.from(key)
+                ops.push({ type: 'delete', key: Array.from(this._toArray(key)) });
+            },
+            get: async (key) => {
+                const result = await this._leaderCall('txnGet', {
+                    id,
+                    ops: takeOps(),
+                    key: Array.from(this._toArray(key))
+                });
+                return result ? new Uint8Array(result) : null;
+            },
+            scan: async (startKey, endKey, limit = 1000) => {
+                const results = await this._leaderCall('txnScan', {
+                    id,
+                    ops: takeOps(),
+                    startKey: Array.from(this._toArray(startKey)),
+                    endKey: Array.from(this._toArray(endKey)),
+                    limit
                 });
+                return results.map(entry => ({ key: new Uint8Array(entry.key), value: new Uint8Array(entry.value) }));
+            },
+            transaction: async (nested) => {
+                const savepoint = await this._leaderCall('txnSavepoint', { id, ops: takeOps() });
+                let result;
+                try {
+                    result = await nested(tx);
+                } catch (e) {
+                    ops = [];
+                    await this._leaderCall('txnRollbackTo', { id, savepoint });
+                    throw e;
+                }
+                await this._leaderCall('txnRelease', { id, ops: takeOps(), savepoint });
+                return result;
+            }
+        };
+
+        try {
+            await fn(tx);
+        } catch (e) {
+            await this._leaderCall('abortTransaction', { id });
+            throw e;
+        }
+
+        await this._leaderCall('commitTransaction', { id, ops: takeOps() });
+        return true;
+    }
+
+    async _fallbackTransaction(fn) {
+        const ops = [];
+        const pending = new Map();
+
+        const tx = {
+            put: (key, value) => {
+                const keyBytes = Uint8Array.from(this._toArray(key));
+                const valueBytes = Uint8Array.from(this._toArray(value));
+                pending.set(keyBytes.join(','), { key: keyBytes, value: valueBytes });
+                ops.push({ type: 'put', key: keyBytes, value: valueBytes });
+            },
+            delete: (key) => {
+                const keyBytes = Uint8Array.from(this._toArray(key));
+                pending.set(keyBytes.join(','), { key: keyBytes, value: null });
+                ops.push({ type: 'delete', key: keyBytes });
+            },
+            get: async (key) => {
+                const entry = pending.get(this._toArray(key).join(','));
+                if (entry) {
+                    return entry.value;
+                }
+                return this.get(key);
+            },
+            scan: async (startKey, endKey, limit = 1000) => {
+                const startBytes = this._toArray(startKey);
+                const endBytes = this._toArray(endKey);
+                const overlay = [...pending.values()].filter(entry =>
+                    this._compareBytes(entry.key, startBytes) >= 0 &&
+                    this._compareBytes(entry.key, endBytes) <= 0);
+                const committed = await this.scanRange(startBytes, endBytes, limit + overlay.length);
+                const merged = new Map(committed.map(entry => [entry.key.join(','), entry]));
+                for (const entry of overlay) {
+                    if (entry.value) {
+                        merged.set(entry.key.join(','), entry);
+                    } else {
+                        merged.delete(entry.key.join(','));
+                    }
+                }
+                return [...merged.values()]
+                    .sort((a, b) => this._compareBytes(a.key, b.key))
+                    .slice(0, limit);
+            },
+            transaction: async (nested) => {
+                const opsLength = ops.length;
+                const pendingSnapshot = new Map(pending);
+                try {
+                    return await nested(tx);
+                } catch (e) {
+                    ops.length = opsLength;
+                    pending.clear();
+                    for (const [id, entry] of pendingSnapshot) {
+                        pending.set(id, entry);
+                    }
+                    throw e;
+                }
             }
         };
 
@@ -564,33 +904,46 @@ export class SikioDB {
 
         if (ops.length === 0) return true;
 
-        return this._commitTransaction(ops);
+        await this._fallback.transaction(ops.map((op) => {
+            const key = this._toFallbackKey(op.key);
+            if (op.type === 'put') {
+                return { type: 'put', key, value: this._wrapRawValue(op.value) };
+            }
+            return { type: 'delete', key };
+        }));
+        return true;
     }
 
-    async _commitTransaction(ops) {
-        if (!this._isLeader && !this._fallback) {
-            await this._coordinator.proxyRequest('commitTransaction', { ops });
-            return true;
+    async transactionWithRetry(fn, { maxAttempts = 5, backoffMs = 10 } = {}) {
+        for (let attempt = 1; ; attempt++) {
+            try {
+                return await this.transaction(fn);
+            } catch (e) {
+                if (attempt >= maxAttempts || !this._isTransactionConflict(e)) {
+                    throw e;
+                }
+                await new Promise(resolve => setTimeout(resolve, backoffMs * attempt));
+            }
         }
+    }
 
-        if (this._fallback) {
-            const idbOps = ops.map((op) => {
-                const keyBytes = new Uint8Array(op.key);
-                const key = this._toFallbackKey(keyBytes);
-
-                if (op.type === 'put') {
-                    const valueBytes = new Uint8Array(op.value);
-                    return { type: 'put', key, value: this._wrapRawValue(valueBytes) };
-                }
+    _isTransactionConflict(error) {
+        return error?.name === 'TransactionConflict';
+    }
 
-                return { type: 'delete', key };
-            });
-            await this._fallback.transaction(idbOps);
-            return true;
+    async _leaderCall(method, args) {
+        if (!this._isLeader) {
+            return this._coordinator.proxyRequest(method, args);
         }
+        return this._call(method, args);
+    }
 
-        await this._call('commitTransaction', { ops });
-        return true;
+    _compareBytes(a, b) {
+        const length = Math.min(a.length, b.length);
+        for (let i = 0; i < length; i++) {
+            if (a[i] !== b[i]) return a[i] - b[i];
+        }
+        return a.length - b.length;
     }
 
 
@@ -616,17 +969,35 @@ export class SikioDB {
 
     async import(data) {
         const parsed = typeof data === 'string' ? JSON.parse(data) : data;
+        if (!this._isLeader && !this._fallback) {
+            return this._coordinator.proxyRequest('import', { data: parsed });
+        }
         const encoder = new TextEncoder();
+        const pairs = Object.entries(parsed).map(([key, value]) => [
+            encoder.encode(key),
+            Array.isArray(value) ? new Uint8Array(value) : encoder.encode(JSON.stringify(value))
+        ]);
 
-        for (const [key, value] of Object.entries(parsed)) {
-            const keyBytes = encoder.encode(key);
-            const valueBytes = Array.isArray(value)
-                ? new Uint8Array(value)
-                : encoder.encode(JSON.stringify(value));
-            await this.put(keyBytes, valueBytes);
+        if (this._fallback) {
+            for (const [keyBytes, valueBytes] of pairs) {
+                await this.put(keyBytes, valueBytes);
+            }
+            return pairs.length;
         }
 
-        return Object.keys(parsed).length;
+        const size = pairs.reduce((acc, [k, v]) => acc + k.length + v.length + 8, 0);
+        const buffer = new Uint8Array(size);
+        const view = new DataView(buffer.buffer);
+        let offset = 0;
+        for (const [keyBytes, valueBytes] of pairs) {
+            view.setUint32(offset, keyBytes.length, true);
+            buffer.set(keyBytes, offset + 4);
+            offset += 4 + keyBytes.length;
+            view.setUint32(offset, valueBytes.length, true);
+            buffer.set(valueBytes, offset + 4);
+            offset += 4 + valueBytes.length;
+        }
+        return this._call('putBatch', { data: buffer }, [buffer.buffer]);
     }
 
     get isLeader() {
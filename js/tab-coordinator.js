@@ -246,7 +246,8 @@ export class TabCoordinator {
                 type: 'response',
                 requestId: data.requestId,
                 tabId: data.tabId,
-                error: error.message
+                error: error.message,
+                errorName: error.name
             });
         }
     }
@@ -260,7 +261,9 @@ export class TabCoordinator {
         this.pendingRequests.delete(data.requestId);
 
         if (data.error) {
-            pending.reject(new Error(data.error));
+            const error = new Error(data.error);
+            if (data.errorName) error.name = data.errorName;
+            pending.reject(error);
         } else {
             pending.resolve(data.result);
         }
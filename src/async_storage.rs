@@ -0,0 +1,900 @@
+use crate::error::{Result, SikioError};
+use crate::page::DEFAULT_PAGE_SIZE;
+use crate::storage::{
+    call_method, get_opfs_root, get_or_create_directory, get_or_create_file, storage_error,
+    StorageBackend,
+};
+use js_sys::{Object, Reflect, Uint8Array};
+use lru::LruCache;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+const LOADED_PAGES: usize = 4096;
+const READ_AHEAD_PAGES: u64 = 16;
+const MAX_READ_AHEAD_PAGES: u64 = 4096;
+const WAL_LOAD_BYTES: u64 = 1024 * 1024;
+#[allow(async_fn_in_trait)]
+pub trait AsyncStorageBackend {
+    fn page_size(&self) -> usize;
+    async fn read_data(&self, offset: u64, length: usize) -> Result<Vec<u8>>;
+    async fn read_page(&self, page_id: u64) -> Result<Vec<u8>> {
+        let page_size = self.page_size() as u64;
+        let offset = page_id * page_size;
+        if offset + page_size > self.data_size() {
+            return Err(SikioError::IoError(format!(
+                "Page {} beyond file size",
+                page_id
+            )));
+        }
+        self.read_data(offset, page_size as usize).await
+    }
+    async fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()>;
+    async fn append_wal(&mut self, data: &[u8]) -> Result<u64>;
+    async fn read_wal(&self, offset: u64, length: usize) -> Result<Vec<u8>>;
+    async fn flush_data(&mut self) -> Result<()>;
+    async fn flush_wal(&mut self) -> Result<()>;
+    async fn truncate_wal(&mut self) -> Result<()>;
+    async fn truncate_data(&mut self, length: u64) -> Result<()>;
+    fn data_size(&self) -> u64;
+    fn data_page_count(&self) -> u64 {
+        self.data_size() / self.page_size() as u64
+    }
+    fn wal_size(&self) -> u64;
+}
+pub struct AsyncOPFSStorage {
+    data_file: JsValue,
+    wal_file: JsValue,
+    page_size: usize,
+    data_size: u64,
+    wal_size: u64,
+    durable_wal_size: u64,
+    pending_pages: BTreeMap<u64, Vec<u8>>,
+    pending_wal: Vec<u8>,
+    wal_truncated: bool,
+}
+impl AsyncOPFSStorage {
+    pub async fn open(db_name: &str) -> std::result::Result<Self, JsValue> {
+        Self::open_with_page_size(db_name, DEFAULT_PAGE_SIZE).await
+    }
+    pub async fn open_with_page_size(
+        db_name: &str,
+        page_size: usize,
+    ) -> std::result::Result<Self, JsValue> {
+        let root = get_opfs_root().await?;
+        let db_dir = get_or_create_directory(&root, db_name).await?;
+        let data_file: JsValue = get_or_create_file(&db_dir, "data.sdb").await?.into();
+        let wal_file: JsValue = get_or_create_file(&db_dir, "wal.sdb").await?.into();
+        let data_size = file_size(&data_file).await?;
+        let wal_size = file_size(&wal_file).await?;
+        Ok(AsyncOPFSStorage {
+            data_file,
+            wal_file,
+            page_size,
+            data_size,
+            wal_size,
+            durable_wal_size: wal_size,
+            pending_pages: BTreeMap::new(),
+            pending_wal: Vec::new(),
+            wal_truncated: false,
+        })
+    }
+}
+impl AsyncStorageBackend for AsyncOPFSStorage {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+    async fn read_data(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+        let end = offset + length as u64;
+        if end > self.data_size {
+            return Err(SikioError::IoError("Data read beyond size".into()));
+        }
+        let mut data = read_range(&self.data_file, offset, length).await?;
+        let page_size = self.page_size as u64;
+        for (&page_id, bytes) in &self.pending_pages {
+            let page_start = page_id * page_size;
+            let from = page_start.max(offset);
+            let to = (page_start + page_size).min(end);
+            if from < to {
+                data[(from - offset) as usize..(to - offset) as usize].copy_from_slice(
+                    &bytes[(from - page_start) as usize..(to - page_start) as usize],
+                );
+            }
+        }
+        Ok(data)
+    }
+    async fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()> {
+        if data.len() != self.page_size {
+            return Err(SikioError::IoError(format!(
+                "Invalid page size: {}",
+                data.len()
+            )));
+        }
+        self.pending_pages.insert(page_id, data.to_vec());
+        let new_end = (page_id + 1) * self.page_size as u64;
+        if new_end > self.data_size {
+            self.data_size = new_end;
+        }
+        Ok(())
+    }
+    async fn append_wal(&mut self, data: &[u8]) -> Result<u64> {
+        let offset = self.wal_size;
+        self.pending_wal.extend_from_slice(data);
+        self.wal_size += data.len() as u64;
+        Ok(offset)
+    }
+    async fn read_wal(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+        let end = offset + length as u64;
+        if end > self.wal_size {
+            return Err(SikioError::IoError("WAL read beyond size".into()));
+        }
+        let durable = if self.wal_truncated {
+            0
+        } else {
+            self.durable_wal_size
+        };
+        let mut buffer = Vec::with_capacity(length);
+        if offset < durable {
+            let durable_end = end.min(durable);
+            let part = read_range(&self.wal_file, offset, (durable_end - offset) as usize).await?;
+            buffer.extend(part);
+        }
+        if end > durable {
+            let start = offset.saturating_sub(durable) as usize;
+            buffer.extend_from_slice(&self.pending_wal[start..(end - durable) as usize]);
+        }
+        Ok(buffer)
+    }
+    async fn flush_data(&mut self) -> Result<()> {
+        if self.pending_pages.is_empty() {
+            return Ok(());
+        }
+        let writes = self
+            .pending_pages
+            .iter()
+            .map(|(&page_id, bytes)| (page_id * self.page_size as u64, bytes.as_slice()));
+        write_ranges(&self.data_file, true, writes).await?;
+        self.pending_pages.clear();
+        Ok(())
+    }
+    async fn flush_wal(&mut self) -> Result<()> {
+        if self.pending_wal.is_empty() && !self.wal_truncated {
+            return Ok(());
+        }
+        let (keep_existing, position) = if self.wal_truncated {
+            (false, 0)
+        } else {
+            (true, self.durable_wal_size)
+        };
+        let writes = Some((position, self.pending_wal.as_slice()))
+            .into_iter()
+            .filter(|(_, data)| !data.is_empty());
+        write_ranges(&self.wal_file, keep_existing, writes).await?;
+        self.pending_wal.clear();
+        self.wal_truncated = false;
+        self.durable_wal_size = self.wal_size;
+        Ok(())
+    }
+    async fn truncate_wal(&mut self) -> Result<()> {
+        self.pending_wal.clear();
+        self.wal_truncated = true;
+        self.wal_size = 0;
+        Ok(())
+    }
+    async fn truncate_data(&mut self, length: u64) -> Result<()> {
+        if length >= self.data_size {
+            return Ok(());
+        }
+        let first_dropped = length.div_ceil(self.page_size as u64);
+        self.pending_pages
+            .retain(|&page_id, _| page_id < first_dropped);
+        truncate_file(&self.data_file, length).await?;
+        self.data_size = length;
+        Ok(())
+    }
+    fn data_size(&self) -> u64 {
+        self.data_size
+    }
+    fn wal_size(&self) -> u64 {
+        self.wal_size
+    }
+}
+struct Undo {
+    pages: HashMap<u64, Option<Vec<u8>>>,
+    wal_pending: Option<Vec<u8>>,
+    wal_pending_len: usize,
+    wal_kept: u64,
+    wal_rewrite: bool,
+    data_size: u64,
+    backend_data_size: u64,
+    data_truncated_to: Option<u64>,
+}
+struct PagedState {
+    page_size: usize,
+    capacity: usize,
+    data_size: u64,
+    backend_data_size: u64,
+    data_truncated_to: Option<u64>,
+    dirty: BTreeMap<u64, Vec<u8>>,
+    loaded: LruCache<u64, Vec<u8>>,
+    wal_kept: u64,
+    wal_loaded: Vec<u8>,
+    wal_pending: Vec<u8>,
+    wal_rewrite: bool,
+    missing_pages: BTreeSet<u64>,
+    missing_wal: Option<u64>,
+    read_ahead: u64,
+    undo: Option<Undo>,
+}
+impl PagedState {
+    fn wal_size(&self) -> u64 {
+        self.wal_kept + self.wal_pending.len() as u64
+    }
+    fn journal_page(&mut self, page_id: u64) {
+        if let Some(undo) = &mut self.undo {
+            undo.pages
+                .entry(page_id)
+                .or_insert_with(|| self.dirty.get(&page_id).cloned());
+        }
+    }
+    fn journal_wal(&mut self) {
+        if let Some(undo) = &mut self.undo {
+            if undo.wal_pending.is_none() {
+                undo.wal_pending = Some(self.wal_pending.clone());
+            }
+        }
+    }
+}
+fn not_loaded(what: &str) -> SikioError {
+    SikioError::IoError(format!("{} not loaded yet", what))
+}
+/// Storage over an `AsyncStorageBackend` that loads pages on demand.
+pub struct PagedStorage<A: AsyncStorageBackend> {
+    state: Rc<RefCell<PagedState>>,
+    backend: Rc<RefCell<Option<A>>>,
+}
+impl<A: AsyncStorageBackend> Clone for PagedStorage<A> {
+    fn clone(&self) -> Self {
+        PagedStorage {
+            state: self.state.clone(),
+            backend: self.backend.clone(),
+        }
+    }
+}
+impl<A: AsyncStorageBackend> PagedStorage<A> {
+    pub fn new(backend: A) -> Self {
+        Self::with_capacity(backend, LOADED_PAGES)
+    }
+    pub fn with_capacity(backend: A, capacity: usize) -> Self {
+        let state = PagedState {
+            page_size: backend.page_size(),
+            capacity,
+            data_size: backend.data_size(),
+            backend_data_size: backend.data_size(),
+            data_truncated_to: None,
+            dirty: BTreeMap::new(),
+            loaded: LruCache::unbounded(),
+            wal_kept: backend.wal_size(),
+            wal_loaded: Vec::new(),
+            wal_pending: Vec::new(),
+            wal_rewrite: false,
+            missing_pages: BTreeSet::new(),
+            missing_wal: None,
+            read_ahead: READ_AHEAD_PAGES,
+            undo: None,
+        };
+        PagedStorage {
+            state: Rc::new(RefCell::new(state)),
+            backend: Rc::new(RefCell::new(Some(backend))),
+        }
+    }
+    pub fn has_pending(&self) -> bool {
+        let state = self.state.borrow();
+        state.wal_rewrite
+            || !state.dirty.is_empty()
+            || !state.wal_pending.is_empty()
+            || state.data_truncated_to.is_some()
+    }
+    pub fn resident_pages(&self) -> usize {
+        let state = self.state.borrow();
+        state.dirty.len() + state.loaded.len()
+    }
+    fn take_backend(&self) -> Result<A> {
+        self.backend
+            .borrow_mut()
+            .take()
+            .ok_or_else(|| SikioError::IoError("Async storage is busy".into()))
+    }
+    pub async fn load(&self) -> Result<()> {
+        let backend = self.take_backend()?;
+        let result = self.load_from(&backend).await;
+        *self.backend.borrow_mut() = Some(backend);
+        result
+    }
+    // Read-ahead doubles per load within one operation.
+    async fn load_from(&self, backend: &A) -> Result<()> {
+        let (pages, wal_end, read_ahead, wal_start) = {
+            let mut state = self.state.borrow_mut();
+            let read_ahead = state.read_ahead;
+            state.read_ahead = (read_ahead * 2).min(MAX_READ_AHEAD_PAGES);
+            let pages = std::mem::take(&mut state.missing_pages);
+            (
+                pages,
+                state.missing_wal.take(),
+                read_ahead,
+                state.wal_loaded.len() as u64,
+            )
+        };
+        let page_size = backend.page_size() as u64;
+        let backend_size = backend.data_size();
+        let mut runs: Vec<(u64, u64)> = Vec::new();
+        for page_id in pages {
+            let end = (page_id + read_ahead).min(backend_size.div_ceil(page_size));
+            match runs.last_mut() {
+                Some((_, run_end)) if page_id <= *run_end => *run_end = (*run_end).max(end),
+                _ => runs.push((page_id, end)),
+            }
+        }
+        for (start, end) in runs {
+            let offset = start * page_size;
+            let length = (end * page_size).min(backend_size).saturating_sub(offset);
+            if length == 0 {
+                continue;
+            }
+            let bytes = backend.read_data(offset, length as usize).await?;
+            let mut state = self.state.borrow_mut();
+            for (page_id, chunk) in (start..).zip(bytes.chunks(page_size as usize)) {
+                if state.dirty.contains_key(&page_id) {
+                    continue;
+                }
+                let mut page = chunk.to_vec();
+                page.resize(page_size as usize, 0);
+                state.loaded.put(page_id, page);
+            }
+        }
+        if let Some(wal_end) = wal_end {
+            let end = wal_end
+                .max(wal_start + wal_start.max(WAL_LOAD_BYTES))
+                .min(backend.wal_size());
+            if end > wal_start {
+                let bytes = backend
+                    .read_wal(wal_start, (end - wal_start) as usize)
+                    .await?;
+                self.state.borrow_mut().wal_loaded.extend(bytes);
+            }
+        }
+        Ok(())
+    }
+    pub async fn persist(&self) -> Result<()> {
+        let mut backend = self.take_backend()?;
+        let result = self.persist_to(&mut backend).await;
+        *self.backend.borrow_mut() = Some(backend);
+        result
+    }
+    // Appended WAL precedes its pages; a rewritten WAL follows them.
+    async fn persist_to(&self, backend: &mut A) -> Result<()> {
+        let (pages, wal, rewrite, truncate_to) = {
+            let state = self.state.borrow();
+            let pages: Vec<(u64, Vec<u8>)> = state
+                .dirty
+                .iter()
+                .map(|(&page_id, bytes)| (page_id, bytes.clone()))
+                .collect();
+            (
+                pages,
+                state.wal_pending.clone(),
+                state.wal_rewrite,
+                state.data_truncated_to,
+            )
+        };
+        if rewrite {
+            self.write_pages(backend, pages).await?;
+            backend.truncate_wal().await?;
+            self.append_wal(backend, wal, true).await?;
+        } else {
+            self.append_wal(backend, wal, false).await?;
+            self.write_pages(backend, pages).await?;
+        }
+        if let Some(length) = truncate_to {
+            backend.truncate_data(length).await?;
+            self.state.borrow_mut().data_truncated_to = None;
+        }
+        let mut state = self.state.borrow_mut();
+        state.backend_data_size = state.data_size;
+        Ok(())
+    }
+    async fn write_pages(&self, backend: &mut A, pages: Vec<(u64, Vec<u8>)>) -> Result<()> {
+        for (page_id, bytes) in &pages {
+            backend.write_page(*page_id, bytes).await?;
+        }
+        backend.flush_data().await?;
+        let mut state = self.state.borrow_mut();
+        for (page_id, bytes) in pages {
+            state.dirty.remove(&page_id);
+            state.loaded.put(page_id, bytes);
+        }
+        Ok(())
+    }
+    async fn append_wal(&self, backend: &mut A, wal: Vec<u8>, rewrite: bool) -> Result<()> {
+        if !wal.is_empty() {
+            backend.append_wal(&wal).await?;
+        }
+        backend.flush_wal().await?;
+        let mut state = self.state.borrow_mut();
+        state.wal_pending.drain(..wal.len());
+        state.wal_kept = backend.wal_size();
+        if rewrite {
+            state.wal_rewrite = false;
+            state.wal_loaded.clear();
+        }
+        Ok(())
+    }
+}
+impl<A: AsyncStorageBackend> StorageBackend for PagedStorage<A> {
+    fn page_size(&self) -> usize {
+        self.state.borrow().page_size
+    }
+    fn read_data(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+        let mut guard = self.state.borrow_mut();
+        let state = &mut *guard;
+        let end = offset + length as u64;
+        if end > state.data_size {
+            return Err(SikioError::IoError("Data read beyond size".into()));
+        }
+        let page_size = state.page_size as u64;
+        let mut buffer = vec![0u8; length];
+        let mut missing = false;
+        for page_id in offset / page_size..end.div_ceil(page_size) {
+            let page_start = page_id * page_size;
+            let from = page_start.max(offset);
+            let to = (page_start + page_size).min(end);
+            let target = &mut buffer[(from - offset) as usize..(to - offset) as usize];
+            let range = (from - page_start) as usize..(to - page_start) as usize;
+            if let Some(bytes) = state.dirty.get(&page_id) {
+                target.copy_from_slice(&bytes[range]);
+            } else if page_start >= state.backend_data_size {
+                continue;
+            } else if let Some(bytes) = state.loaded.get(&page_id) {
+                target.copy_from_slice(&bytes[range]);
+            } else {
+                state.missing_pages.insert(page_id);
+                missing = true;
+            }
+        }
+        match missing {
+            true => Err(not_loaded("Data page")),
+            false => Ok(buffer),
+        }
+    }
+    fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        if data.len() != state.page_size {
+            return Err(SikioError::IoError(format!(
+                "Invalid page size: {}",
+                data.len()
+            )));
+        }
+        state.journal_page(page_id);
+        state.dirty.insert(page_id, data.to_vec());
+        state.loaded.pop(&page_id);
+        let page_end = (page_id + 1) * state.page_size as u64;
+        state.data_size = state.data_size.max(page_end);
+        Ok(())
+    }
+    fn append_wal(&mut self, data: &[u8]) -> Result<u64> {
+        let mut state = self.state.borrow_mut();
+        let offset = state.wal_size();
+        state.wal_pending.extend_from_slice(data);
+        Ok(offset)
+    }
+    fn read_wal(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+        let mut state = self.state.borrow_mut();
+        let end = offset + length as u64;
+        if end > state.wal_size() {
+            return Err(SikioError::IoError("WAL read beyond size".into()));
+        }
+        let kept = state.wal_kept;
+        let mut buffer = Vec::with_capacity(length);
+        if offset < kept {
+            let kept_end = end.min(kept);
+            if kept_end > state.wal_loaded.len() as u64 {
+                state.missing_wal = Some(state.missing_wal.unwrap_or(0).max(kept_end));
+                return Err(not_loaded("WAL range"));
+            }
+            buffer.extend_from_slice(&state.wal_loaded[offset as usize..kept_end as usize]);
+        }
+        if end > kept {
+            let start = offset.saturating_sub(kept) as usize;
+            buffer.extend_from_slice(&state.wal_pending[start..(end - kept) as usize]);
+        }
+        Ok(buffer)
+    }
+    fn flush_data(&mut self) -> Result<()> {
+        Ok(())
+    }
+    fn flush_wal(&mut self) -> Result<()> {
+        Ok(())
+    }
+    fn truncate_wal_to(&mut self, length: u64) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        if length >= state.wal_size() {
+            return Ok(());
+        }
+        let kept = state.wal_kept;
+        if length < kept && length > state.wal_loaded.len() as u64 {
+            state.missing_wal = Some(state.missing_wal.unwrap_or(0).max(length));
+            return Err(not_loaded("WAL range"));
+        }
+        state.journal_wal();
+        if length >= kept {
+            state.wal_pending.truncate((length - kept) as usize);
+        } else {
+            state.wal_pending = state.wal_loaded[..length as usize].to_vec();
+            state.wal_kept = 0;
+            state.wal_rewrite = true;
+        }
+        Ok(())
+    }
+    fn truncate_data(&mut self, length: u64) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        if length >= state.data_size {
+            return Ok(());
+        }
+        let first_dropped = length.div_ceil(state.page_size as u64);
+        let dropped: Vec<u64> = state
+            .dirty
+            .range(first_dropped..)
+            .map(|(&id, _)| id)
+            .collect();
+        for page_id in dropped {
+            state.journal_page(page_id);
+            state.dirty.remove(&page_id);
+        }
+        let unloaded: Vec<u64> = state
+            .loaded
+            .iter()
+            .map(|(&id, _)| id)
+            .filter(|&id| id >= first_dropped)
+            .collect();
+        for page_id in unloaded {
+            state.loaded.pop(&page_id);
+        }
+        state.data_size = length;
+        state.backend_data_size = state.backend_data_size.min(length);
+        let pending = state.data_truncated_to.get_or_insert(length);
+        *pending = (*pending).min(length);
+        Ok(())
+    }
+    fn data_size(&self) -> u64 {
+        self.state.borrow().data_size
+    }
+    fn wal_size(&self) -> u64 {
+        self.state.borrow().wal_size()
+    }
+    fn begin_op(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.missing_pages.clear();
+        state.missing_wal = None;
+        state.undo = Some(Undo {
+            pages: HashMap::new(),
+            wal_pending: None,
+            wal_pending_len: state.wal_pending.len(),
+            wal_kept: state.wal_kept,
+            wal_rewrite: state.wal_rewrite,
+            data_size: state.data_size,
+            backend_data_size: state.backend_data_size,
+            data_truncated_to: state.data_truncated_to,
+        });
+    }
+    fn undo_op(&mut self) {
+        let mut state = self.state.borrow_mut();
+        let Some(undo) = state.undo.take() else {
+            return;
+        };
+        for (page_id, previous) in undo.pages {
+            match previous {
+                Some(bytes) => state.dirty.insert(page_id, bytes),
+                None => state.dirty.remove(&page_id),
+            };
+        }
+        match undo.wal_pending {
+            Some(pending) => state.wal_pending = pending,
+            None => state.wal_pending.truncate(undo.wal_pending_len),
+        }
+        state.wal_kept = undo.wal_kept;
+        state.wal_rewrite = undo.wal_rewrite;
+        state.data_size = undo.data_size;
+        state.backend_data_size = undo.backend_data_size;
+        state.data_truncated_to = undo.data_truncated_to;
+    }
+    fn end_op(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.undo = None;
+        state.read_ahead = READ_AHEAD_PAGES;
+        state.wal_loaded = Vec::new();
+        while state.loaded.len() > state.capacity {
+            state.loaded.pop_lru();
+        }
+    }
+    fn needs_load(&self) -> bool {
+        let state = self.state.borrow();
+        !state.missing_pages.is_empty() || state.missing_wal.is_some()
+    }
+}
+async fn await_method(
+    obj: &JsValue,
+    method: &str,
+    args: &[JsValue],
+) -> std::result::Result<JsValue, JsValue> {
+    let promise = call_method(obj, method, args)?;
+    JsFuture::from(js_sys::Promise::from(promise)).await
+}
+async fn file_size(handle: &JsValue) -> std::result::Result<u64, JsValue> {
+    let file = await_method(handle, "getFile", &[]).await?;
+    let size = Reflect::get(&file, &"size".into())?;
+    size.as_f64()
+        .map(|s| s as u64)
+        .ok_or_else(|| JsValue::from_str("Expected number"))
+}
+async fn read_range(handle: &JsValue, offset: u64, length: usize) -> Result<Vec<u8>> {
+    let file = await_method(handle, "getFile", &[])
+        .await
+        .map_err(storage_error)?;
+    let start = JsValue::from_f64(offset as f64);
+    let end = JsValue::from_f64((offset + length as u64) as f64);
+    let blob = call_method(&file, "slice", &[start, end]).map_err(storage_error)?;
+    let buffer = await_method(&blob, "arrayBuffer", &[])
+        .await
+        .map_err(storage_error)?;
+    let bytes = Uint8Array::new(&buffer).to_vec();
+    if bytes.len() != length {
+        return Err(SikioError::IoError(format!(
+            "Short read: {} of {} bytes",
+            bytes.len(),
+            length
+        )));
+    }
+    Ok(bytes)
+}
+async fn truncate_file(handle: &JsValue, length: u64) -> Result<()> {
+    let options = Object::new();
+    Reflect::set(&options, &"keepExistingData".into(), &JsValue::TRUE).map_err(storage_error)?;
+    let writable = await_method(handle, "createWritable", &[options.into()])
+        .await
+        .map_err(storage_error)?;
+    if let Err(e) = await_method(&writable, "truncate", &[JsValue::from_f64(length as f64)]).await {
+        let _ = await_method(&writable, "abort", &[]).await;
+        return Err(storage_error(e));
+    }
+    await_method(&writable, "close", &[])
+        .await
+        .map_err(storage_error)?;
+    Ok(())
+}
+async fn write_ranges<'a>(
+    handle: &JsValue,
+    keep_existing: bool,
+    writes: impl Iterator<Item = (u64, &'a [u8])>,
+) -> Result<()> {
+    let options = Object::new();
+    Reflect::set(
+        &options,
+        &"keepExistingData".into(),
+        &JsValue::from_bool(keep_existing),
+    )
+    .map_err(storage_error)?;
+    let writable = await_method(handle, "createWritable", &[options.into()])
+        .await
+        .map_err(storage_error)?;
+    for (position, data) in writes {
+        let params = Object::new();
+        let _ = Reflect::set(&params, &"type".into(), &"write".into());
+        let _ = Reflect::set(
+            &params,
+            &"position".into(),
+            &JsValue::from_f64(position as f64),
+        );
+        let _ = Reflect::set(&params, &"data".into(), &Uint8Array::from(data));
+        if let Err(e) = await_method(&writable, "write", &[params.into()]).await {
+            let _ = await_method(&writable, "abort", &[]).await;
+            return Err(storage_error(e));
+        }
+    }
+    await_method(&writable, "close", &[])
+        .await
+        .map_err(storage_error)?;
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::changelog::{ChangeLogConfig, ChangeOp};
+    use crate::db::{DatabaseConfig, SikioDB};
+    use crate::storage::MemoryStorage;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    struct ReadyStorage(MemoryStorage);
+
+    impl AsyncStorageBackend for ReadyStorage {
+        fn page_size(&self) -> usize {
+            self.0.page_size()
+        }
+        async fn read_data(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+            self.0.read_data(offset, length)
+        }
+        async fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()> {
+            self.0.write_page(page_id, data)
+        }
+        async fn append_wal(&mut self, data: &[u8]) -> Result<u64> {
+            self.0.append_wal(data)
+        }
+        async fn read_wal(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+            self.0.read_wal(offset, length)
+        }
+        async fn flush_data(&mut self) -> Result<()> {
+            self.0.flush_data()
+        }
+        async fn flush_wal(&mut self) -> Result<()> {
+            self.0.flush_wal()
+        }
+        async fn truncate_wal(&mut self) -> Result<()> {
+            self.0.truncate_wal()
+        }
+        async fn truncate_data(&mut self, length: u64) -> Result<()> {
+            self.0.truncate_data(length)
+        }
+        fn data_size(&self) -> u64 {
+            self.0.data_size()
+        }
+        fn wal_size(&self) -> u64 {
+            self.0.wal_size()
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    fn paged_get(
+        db: &mut SikioDB,
+        storage: &PagedStorage<ReadyStorage>,
+        key: &[u8],
+    ) -> Option<Vec<u8>> {
+        let scan = db.run_paged(storage, true, |db| {
+            let mut txn = db.begin_write();
+            db.txn_scan(&mut txn, key, key, 1)
+        });
+        block_on(scan).unwrap().pop().map(|(_, value)| value)
+    }
+
+    #[test]
+    fn paged_storage_persists_through_async_backend() {
+        let disk = MemoryStorage::new();
+        let storage = PagedStorage::new(ReadyStorage(disk.clone()));
+        let config = DatabaseConfig::default();
+        let mut db = block_on(SikioDB::open_paged(storage.clone(), &config)).unwrap();
+        block_on(storage.persist()).unwrap();
+        let mut txn = db.begin_write();
+        txn.put(b"a".to_vec(), b"1".to_vec()).unwrap();
+        txn.put(b"b".to_vec(), vec![5u8; 5000]).unwrap();
+        let commit = db.run_paged(&storage, false, |db| db.commit_transaction(&mut txn));
+        block_on(commit).unwrap();
+        assert!(storage.has_pending());
+        assert!(disk.wal_bytes().is_empty());
+        block_on(storage.persist()).unwrap();
+        assert!(!storage.has_pending());
+        assert!(!disk.wal_bytes().is_empty());
+        drop(db);
+
+        let storage = PagedStorage::new(ReadyStorage(disk.clone()));
+        let mut db = block_on(SikioDB::open_paged(storage.clone(), &config)).unwrap();
+        block_on(storage.persist()).unwrap();
+        assert!(disk.wal_bytes().is_empty());
+        assert_eq!(paged_get(&mut db, &storage, b"a"), Some(b"1".to_vec()));
+        drop(db);
+
+        let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+        assert_eq!(db.get(b"b").unwrap(), Some(vec![5u8; 5000]));
+    }
+
+    #[test]
+    fn paged_storage_loads_only_the_pages_it_reads() {
+        let disk = MemoryStorage::new();
+        let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+        let rows = (0..3000u32).map(|i| (i.to_be_bytes().to_vec(), vec![i as u8; 300]));
+        db.bulk_load(rows).unwrap();
+        db.checkpoint().unwrap();
+        drop(db);
+        let total_pages = disk.data_page_count() as usize;
+
+        let storage = PagedStorage::with_capacity(ReadyStorage(disk.clone()), 32);
+        let config = DatabaseConfig::default();
+        let mut db = block_on(SikioDB::open_paged(storage.clone(), &config)).unwrap();
+        let key = 1234u32.to_be_bytes();
+        assert_eq!(
+            paged_get(&mut db, &storage, &key),
+            Some(vec![1234u32 as u8; 300])
+        );
+        assert!(storage.resident_pages() <= 32);
+        assert!(storage.resident_pages() < total_pages / 4);
+    }
+
+    #[test]
+    fn paged_storage_retries_operations_that_miss_pages() {
+        let disk = MemoryStorage::new();
+        let config = DatabaseConfig {
+            change_log: Some(ChangeLogConfig::default()),
+            ..DatabaseConfig::default()
+        };
+        let storage = PagedStorage::with_capacity(ReadyStorage(disk.clone()), 4);
+        let mut db = block_on(SikioDB::open_paged(storage.clone(), &config)).unwrap();
+        let start = db.commit_version();
+        let mut model: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        let mut rng = 0x2545_F491_4F6C_DD1Du64;
+        let mut next = move |n: u64| {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            rng % n
+        };
+        for step in 0..400u64 {
+            let key = format!("key{:03}", next(150)).into_bytes();
+            match next(10) {
+                0..=5 => {
+                    let value = vec![b'a' + (step % 26) as u8; 50 + next(900) as usize];
+                    let mut txn = db.begin_write();
+                    txn.put(key.clone(), value.clone()).unwrap();
+                    let commit =
+                        db.run_paged(&storage, false, |db| db.commit_transaction(&mut txn));
+                    block_on(commit).unwrap();
+                    model.insert(key, value);
+                }
+                6..=7 => {
+                    let mut txn = db.begin_write();
+                    txn.delete(key.clone()).unwrap();
+                    let commit =
+                        db.run_paged(&storage, false, |db| db.commit_transaction(&mut txn));
+                    block_on(commit).unwrap();
+                    model.remove(&key);
+                }
+                8 => {
+                    assert_eq!(paged_get(&mut db, &storage, &key), model.get(&key).cloned());
+                }
+                _ => {
+                    block_on(db.run_paged(&storage, false, |db| db.checkpoint())).unwrap();
+                }
+            }
+            if next(3) == 0 {
+                block_on(storage.persist()).unwrap();
+            }
+        }
+        for (key, value) in &model {
+            assert_eq!(paged_get(&mut db, &storage, key).as_ref(), Some(value));
+        }
+
+        let mut keys = BTreeSet::new();
+        for change in db.changes_since(start, usize::MAX).unwrap().changes {
+            match change.op {
+                ChangeOp::Put => keys.insert(change.key),
+                ChangeOp::Delete => keys.remove(&change.key),
+            };
+        }
+        assert!(keys.iter().eq(model.keys()));
+
+        block_on(storage.persist()).unwrap();
+        drop(db);
+        let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+        for (key, value) in &model {
+            assert_eq!(db.get(key).unwrap().as_ref(), Some(value));
+        }
+    }
+}
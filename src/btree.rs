@@ -1,10 +1,13 @@
 use crate::error::{Result, SikioError};
-use crate::page::{CellPointer, Page, PAGE_DATA_SIZE, PAGE_TYPE_INTERNAL, PAGE_TYPE_LEAF};
+use crate::freelist::FreeList;
+use crate::page::{
+    page_data_size, CellPointer, Page, DEFAULT_PAGE_SIZE, PAGE_TYPE_INTERNAL, PAGE_TYPE_LEAF,
+};
 #[allow(dead_code)]
 const MIN_KEYS_PER_NODE: usize = 2;
 const CELL_POINTER_SIZE: usize = CellPointer::SIZE;
 const CHILD_POINTER_SIZE: usize = 8;
-const SPLIT_THRESHOLD: usize = PAGE_DATA_SIZE - 64;
+const SPLIT_SLACK: usize = 64;
 #[derive(Debug, Clone)]
 pub struct BTreeNode {
     pub page_id: u64,
@@ -60,7 +63,7 @@ impl BTreeNode {
             ptr_offset += CELL_POINTER_SIZE;
             let cell_start = cell_ptr.offset as usize;
             let cell_len = cell_ptr.length as usize;
-            if cell_start + cell_len > PAGE_DATA_SIZE {
+            if cell_start + cell_len > data.len() {
                 return Err(SikioError::PageCorrupted {
                     page_id: page.header.page_id,
                     reason: "Cell data overflow".into(),
@@ -79,7 +82,7 @@ impl BTreeNode {
                 }
             }
         }
-        if !is_leaf && item_count > 0 {
+        if !is_leaf {
             let rightmost_offset = ptr_offset;
             if rightmost_offset + CHILD_POINTER_SIZE <= data.len() {
                 let rightmost = u64::from_le_bytes(
@@ -97,13 +100,14 @@ impl BTreeNode {
         }
         Ok(node)
     }
-    pub fn to_page(&self) -> Result<Page> {
+    pub fn to_page(&self, page_size: usize) -> Result<Page> {
         let page_type = if self.is_leaf {
             PAGE_TYPE_LEAF
         } else {
             PAGE_TYPE_INTERNAL
         };
-        let mut page = Page::new(self.page_id, page_type);
+        let mut page = Page::new(self.page_id, page_type, page_size);
+        let data_size = page.data.len();
         let ptr_area_size = self.keys.len() * CELL_POINTER_SIZE
             + if !self.is_leaf { CHILD_POINTER_SIZE } else { 0 };
         let mut cells: Vec<Vec<u8>> = Vec::with_capacity(self.keys.len());
@@ -122,10 +126,10 @@ impl BTreeNode {
             total_cells_size += cell.len();
             cells.push(cell);
         }
-        if ptr_area_size + total_cells_size > PAGE_DATA_SIZE {
+        if ptr_area_size + total_cells_size > data_size {
             return Err(SikioError::BTreeOverflow);
         }
-        let mut current_offset = PAGE_DATA_SIZE;
+        let mut current_offset = data_size;
         let mut cell_pointers: Vec<CellPointer> = Vec::with_capacity(self.keys.len());
         for cell in &cells {
             current_offset -= cell.len();
@@ -240,8 +244,8 @@ impl BTreeNode {
         };
         ptr_size + rightmost_size + cells_size
     }
-    pub fn needs_split(&self) -> bool {
-        self.estimated_size() > SPLIT_THRESHOLD
+    pub fn needs_split(&self, page_size: usize) -> bool {
+        self.estimated_size() > page_data_size(page_size) - SPLIT_SLACK
     }
     pub fn split(&mut self) -> (Vec<u8>, BTreeNode) {
         let mid = self.keys.len() / 2;
@@ -323,7 +327,7 @@ impl BTreeNode {
             self.values.push(borrowed_val);
             Ok(right
                 .keys
-                .get(0)
+                .first()
                 .ok_or(SikioError::PageCorrupted {
                     page_id: right.page_id,
                     reason: "Right keys exhausted".into(),
@@ -351,21 +355,21 @@ impl BTreeNode {
 pub struct BTree {
     root_page_id: u64,
     next_page_id: u64,
-    free_page_ids: Vec<u64>,
+    free_list: FreeList,
 }
 impl BTree {
     pub fn new() -> Self {
         BTree {
             root_page_id: 0,
             next_page_id: 2,
-            free_page_ids: Vec::new(),
+            free_list: FreeList::new(DEFAULT_PAGE_SIZE),
         }
     }
     pub fn with_root(root_id: u64, next_id: u64) -> Self {
         BTree {
             root_page_id: root_id,
             next_page_id: next_id,
-            free_page_ids: Vec::new(),
+            free_list: FreeList::new(DEFAULT_PAGE_SIZE),
         }
     }
     pub fn root_page_id(&self) -> u64 {
@@ -375,7 +379,7 @@ impl BTree {
         self.root_page_id = page_id;
     }
     pub fn allocate_page(&mut self) -> u64 {
-        if let Some(id) = self.free_page_ids.pop() {
+        if let Some(id) = self.free_list.allocate() {
             return id;
         }
         let id = self.next_page_id;
@@ -383,11 +387,7 @@ impl BTree {
         id
     }
     pub fn reclaim_page(&mut self, page_id: u64) {
-        let pos = self
-            .free_page_ids
-            .binary_search(&page_id)
-            .unwrap_or_else(|i| i);
-        self.free_page_ids.insert(pos, page_id);
+        self.free_list.release(page_id);
     }
     pub fn next_page_id(&self) -> u64 {
         self.next_page_id
@@ -395,11 +395,17 @@ impl BTree {
     pub fn set_next_page_id(&mut self, id: u64) {
         self.next_page_id = id;
     }
-    pub fn free_page_ids(&self) -> &[u64] {
-        &self.free_page_ids
+    pub fn free_page_count(&self) -> u64 {
+        self.free_list.free_count()
     }
-    pub fn set_free_page_ids(&mut self, ids: Vec<u64>) {
-        self.free_page_ids = ids;
+    pub fn free_list(&self) -> &FreeList {
+        &self.free_list
+    }
+    pub fn free_list_mut(&mut self) -> &mut FreeList {
+        &mut self.free_list
+    }
+    pub fn set_free_list(&mut self, free_list: FreeList) {
+        self.free_list = free_list;
     }
 }
 impl Default for BTree {
@@ -0,0 +1,275 @@
+use crate::btree::BTreeNode;
+use crate::error::{Result, SikioError};
+use crate::page::{OverflowPage, Page};
+use crate::storage::StorageBackend;
+use std::collections::HashSet;
+pub trait PageSink {
+    fn page_size(&self) -> usize;
+    fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()>;
+    fn write_node(&mut self, page: Page) -> Result<()> {
+        self.write_page(page.header.page_id, &page.to_bytes())
+    }
+}
+impl<S: StorageBackend + ?Sized> PageSink for S {
+    fn page_size(&self) -> usize {
+        StorageBackend::page_size(self)
+    }
+    fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()> {
+        StorageBackend::write_page(self, page_id, data)
+    }
+}
+enum Entry {
+    Value(Vec<u8>),
+    Child(u64),
+}
+struct Level {
+    node: BTreeNode,
+    min_key: Vec<u8>,
+    held: Option<(Vec<u8>, BTreeNode)>,
+    written: u64,
+}
+impl Level {
+    fn new(is_leaf: bool) -> Self {
+        let node = match is_leaf {
+            true => BTreeNode::new_leaf(0),
+            false => BTreeNode::new_internal(0),
+        };
+        Level {
+            node,
+            min_key: Vec::new(),
+            held: None,
+            written: 0,
+        }
+    }
+    fn len(&self) -> usize {
+        match self.node.is_leaf {
+            true => self.node.keys.len(),
+            false => self.node.children.len(),
+        }
+    }
+    fn append(&mut self, key: Vec<u8>, entry: Entry) {
+        if self.len() == 0 {
+            self.min_key = key.clone();
+        }
+        match entry {
+            Entry::Value(value) => {
+                self.node.keys.push(key);
+                self.node.values.push(value);
+            }
+            Entry::Child(child) => {
+                if !self.node.children.is_empty() {
+                    self.node.keys.push(key);
+                }
+                self.node.children.push(child);
+            }
+        }
+    }
+    fn pop(&mut self) -> Option<(Vec<u8>, Entry)> {
+        let key = self.node.keys.pop()?;
+        let entry = match self.node.is_leaf {
+            true => Entry::Value(self.node.values.pop()?),
+            false => Entry::Child(self.node.children.pop()?),
+        };
+        Some((key, entry))
+    }
+}
+fn lend_to_last(left: &mut BTreeNode, right: &mut BTreeNode, right_min: &mut Vec<u8>) {
+    while right.is_underflow() && left.can_lend() {
+        let key = match left.keys.pop() {
+            Some(key) => key,
+            None => return,
+        };
+        if left.is_leaf {
+            right.keys.insert(0, key.clone());
+            right
+                .values
+                .insert(0, left.values.pop().unwrap_or_default());
+            *right_min = key;
+        } else {
+            right.keys.insert(0, std::mem::replace(right_min, key));
+            right.children.insert(0, left.children.pop().unwrap_or(0));
+        }
+    }
+}
+pub struct BulkBuilder<'a, S: PageSink + ?Sized> {
+    sink: &'a mut S,
+    page_size: usize,
+    next_page_id: u64,
+    levels: Vec<Level>,
+    linked: HashSet<u64>,
+    last_key: Option<Vec<u8>>,
+}
+impl<'a, S: PageSink + ?Sized> BulkBuilder<'a, S> {
+    pub fn new(sink: &'a mut S, first_page_id: u64) -> Self {
+        let page_size = sink.page_size();
+        BulkBuilder {
+            sink,
+            page_size,
+            next_page_id: first_page_id,
+            levels: Vec::new(),
+            linked: HashSet::new(),
+            last_key: None,
+        }
+    }
+    pub fn with_spine(sink: &'a mut S, first_page_id: u64, spine: Vec<BTreeNode>) -> Self {
+        let mut builder = Self::new(sink, first_page_id);
+        let top = spine.len().saturating_sub(1);
+        for (depth, node) in spine.into_iter().enumerate() {
+            if depth < top {
+                builder.linked.insert(node.page_id);
+            }
+            if node.is_leaf {
+                builder.last_key = node.keys.last().cloned();
+            }
+            builder.levels.push(Level {
+                node,
+                min_key: Vec::new(),
+                held: None,
+                written: 0,
+            });
+        }
+        builder
+    }
+    pub fn next_page_id(&self) -> u64 {
+        self.next_page_id
+    }
+    pub fn add(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+        if self.last_key.as_ref().is_some_and(|last| key <= *last) {
+            return Err(SikioError::Corrupted(
+                "Bulk build keys must be strictly ascending".into(),
+            ));
+        }
+        self.last_key = Some(key.clone());
+        self.push(0, key, Entry::Value(value))
+    }
+    pub fn write_overflow(&mut self, data: &[u8]) -> Result<(u64, u32)> {
+        let max_per_page = OverflowPage::max_data_per_page(self.page_size);
+        let start_page = self.next_page_id;
+        let mut chunks = data.chunks(max_per_page).peekable();
+        while let Some(chunk) = chunks.next() {
+            let mut page = OverflowPage::new(self.allocate_page(), self.page_size);
+            page.data = chunk.to_vec();
+            page.data_length = chunk.len() as u32;
+            if chunks.peek().is_some() {
+                page.next_page = self.next_page_id;
+            }
+            self.sink.write_page(page.page_id, &page.to_bytes())?;
+        }
+        Ok((start_page, data.len() as u32))
+    }
+    pub fn finish(mut self) -> Result<(u64, u64)> {
+        if self.levels.is_empty() {
+            self.levels.push(Level::new(true));
+        }
+        let mut depth = 0;
+        loop {
+            let is_top = depth + 1 == self.levels.len();
+            let level = &mut self.levels[depth];
+            let node = std::mem::replace(&mut level.node, BTreeNode::new_leaf(0));
+            let mut min_key = std::mem::take(&mut level.min_key);
+            match level.held.take() {
+                None if is_top && level.written == 0 => {
+                    let root = self.write_node(node)?;
+                    return Ok((root, self.next_page_id));
+                }
+                None => self.emit(depth, min_key, node)?,
+                Some((held_min, mut held)) => {
+                    let mut node = node;
+                    lend_to_last(&mut held, &mut node, &mut min_key);
+                    self.emit(depth, held_min, held)?;
+                    self.emit(depth, min_key, node)?;
+                }
+            }
+            depth += 1;
+        }
+    }
+    fn allocate_page(&mut self) -> u64 {
+        let page_id = self.next_page_id;
+        self.next_page_id += 1;
+        page_id
+    }
+    fn push(&mut self, depth: usize, key: Vec<u8>, entry: Entry) -> Result<()> {
+        if depth == self.levels.len() {
+            self.levels.push(Level::new(depth == 0));
+        }
+        let page_size = self.page_size;
+        let level = &mut self.levels[depth];
+        level.append(key, entry);
+        if level.len() == 1 || !level.node.needs_split(page_size) {
+            return Ok(());
+        }
+        let (key, entry) = level
+            .pop()
+            .ok_or_else(|| SikioError::Corrupted("Bulk build node is empty".into()))?;
+        let empty = Level::new(level.node.is_leaf).node;
+        let full = std::mem::replace(&mut level.node, empty);
+        let full_min = std::mem::take(&mut level.min_key);
+        let previous = level.held.replace((full_min, full));
+        level.append(key, entry);
+        match previous {
+            Some((min_key, node)) => self.emit(depth, min_key, node),
+            None => Ok(()),
+        }
+    }
+    fn emit(&mut self, depth: usize, min_key: Vec<u8>, node: BTreeNode) -> Result<()> {
+        let page_id = self.write_node(node)?;
+        self.levels[depth].written += 1;
+        if self.linked.contains(&page_id) {
+            return Ok(());
+        }
+        self.push(depth + 1, min_key, Entry::Child(page_id))
+    }
+    fn write_node(&mut self, mut node: BTreeNode) -> Result<u64> {
+        if node.page_id == 0 {
+            node.page_id = self.allocate_page();
+        }
+        let page_id = node.page_id;
+        self.sink.write_node(node.to_page(self.page_size)?)?;
+        Ok(page_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page::{Page, DEFAULT_PAGE_SIZE};
+    use crate::storage::MemoryStorage;
+
+    fn collect(storage: &MemoryStorage, page_id: u64, keys: &mut Vec<Vec<u8>>) -> usize {
+        let page = Page::from_bytes(&storage.read_page(page_id).unwrap()).unwrap();
+        let node = BTreeNode::from_page(&page).unwrap();
+        if node.is_leaf {
+            keys.extend(node.keys);
+            return 1;
+        }
+        assert_eq!(node.children.len(), node.keys.len() + 1);
+        let depths: Vec<usize> = node
+            .children
+            .iter()
+            .map(|&child| collect(storage, child, keys))
+            .collect();
+        assert!(depths.iter().all(|&d| d == depths[0]));
+        depths[0] + 1
+    }
+
+    #[test]
+    fn builds_balanced_tree_with_full_leaves() {
+        let mut storage = MemoryStorage::new();
+        let expected: Vec<Vec<u8>> = (0..20_000u32)
+            .map(|i| format!("key{:06}", i).into_bytes())
+            .collect();
+        let mut builder = BulkBuilder::new(&mut storage, 2);
+        for key in &expected {
+            builder.add(key.clone(), vec![7u8; 40]).unwrap();
+        }
+        assert!(builder.add(b"key000000".to_vec(), Vec::new()).is_err());
+        let (root, next_page_id) = builder.finish().unwrap();
+
+        let mut keys = Vec::new();
+        let depth = collect(&storage, root, &mut keys);
+        assert_eq!(keys, expected);
+        assert_eq!(depth, 3);
+        let per_leaf = DEFAULT_PAGE_SIZE / 60;
+        assert!(next_page_id - 2 < (expected.len() / per_leaf) as u64 * 11 / 10 + 3);
+    }
+}
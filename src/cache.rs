@@ -217,16 +217,16 @@ impl Default for PageCache {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::page::{Page, PAGE_TYPE_LEAF};
+    use crate::page::{Page, DEFAULT_PAGE_SIZE, PAGE_TYPE_LEAF};
 
     #[test]
     fn retains_dirty_pages_on_eviction() {
         let mut cache = PageCache::with_capacity(1);
 
-        let page_a = Page::new(2, PAGE_TYPE_LEAF);
+        let page_a = Page::new(2, PAGE_TYPE_LEAF, DEFAULT_PAGE_SIZE);
         cache.insert(page_a, true);
 
-        let page_b = Page::new(3, PAGE_TYPE_LEAF);
+        let page_b = Page::new(3, PAGE_TYPE_LEAF, DEFAULT_PAGE_SIZE);
         cache.insert(page_b, true);
 
         assert!(cache.contains(2));
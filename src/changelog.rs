@@ -0,0 +1,188 @@
+use crate::error::{Result, SikioError};
+use std::collections::VecDeque;
+const CHANGE_LOG_MAX_CHANGES: usize = 10_000;
+const CHANGE_LOG_MAX_BYTES: usize = 8 * 1024 * 1024;
+const CHANGE_OVERHEAD: usize = 16;
+#[derive(Debug, Clone)]
+pub struct ChangeLogConfig {
+    pub max_changes: usize,
+    pub max_bytes: usize,
+}
+impl Default for ChangeLogConfig {
+    fn default() -> Self {
+        ChangeLogConfig {
+            max_changes: CHANGE_LOG_MAX_CHANGES,
+            max_bytes: CHANGE_LOG_MAX_BYTES,
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOp {
+    Put,
+    Delete,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub lsn: u64,
+    pub op: ChangeOp,
+    pub key: Vec<u8>,
+    pub value: Option<Vec<u8>>,
+}
+impl Change {
+    pub(crate) fn size(&self) -> usize {
+        CHANGE_OVERHEAD + self.key.len() + self.value.as_ref().map_or(0, |v| v.len())
+    }
+}
+#[derive(Debug, Clone)]
+pub struct ChangeBatch {
+    pub changes: Vec<Change>,
+    pub resume_token: u64,
+}
+#[derive(Debug, Clone)]
+pub struct ChangeLog {
+    config: ChangeLogConfig,
+    changes: VecDeque<Change>,
+    bytes: usize,
+    horizon: u64,
+    latest: u64,
+}
+impl ChangeLog {
+    pub fn new(config: ChangeLogConfig, horizon: u64) -> Self {
+        ChangeLog {
+            config,
+            changes: VecDeque::new(),
+            bytes: 0,
+            horizon,
+            latest: horizon,
+        }
+    }
+    pub fn config(&self) -> &ChangeLogConfig {
+        &self.config
+    }
+    pub fn horizon(&self) -> u64 {
+        self.horizon
+    }
+    pub fn latest(&self) -> u64 {
+        self.latest
+    }
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+    pub fn record(&mut self, lsn: u64, changes: impl IntoIterator<Item = Change>) {
+        self.latest = self.latest.max(lsn);
+        for change in changes {
+            self.bytes += change.size();
+            self.changes.push_back(change);
+        }
+        self.trim();
+    }
+    pub fn reset(&mut self, horizon: u64) {
+        self.changes.clear();
+        self.bytes = 0;
+        self.horizon = horizon;
+        self.latest = horizon;
+    }
+    pub fn truncate_after(&mut self, lsn: u64) {
+        while self.changes.back().is_some_and(|change| change.lsn > lsn) {
+            if let Some(change) = self.changes.pop_back() {
+                self.bytes -= change.size();
+            }
+        }
+        self.latest = self.latest.min(lsn).max(self.horizon);
+    }
+    fn trim(&mut self) {
+        while self.changes.len() > self.config.max_changes || self.bytes > self.config.max_bytes {
+            let lsn = match self.changes.front() {
+                Some(change) => change.lsn,
+                None => break,
+            };
+            while self.changes.front().is_some_and(|change| change.lsn == lsn) {
+                if let Some(change) = self.changes.pop_front() {
+                    self.bytes -= change.size();
+                }
+            }
+            self.horizon = lsn;
+        }
+    }
+    pub fn changes_since(&self, lsn: u64, limit: usize) -> Result<ChangeBatch> {
+        if lsn < self.horizon {
+            return Err(SikioError::ChangeLogExpired {
+                requested: lsn,
+                horizon: self.horizon,
+            });
+        }
+        let start = self.changes.partition_point(|change| change.lsn <= lsn);
+        let mut changes: Vec<Change> = Vec::new();
+        let mut resume_token = self.latest.max(lsn);
+        for change in self.changes.range(start..) {
+            let last = changes.last().map(|last| last.lsn);
+            if changes.len() >= limit && last != Some(change.lsn) {
+                resume_token = last.unwrap_or(lsn);
+                break;
+            }
+            changes.push(change.clone());
+        }
+        Ok(ChangeBatch {
+            changes,
+            resume_token,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(lsn: u64, key: &[u8]) -> Change {
+        Change {
+            lsn,
+            op: ChangeOp::Put,
+            key: key.to_vec(),
+            value: Some(b"v".to_vec()),
+        }
+    }
+
+    #[test]
+    fn batches_never_split_a_commit_and_resume_after_it() {
+        let mut log = ChangeLog::new(ChangeLogConfig::default(), 0);
+        log.record(3, [put(3, b"a")]);
+        log.record(6, [put(6, b"b"), put(6, b"c")]);
+        log.record(8, [put(8, b"d")]);
+        log.record(9, []);
+
+        let batch = log.changes_since(0, 2).unwrap();
+        let keys: Vec<_> = batch.changes.iter().map(|c| c.key.clone()).collect();
+        assert_eq!(keys, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+        assert_eq!(batch.resume_token, 6);
+
+        let batch = log.changes_since(batch.resume_token, 2).unwrap();
+        assert_eq!(batch.changes, vec![put(8, b"d")]);
+        let batch = log.changes_since(batch.resume_token, 2).unwrap();
+        assert!(batch.changes.is_empty());
+        assert_eq!(batch.resume_token, 9);
+    }
+
+    #[test]
+    fn trimming_moves_the_horizon_by_whole_commits() {
+        let config = ChangeLogConfig {
+            max_changes: 2,
+            ..ChangeLogConfig::default()
+        };
+        let mut log = ChangeLog::new(config, 1);
+        log.record(3, [put(3, b"a"), put(3, b"b")]);
+        log.record(5, [put(5, b"c")]);
+        assert_eq!(log.len(), 1);
+        assert_eq!(log.horizon(), 3);
+        assert!(matches!(
+            log.changes_since(1, 10),
+            Err(SikioError::ChangeLogExpired { horizon: 3, .. })
+        ));
+        assert_eq!(
+            log.changes_since(3, 10).unwrap().changes,
+            vec![put(5, b"c")]
+        );
+    }
+}
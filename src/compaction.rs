@@ -1,7 +1,8 @@
 use crate::btree::BTree;
-use crate::page::PAGE_SIZE;
+#[derive(Debug, Clone)]
 pub struct CompactionStats {
     pub pages_scanned: u64,
+    pub pages_moved: u64,
     pub pages_freed: u64,
     pub bytes_reclaimed: u64,
 }
@@ -9,38 +10,99 @@ impl CompactionStats {
     pub fn new() -> Self {
         CompactionStats {
             pages_scanned: 0,
+            pages_moved: 0,
             pages_freed: 0,
             bytes_reclaimed: 0,
         }
     }
 }
+impl Default for CompactionStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 pub fn estimate_fragmentation(btree: &BTree) -> f64 {
     let total_pages = btree.next_page_id();
-    let free_pages = btree.free_page_ids().len() as u64;
+    let free_pages = btree.free_page_count();
     if total_pages == 0 {
         return 0.0;
     }
     (free_pages as f64 / total_pages as f64) * 100.0
 }
-pub fn reclaim_free_pages(btree: &mut BTree) -> CompactionStats {
+pub fn reclaim_free_pages(btree: &mut BTree, page_size: usize) -> CompactionStats {
     let mut stats = CompactionStats::new();
-    let free_count = btree.free_page_ids().len();
-    stats.pages_freed = free_count as u64;
-    stats.bytes_reclaimed = free_count as u64 * PAGE_SIZE as u64;
+    let free_count = btree.free_page_count();
+    stats.pages_freed = free_count;
+    stats.bytes_reclaimed = free_count * page_size as u64;
     stats
 }
+pub fn plan_relocations(
+    units: &[Vec<u64>],
+    free_ids: &[u64],
+    max_pages: u64,
+) -> Vec<(usize, Vec<u64>)> {
+    let mut order: Vec<usize> = (0..units.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(units[i].iter().max().copied()));
+    let mut free = free_ids.to_vec();
+    free.sort_unstable();
+    let mut next_free = 0;
+    let mut budget = max_pages;
+    let mut plan = Vec::new();
+    for unit in order {
+        let pages = &units[unit];
+        let highest = match pages.iter().max() {
+            Some(&highest) => highest,
+            None => continue,
+        };
+        if next_free >= free.len() || free[next_free] >= highest {
+            break;
+        }
+        if pages.len() as u64 > budget || free.len() - next_free < pages.len() {
+            break;
+        }
+        let dests = &free[next_free..next_free + pages.len()];
+        if dests[dests.len() - 1] >= highest {
+            continue;
+        }
+        plan.push((unit, dests.to_vec()));
+        next_free += pages.len();
+        budget -= pages.len() as u64;
+    }
+    plan
+}
 pub fn should_compact(btree: &BTree, threshold_percent: f64) -> bool {
     estimate_fragmentation(btree) > threshold_percent
 }
+#[derive(Debug, Clone)]
 pub struct VacuumConfig {
     pub fragmentation_threshold: f64,
     pub max_pages_per_run: u64,
+    pub time_budget_ms: u64,
 }
 impl Default for VacuumConfig {
     fn default() -> Self {
         VacuumConfig {
             fragmentation_threshold: 20.0,
             max_pages_per_run: 1000,
+            time_budget_ms: 50,
         }
     }
 }
+#[derive(Debug, Clone, Default)]
+pub struct CompactionProgress {
+    pub pending: bool,
+    pub runs: u64,
+    pub pages_moved: u64,
+    pub pages_freed: u64,
+    pub bytes_reclaimed: u64,
+    pub last_run: Option<CompactionStats>,
+}
+impl CompactionProgress {
+    pub fn record(&mut self, stats: &CompactionStats) {
+        self.runs += 1;
+        self.pages_moved += stats.pages_moved;
+        self.pages_freed += stats.pages_freed;
+        self.bytes_reclaimed += stats.bytes_reclaimed;
+        self.last_run = Some(stats.clone());
+    }
+}
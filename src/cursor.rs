@@ -2,17 +2,7 @@ use crate::btree::BTreeNode;
 use crate::cache::PageCache;
 use crate::error::Result;
 use crate::page::Page;
-use crate::storage::OPFSStorage;
-
-trait CursorStorage {
-    fn read_page(&self, page_id: u64) -> Result<Vec<u8>>;
-}
-
-impl CursorStorage for OPFSStorage {
-    fn read_page(&self, page_id: u64) -> Result<Vec<u8>> {
-        OPFSStorage::read_page(self, page_id)
-    }
-}
+use crate::storage::StorageBackend;
 #[derive(Debug, Clone, Copy)]
 struct StackEntry {
     page_id: u64,
@@ -46,10 +36,15 @@ impl CursorState {
         self.current_value = None;
     }
 }
+impl Default for CursorState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 pub fn cursor_first(
     state: &mut CursorState,
     root_page_id: u64,
-    storage: &OPFSStorage,
+    storage: &dyn StorageBackend,
     cache: &mut PageCache,
 ) -> Result<bool> {
     cursor_first_internal(state, root_page_id, storage, cache)
@@ -57,7 +52,7 @@ pub fn cursor_first(
 pub fn cursor_last(
     state: &mut CursorState,
     root_page_id: u64,
-    storage: &OPFSStorage,
+    storage: &dyn StorageBackend,
     cache: &mut PageCache,
 ) -> Result<bool> {
     cursor_last_internal(state, root_page_id, storage, cache)
@@ -66,27 +61,27 @@ pub fn cursor_seek(
     state: &mut CursorState,
     key: &[u8],
     root_page_id: u64,
-    storage: &OPFSStorage,
+    storage: &dyn StorageBackend,
     cache: &mut PageCache,
 ) -> Result<bool> {
     cursor_seek_internal(state, key, root_page_id, storage, cache)
 }
 pub fn cursor_next(
     state: &mut CursorState,
-    storage: &OPFSStorage,
+    storage: &dyn StorageBackend,
     cache: &mut PageCache,
 ) -> Result<bool> {
     cursor_next_internal(state, storage, cache, true)
 }
 pub fn cursor_prev(
     state: &mut CursorState,
-    storage: &OPFSStorage,
+    storage: &dyn StorageBackend,
     cache: &mut PageCache,
 ) -> Result<bool> {
     cursor_prev_internal(state, storage, cache)
 }
 
-fn cursor_first_internal<S: CursorStorage>(
+fn cursor_first_internal<S: StorageBackend + ?Sized>(
     state: &mut CursorState,
     root_page_id: u64,
     storage: &S,
@@ -99,7 +94,7 @@ fn cursor_first_internal<S: CursorStorage>(
     descend_leftmost(state, root_page_id, storage, cache)
 }
 
-fn cursor_last_internal<S: CursorStorage>(
+fn cursor_last_internal<S: StorageBackend + ?Sized>(
     state: &mut CursorState,
     root_page_id: u64,
     storage: &S,
@@ -112,7 +107,7 @@ fn cursor_last_internal<S: CursorStorage>(
     descend_rightmost(state, root_page_id, storage, cache)
 }
 
-fn cursor_seek_internal<S: CursorStorage>(
+fn cursor_seek_internal<S: StorageBackend + ?Sized>(
     state: &mut CursorState,
     key: &[u8],
     root_page_id: u64,
@@ -171,7 +166,7 @@ fn cursor_seek_internal<S: CursorStorage>(
     }
 }
 
-fn cursor_next_internal<S: CursorStorage>(
+fn cursor_next_internal<S: StorageBackend + ?Sized>(
     state: &mut CursorState,
     storage: &S,
     cache: &mut PageCache,
@@ -236,7 +231,7 @@ fn cursor_next_internal<S: CursorStorage>(
     }
 }
 
-fn cursor_prev_internal<S: CursorStorage>(
+fn cursor_prev_internal<S: StorageBackend + ?Sized>(
     state: &mut CursorState,
     storage: &S,
     cache: &mut PageCache,
@@ -294,7 +289,7 @@ fn cursor_prev_internal<S: CursorStorage>(
     }
 }
 
-fn descend_leftmost<S: CursorStorage>(
+fn descend_leftmost<S: StorageBackend + ?Sized>(
     state: &mut CursorState,
     mut page_id: u64,
     storage: &S,
@@ -331,7 +326,7 @@ fn descend_leftmost<S: CursorStorage>(
     }
 }
 
-fn descend_rightmost<S: CursorStorage>(
+fn descend_rightmost<S: StorageBackend + ?Sized>(
     state: &mut CursorState,
     mut page_id: u64,
     storage: &S,
@@ -370,7 +365,7 @@ fn descend_rightmost<S: CursorStorage>(
     }
 }
 
-fn load_node_for_cursor<S: CursorStorage>(
+fn load_node_for_cursor<S: StorageBackend + ?Sized>(
     page_id: u64,
     storage: &S,
     cache: &mut PageCache,
@@ -390,32 +385,12 @@ mod tests {
     use super::*;
     use crate::btree::BTreeNode;
     use crate::cache::PageCache;
-    use crate::error::SikioError;
-    use std::collections::HashMap;
-
-    struct MemoryStorage {
-        pages: HashMap<u64, Vec<u8>>,
-    }
-
-    impl MemoryStorage {
-        fn new() -> Self {
-            MemoryStorage {
-                pages: HashMap::new(),
-            }
-        }
+    use crate::page::DEFAULT_PAGE_SIZE;
+    use crate::storage::MemoryStorage;
 
-        fn put_node(&mut self, node: &BTreeNode) {
-            let page = node.to_page().unwrap();
-            self.pages.insert(node.page_id, page.to_bytes());
-        }
-    }
-
-    impl CursorStorage for MemoryStorage {
-        fn read_page(&self, page_id: u64) -> Result<Vec<u8>> {
-            self.pages.get(&page_id).cloned().ok_or_else(|| {
-                SikioError::IoError(format!("Missing page {}", page_id))
-            })
-        }
+    fn put_node(storage: &mut MemoryStorage, node: &BTreeNode) {
+        let page = node.to_page(DEFAULT_PAGE_SIZE).unwrap();
+        storage.write_page(node.page_id, &page.to_bytes()).unwrap();
     }
 
     fn bytes(s: &str) -> Vec<u8> {
@@ -430,17 +405,17 @@ mod tests {
         let mut leaf1 = BTreeNode::new_leaf(3);
         leaf1.keys = vec![bytes("a"), bytes("b")];
         leaf1.values = vec![bytes("va"), bytes("vb")];
-        storage.put_node(&leaf1);
+        put_node(&mut storage, &leaf1);
 
         let mut leaf2 = BTreeNode::new_leaf(4);
         leaf2.keys = vec![bytes("c"), bytes("d")];
         leaf2.values = vec![bytes("vc"), bytes("vd")];
-        storage.put_node(&leaf2);
+        put_node(&mut storage, &leaf2);
 
         let mut root = BTreeNode::new_internal(2);
         root.keys = vec![bytes("c")];
         root.children = vec![3, 4];
-        storage.put_node(&root);
+        put_node(&mut storage, &root);
 
         let mut state = CursorState::new();
         assert!(cursor_first_internal(&mut state, 2, &storage, &mut cache).unwrap());
@@ -464,17 +439,17 @@ mod tests {
         let mut leaf1 = BTreeNode::new_leaf(3);
         leaf1.keys = vec![bytes("a"), bytes("b")];
         leaf1.values = vec![bytes("va"), bytes("vb")];
-        storage.put_node(&leaf1);
+        put_node(&mut storage, &leaf1);
 
         let mut leaf2 = BTreeNode::new_leaf(4);
         leaf2.keys = vec![bytes("c"), bytes("d")];
         leaf2.values = vec![bytes("vc"), bytes("vd")];
-        storage.put_node(&leaf2);
+        put_node(&mut storage, &leaf2);
 
         let mut root = BTreeNode::new_internal(2);
         root.keys = vec![bytes("c")];
         root.children = vec![3, 4];
-        storage.put_node(&root);
+        put_node(&mut storage, &root);
 
         let mut state = CursorState::new();
         assert!(cursor_seek_internal(&mut state, b"c", 2, &storage, &mut cache).unwrap());
@@ -489,17 +464,17 @@ mod tests {
         let mut leaf1 = BTreeNode::new_leaf(3);
         leaf1.keys = vec![bytes("a"), bytes("b")];
         leaf1.values = vec![bytes("va"), bytes("vb")];
-        storage.put_node(&leaf1);
+        put_node(&mut storage, &leaf1);
 
         let mut leaf2 = BTreeNode::new_leaf(4);
         leaf2.keys = vec![bytes("c"), bytes("d")];
         leaf2.values = vec![bytes("vc"), bytes("vd")];
-        storage.put_node(&leaf2);
+        put_node(&mut storage, &leaf2);
 
         let mut root = BTreeNode::new_internal(2);
         root.keys = vec![bytes("c")];
         root.children = vec![3, 4];
-        storage.put_node(&root);
+        put_node(&mut storage, &root);
 
         let mut state = CursorState::new();
         assert!(cursor_last_internal(&mut state, 2, &storage, &mut cache).unwrap());
@@ -526,7 +501,7 @@ mod tests {
         let mut leaf = BTreeNode::new_leaf(2);
         leaf.keys = vec![bytes("a"), bytes("b")];
         leaf.values = vec![bytes("va"), bytes("vb")];
-        storage.put_node(&leaf);
+        put_node(&mut storage, &leaf);
 
         let mut state = CursorState::new();
         assert!(!cursor_seek_internal(&mut state, b"z", 2, &storage, &mut cache).unwrap());
@@ -1,28 +1,73 @@
+use crate::async_storage::{AsyncOPFSStorage, AsyncStorageBackend, PagedStorage};
 use crate::btree::{BTree, BTreeNode};
+use crate::bulk::{BulkBuilder, PageSink};
 use crate::cache::PageCache;
+use crate::changelog::{Change, ChangeBatch, ChangeLog, ChangeLogConfig, ChangeOp};
+use crate::compaction::{
+    plan_relocations, should_compact, CompactionProgress, CompactionStats, VacuumConfig,
+};
 use crate::compression::{compress, decompress};
 use crate::error::{Result, SikioError};
-use crate::page::{validate_key_value, OverflowPage, Page, OVERFLOW_THRESHOLD, PAGE_SIZE};
+use crate::freelist::FreeList;
+use crate::page::{
+    overflow_threshold, validate_key_value, validate_page_size, OverflowPage, Page,
+    DEFAULT_PAGE_SIZE, SUPPORTED_PAGE_SIZES,
+};
 use crate::range::{prefix_to_range, RangeBound};
-use crate::storage::OPFSStorage;
-use crate::transaction::{ReadTransaction, TransactionOp, WriteTransaction};
-use crate::wal::{WalEntry, WalOperation, WalReader};
+use crate::storage::{storage_estimate, MemoryStorage, OPFSStorage, StorageBackend};
+use crate::time::now_ms;
+use crate::transaction::{ReadTransaction, Savepoint, TransactionOp, WriteTransaction};
+use crate::wal::{RecoveryReport, WalCursor, WalEntry, WalOperation};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use wasm_bindgen::prelude::*;
 const WAL_CHECKPOINT_THRESHOLD: u64 = 50 * 1024 * 1024;
+const WAL_ARCHIVE_RETAIN_SEGMENTS: usize = 64;
 const METADATA_PAGE_ID_1: u64 = 0;
 const METADATA_PAGE_ID_2: u64 = 1;
+const IMAGE_BATCH_PAGES: usize = 256;
 const METADATA_MAGIC: u64 = 0x53494B494F4442;
+const METADATA_MAGIC_V2: u64 = 0x0253494B494F4442;
 const VAL_TYPE_RAW: u8 = 0x00;
 const VAL_TYPE_TTL: u8 = 0x01;
 const OVERFLOW_MARKER_PREFIX: u8 = 0xFF;
 const OVERFLOW_MARKER_SIZE: usize = 13;
 const BATCH_PAIRS_INITIAL_CAPACITY: usize = 1000;
+const BULK_LOAD_MIN_ENTRIES: usize = 64;
+const GROUP_COMMIT_MAX_BYTES: usize = 1024 * 1024;
+const PERIODIC_FLUSH_MS: u64 = 1000;
 fn wrap_raw_value(value: &[u8]) -> Vec<u8> {
     let mut wrapped = Vec::with_capacity(1 + value.len());
     wrapped.push(VAL_TYPE_RAW);
     wrapped.extend_from_slice(value);
     wrapped
 }
+fn unwrap_value(value: &[u8]) -> Option<Vec<u8>> {
+    match value.first() {
+        Some(&VAL_TYPE_RAW) => Some(value[1..].to_vec()),
+        Some(&VAL_TYPE_TTL) if value.len() >= 9 => Some(value[9..].to_vec()),
+        _ => None,
+    }
+}
+fn wal_change(lsn: u64, entry: &WalEntry) -> Change {
+    let op = match entry.operation {
+        WalOperation::Put => ChangeOp::Put,
+        _ => ChangeOp::Delete,
+    };
+    Change {
+        lsn,
+        op,
+        key: entry.key.clone(),
+        value: entry.value.as_deref().and_then(unwrap_value),
+    }
+}
+fn child_index(node: &BTreeNode, key: &[u8]) -> usize {
+    let pos = node.find_key_position(key);
+    match node.keys.get(pos) {
+        Some(separator) if separator.as_slice() == key => pos + 1,
+        _ => pos,
+    }
+}
 fn wrap_ttl_value(value: &[u8], expiry: u64) -> Vec<u8> {
     let mut wrapped = Vec::with_capacity(1 + 8 + value.len());
     wrapped.push(VAL_TYPE_TTL);
@@ -32,23 +77,398 @@ fn wrap_ttl_value(value: &[u8], expiry: u64) -> Vec<u8> {
 }
 #[wasm_bindgen]
 pub struct SikioDB {
-    storage: OPFSStorage,
+    storage: Box<dyn StorageBackend>,
     btree: BTree,
     cache: PageCache,
-    wal_sequence: u64,
+    lsn: u64,
+    commit_lsn: u64,
+    generation: u64,
+    imaged_pages: HashSet<u64>,
+    auto_compaction: bool,
+    vacuum_config: VacuumConfig,
+    compaction: CompactionProgress,
+    compacting: bool,
+    group_commit: Option<GroupCommitConfig>,
+    pending_wal: Vec<u8>,
+    pending_changes: Vec<(u64, Vec<Change>)>,
+    durability: Durability,
+    last_wal_flush: u64,
+    checkpoint_policy: CheckpointPolicy,
+    last_checkpoint: u64,
+    wal_archive: Option<WalArchiveConfig>,
+    changes: Option<ChangeLog>,
+    recovery: RecoveryReport,
+    fail_on_corrupt_wal: bool,
+    paged: Option<PagedStorage<AsyncOPFSStorage>>,
+    op: Option<OpMark>,
+}
+// State a retried write restores after reloading from storage.
+struct OpMark {
+    compaction: CompactionProgress,
+    latest: Option<u64>,
+    retrying: bool,
+    undone: Option<(Option<ChangeLog>, RecoveryReport)>,
 }
 impl Drop for SikioDB {
     fn drop(&mut self) {
-        if let Err(_e) = self.flush() {}
+        if self.durability == Durability::None {
+            return;
+        }
+        if let Err(_e) = self.flush_internal() {}
+    }
+}
+// Rebuilds the change log from WAL entries streamed oldest first.
+struct ChangeLogReplay {
+    log: ChangeLog,
+    previous: Option<u64>,
+    pending: Vec<Change>,
+}
+impl ChangeLogReplay {
+    fn new(config: ChangeLogConfig) -> Self {
+        ChangeLogReplay {
+            log: ChangeLog::new(config, 0),
+            previous: None,
+            pending: Vec::new(),
+        }
+    }
+    fn feed(&mut self, entry: &WalEntry) {
+        match self.previous {
+            Some(previous) if entry.sequence < previous => return,
+            Some(previous) if entry.sequence <= previous + 1 => {}
+            _ => {
+                self.log.reset(entry.sequence - 1);
+                self.pending.clear();
+            }
+        }
+        self.previous = Some(entry.sequence);
+        match entry.operation {
+            WalOperation::Checkpoint => self.pending.clear(),
+            WalOperation::Commit => {
+                let changes = std::mem::take(&mut self.pending);
+                let changes = changes.into_iter().map(|change| Change {
+                    lsn: entry.sequence,
+                    ..change
+                });
+                self.log.record(entry.sequence, changes);
+            }
+            WalOperation::Put | WalOperation::Delete => {
+                self.pending.push(wal_change(entry.sequence, entry));
+            }
+            WalOperation::PageImage => {}
+        }
+    }
+    fn finish(mut self, lsn: u64) -> ChangeLog {
+        if self.previous.is_none_or(|previous| previous < lsn) {
+            self.log.reset(lsn);
+        }
+        self.log
+    }
+}
+// Grouped writes are readable at once but durable only after the flush.
+#[derive(Debug, Clone)]
+pub struct GroupCommitConfig {
+    pub max_bytes: usize,
+}
+impl Default for GroupCommitConfig {
+    fn default() -> Self {
+        GroupCommitConfig {
+            max_bytes: GROUP_COMMIT_MAX_BYTES,
+        }
+    }
+}
+#[derive(Debug, Clone)]
+pub struct CheckpointPolicy {
+    pub wal_bytes: u64,
+    pub interval_ms: Option<u64>,
+    pub dirty_pages: Option<usize>,
+}
+impl Default for CheckpointPolicy {
+    fn default() -> Self {
+        CheckpointPolicy {
+            wal_bytes: WAL_CHECKPOINT_THRESHOLD,
+            interval_ms: None,
+            dirty_pages: None,
+        }
+    }
+}
+#[derive(Debug, Clone)]
+pub struct WalArchiveConfig {
+    pub retain_segments: usize,
+}
+impl Default for WalArchiveConfig {
+    fn default() -> Self {
+        WalArchiveConfig {
+            retain_segments: WAL_ARCHIVE_RETAIN_SEGMENTS,
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestorePoint {
+    Lsn(u64),
+    Timestamp(u64),
+}
+impl RestorePoint {
+    fn from_js(value: &JsValue) -> std::result::Result<Self, JsValue> {
+        if let Some(lsn) = js_sys::Reflect::get(value, &"lsn".into())?.as_f64() {
+            return Ok(RestorePoint::Lsn(lsn as u64));
+        }
+        match js_sys::Reflect::get(value, &"timestamp".into())?.as_f64() {
+            Some(time) => Ok(RestorePoint::Timestamp(time as u64)),
+            None => Err(JsValue::from_str("Restore point needs lsn or timestamp")),
+        }
+    }
+}
+// Periodic(ms) flushes the WAL on a write once the interval has passed; with
+// no writes coming in, the caller drives flush_if_due from a timer. None
+// leaves the WAL unflushed until a checkpoint, including when dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    #[default]
+    Full,
+    Periodic(u64),
+    None,
+}
+impl Durability {
+    fn from_js(value: &JsValue) -> std::result::Result<Option<Self>, JsValue> {
+        if let Some(mode) = value.as_string() {
+            return match mode.as_str() {
+                "full" => Ok(Some(Durability::Full)),
+                "periodic" => Ok(Some(Durability::Periodic(PERIODIC_FLUSH_MS))),
+                "none" => Ok(Some(Durability::None)),
+                _ => Err(JsValue::from_str(&format!("Unknown durability: {}", mode))),
+            };
+        }
+        if value.is_object() {
+            let interval = js_sys::Reflect::get(value, &"periodicMs".into())?;
+            return match interval.as_f64() {
+                Some(interval) => Ok(Some(Durability::Periodic(interval as u64))),
+                None => Err(JsValue::from_str("Durability object needs periodicMs")),
+            };
+        }
+        Ok(None)
+    }
+}
+pub struct DatabaseConfig {
+    pub page_size: usize,
+    pub auto_compaction: bool,
+    pub vacuum: VacuumConfig,
+    pub group_commit: Option<GroupCommitConfig>,
+    pub durability: Durability,
+    pub checkpoint: CheckpointPolicy,
+    pub wal_archive: Option<WalArchiveConfig>,
+    pub change_log: Option<ChangeLogConfig>,
+    pub fail_on_corrupt_wal: bool,
+}
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            page_size: DEFAULT_PAGE_SIZE,
+            auto_compaction: false,
+            vacuum: VacuumConfig::default(),
+            group_commit: None,
+            durability: Durability::Full,
+            checkpoint: CheckpointPolicy::default(),
+            wal_archive: None,
+            change_log: None,
+            fail_on_corrupt_wal: false,
+        }
+    }
+}
+impl DatabaseConfig {
+    fn from_js(options: &JsValue) -> std::result::Result<Self, JsValue> {
+        let mut config = DatabaseConfig::default();
+        if options.is_object() {
+            if let Some(page_size) = js_sys::Reflect::get(options, &"pageSize".into())?.as_f64() {
+                config.page_size = page_size as usize;
+            }
+            if let Some(auto) = js_sys::Reflect::get(options, &"autoCompaction".into())?.as_bool() {
+                config.auto_compaction = auto;
+            }
+            if let Some(threshold) =
+                js_sys::Reflect::get(options, &"compactionThreshold".into())?.as_f64()
+            {
+                config.vacuum.fragmentation_threshold = threshold;
+            }
+            if let Some(max_pages) =
+                js_sys::Reflect::get(options, &"compactionMaxPages".into())?.as_f64()
+            {
+                config.vacuum.max_pages_per_run = max_pages as u64;
+            }
+            if let Some(budget) =
+                js_sys::Reflect::get(options, &"compactionBudgetMs".into())?.as_f64()
+            {
+                config.vacuum.time_budget_ms = budget as u64;
+            }
+            let group = js_sys::Reflect::get(options, &"groupCommit".into())?;
+            if group.is_object() {
+                let mut group_commit = GroupCommitConfig::default();
+                let max_bytes = js_sys::Reflect::get(&group, &"maxBytes".into())?;
+                if let Some(max_bytes) = max_bytes.as_f64() {
+                    group_commit.max_bytes = max_bytes as usize;
+                }
+                config.group_commit = Some(group_commit);
+            } else if group.as_bool() == Some(true) {
+                config.group_commit = Some(GroupCommitConfig::default());
+            }
+            if let Some(wal_bytes) =
+                js_sys::Reflect::get(options, &"checkpointWalBytes".into())?.as_f64()
+            {
+                config.checkpoint.wal_bytes = wal_bytes as u64;
+            }
+            if let Some(interval) =
+                js_sys::Reflect::get(options, &"checkpointIntervalMs".into())?.as_f64()
+            {
+                config.checkpoint.interval_ms = Some(interval as u64);
+            }
+            if let Some(dirty_pages) =
+                js_sys::Reflect::get(options, &"checkpointDirtyPages".into())?.as_f64()
+            {
+                config.checkpoint.dirty_pages = Some(dirty_pages as usize);
+            }
+            let change_log = js_sys::Reflect::get(options, &"changeLog".into())?;
+            if change_log.is_object() {
+                let mut config_log = ChangeLogConfig::default();
+                let max_changes = js_sys::Reflect::get(&change_log, &"maxChanges".into())?;
+                if let Some(max_changes) = max_changes.as_f64() {
+                    config_log.max_changes = max_changes as usize;
+                }
+                let max_bytes = js_sys::Reflect::get(&change_log, &"maxBytes".into())?;
+                if let Some(max_bytes) = max_bytes.as_f64() {
+                    config_log.max_bytes = max_bytes as usize;
+                }
+                config.change_log = Some(config_log);
+            } else if change_log.as_bool() == Some(true) {
+                config.change_log = Some(ChangeLogConfig::default());
+            }
+            let archive = js_sys::Reflect::get(options, &"walArchive".into())?;
+            if archive.is_object() {
+                let mut wal_archive = WalArchiveConfig::default();
+                let retain = js_sys::Reflect::get(&archive, &"retainSegments".into())?;
+                if let Some(retain) = retain.as_f64() {
+                    wal_archive.retain_segments = retain as usize;
+                }
+                config.wal_archive = Some(wal_archive);
+            } else if archive.as_bool() == Some(true) {
+                config.wal_archive = Some(WalArchiveConfig::default());
+            }
+            let durability = js_sys::Reflect::get(options, &"durability".into())?;
+            if let Some(durability) = Durability::from_js(&durability)? {
+                config.durability = durability;
+            }
+            let strict = js_sys::Reflect::get(options, &"failOnCorruptWal".into())?;
+            if let Some(strict) = strict.as_bool() {
+                config.fail_on_corrupt_wal = strict;
+            }
+        }
+        Ok(config)
+    }
+}
+fn compaction_stats_to_js(stats: &CompactionStats) -> std::result::Result<JsValue, JsValue> {
+    let result = js_sys::Object::new();
+    for (name, value) in [
+        ("pagesScanned", stats.pages_scanned),
+        ("pagesMoved", stats.pages_moved),
+        ("pagesFreed", stats.pages_freed),
+        ("bytesReclaimed", stats.bytes_reclaimed),
+    ] {
+        js_sys::Reflect::set(&result, &name.into(), &JsValue::from_f64(value as f64))?;
+    }
+    Ok(result.into())
+}
+// Errors reach JS as message strings, except the ones callers branch on: those
+// become Error objects with a stable name.
+fn error_to_js(e: &SikioError) -> JsValue {
+    let name = match e {
+        SikioError::TransactionConflict { .. } => "TransactionConflict",
+        _ => return JsValue::from_str(&e.to_string()),
+    };
+    let error = js_sys::Error::new(&e.to_string());
+    error.set_name(name);
+    error.into()
+}
+fn recovery_report_to_js(report: &RecoveryReport) -> std::result::Result<JsValue, JsValue> {
+    let result = js_sys::Object::new();
+    for (name, value) in [
+        ("transactionsReplayed", report.transactions_replayed),
+        ("opsReplayed", report.ops_replayed),
+        ("opsDiscarded", report.ops_discarded),
+        ("bytesDiscarded", report.bytes_discarded),
+    ] {
+        js_sys::Reflect::set(&result, &name.into(), &JsValue::from_f64(value as f64))?;
+    }
+    let stopped_at = match report.stopped_at {
+        Some(offset) => JsValue::from_f64(offset as f64),
+        None => JsValue::NULL,
+    };
+    js_sys::Reflect::set(&result, &"stoppedAt".into(), &stopped_at)?;
+    let stop_reason = match &report.stop_reason {
+        Some(e) => JsValue::from_str(&e.to_string()),
+        None => JsValue::NULL,
+    };
+    js_sys::Reflect::set(&result, &"stopReason".into(), &stop_reason)?;
+    js_sys::Reflect::set(&result, &"midLog".into(), &report.mid_log.into())?;
+    let free_list_error = match &report.free_list_error {
+        Some(e) => JsValue::from_str(&e.to_string()),
+        None => JsValue::NULL,
+    };
+    js_sys::Reflect::set(&result, &"freeListError".into(), &free_list_error)?;
+    let commits = js_sys::Array::new();
+    for lsn in &report.replayed_commits {
+        commits.push(&JsValue::from_f64(*lsn as f64));
+    }
+    js_sys::Reflect::set(&result, &"replayedCommits".into(), &commits)?;
+    let keys = js_sys::Array::new();
+    for key in &report.discarded_keys {
+        keys.push(&js_sys::Uint8Array::from(key.as_slice()));
+    }
+    js_sys::Reflect::set(&result, &"discardedKeys".into(), &keys)?;
+    Ok(result.into())
+}
+struct CacheSink<'a> {
+    cache: &'a mut PageCache,
+    page_size: usize,
+    lsn: u64,
+}
+impl PageSink for CacheSink<'_> {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+    fn write_page(&mut self, page_id: u64, _data: &[u8]) -> Result<()> {
+        Err(SikioError::IoError(format!(
+            "Raw page {} cannot be written through the page cache",
+            page_id
+        )))
     }
+    fn write_node(&mut self, mut page: Page) -> Result<()> {
+        page.header.lsn = self.lsn;
+        self.cache.insert(page, true);
+        Ok(())
+    }
+}
+type SubtreeSplit = (Option<u64>, Option<(Vec<u8>, u64)>);
+enum PageOwner {
+    Root,
+    Child { parent: u64, index: usize },
+    Overflow { leaf: u64, index: usize },
+}
+struct LiveUnit {
+    pages: Vec<u64>,
+    owner: PageOwner,
 }
 struct Metadata {
     root_page_id: u64,
     next_page_id: u64,
-    wal_sequence: u64,
+    lsn: u64,
+    generation: u64,
+    page_size: usize,
+    freelist_root: u64,
+    free_page_count: u64,
     free_page_ids: Vec<u64>,
+    compaction: CompactionProgress,
 }
 const METADATA_HEADER_SIZE: usize = 40;
+const METADATA_V2_HEADER_SIZE: usize = 128;
+const METADATA_PROBE_SIZE: usize = 44;
 impl Metadata {
     fn checksum_old(bytes: &[u8]) -> Option<u32> {
         if bytes.len() < 36 {
@@ -69,42 +489,63 @@ impl Metadata {
         hasher.update(&bytes[36..]);
         Some(hasher.finalize())
     }
-    fn to_bytes(&self) -> [u8; PAGE_SIZE] {
-        let mut bytes = [0u8; PAGE_SIZE];
-        bytes[0..8].copy_from_slice(&METADATA_MAGIC.to_le_bytes());
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; self.page_size];
+        bytes[0..8].copy_from_slice(&METADATA_MAGIC_V2.to_le_bytes());
         bytes[8..16].copy_from_slice(&self.root_page_id.to_le_bytes());
         bytes[16..24].copy_from_slice(&self.next_page_id.to_le_bytes());
-        bytes[24..32].copy_from_slice(&self.wal_sequence.to_le_bytes());
-        let free_count = self.free_page_ids.len() as u32;
+        bytes[24..32].copy_from_slice(&self.lsn.to_le_bytes());
+        let free_count = self.free_page_count.min(u32::MAX as u64) as u32;
         bytes[36..40].copy_from_slice(&free_count.to_le_bytes());
-        let max_ids = (PAGE_SIZE - METADATA_HEADER_SIZE) / 8;
-        let ids_to_write = self.free_page_ids.len().min(max_ids);
-        for (i, &id) in self.free_page_ids.iter().take(ids_to_write).enumerate() {
-            let offset = METADATA_HEADER_SIZE + i * 8;
-            bytes[offset..offset + 8].copy_from_slice(&id.to_le_bytes());
-        }
+        bytes[40..44].copy_from_slice(&(self.page_size as u32).to_le_bytes());
+        bytes[44..52].copy_from_slice(&self.freelist_root.to_le_bytes());
+        bytes[52] = self.compaction.pending as u8;
+        bytes[56..64].copy_from_slice(&self.compaction.runs.to_le_bytes());
+        bytes[64..72].copy_from_slice(&self.compaction.pages_moved.to_le_bytes());
+        bytes[72..80].copy_from_slice(&self.compaction.pages_freed.to_le_bytes());
+        bytes[80..88].copy_from_slice(&self.compaction.bytes_reclaimed.to_le_bytes());
+        bytes[88..96].copy_from_slice(&self.generation.to_le_bytes());
         if let Some(checksum) = Self::checksum_new(&bytes) {
             bytes[32..36].copy_from_slice(&checksum.to_le_bytes());
         }
         bytes
     }
+    fn stored_page_size(bytes: &[u8]) -> Option<usize> {
+        match u64::from_le_bytes(bytes.get(0..8)?.try_into().ok()?) {
+            METADATA_MAGIC => Some(DEFAULT_PAGE_SIZE),
+            METADATA_MAGIC_V2 => {
+                let page_size = u32::from_le_bytes(bytes.get(40..44)?.try_into().ok()?) as usize;
+                validate_page_size(page_size).ok().map(|_| page_size)
+            }
+            _ => None,
+        }
+    }
     fn from_bytes(bytes: &[u8]) -> Option<Self> {
         if bytes.len() < METADATA_HEADER_SIZE {
             return None;
         }
         let magic = u64::from_le_bytes(bytes[0..8].try_into().ok()?);
-        if magic != METADATA_MAGIC {
-            return None;
-        }
         let stored_checksum = u32::from_le_bytes(bytes[32..36].try_into().ok()?);
-        let computed_old = Self::checksum_old(bytes)?;
         let computed_new = Self::checksum_new(bytes)?;
-        if stored_checksum != computed_old && stored_checksum != computed_new {
-            return None;
-        }
+        let (header_size, page_size) = match magic {
+            METADATA_MAGIC => {
+                let computed_old = Self::checksum_old(bytes)?;
+                if stored_checksum != computed_old && stored_checksum != computed_new {
+                    return None;
+                }
+                (METADATA_HEADER_SIZE, DEFAULT_PAGE_SIZE)
+            }
+            METADATA_MAGIC_V2 => {
+                if bytes.len() < METADATA_V2_HEADER_SIZE || stored_checksum != computed_new {
+                    return None;
+                }
+                (METADATA_V2_HEADER_SIZE, Self::stored_page_size(bytes)?)
+            }
+            _ => return None,
+        };
         let root_page_id = u64::from_le_bytes(bytes[8..16].try_into().ok()?);
         let next_page_id = u64::from_le_bytes(bytes[16..24].try_into().ok()?);
-        let wal_sequence = u64::from_le_bytes(bytes[24..32].try_into().ok()?);
+        let lsn = u64::from_le_bytes(bytes[24..32].try_into().ok()?);
         if next_page_id < 2 {
             return None;
         }
@@ -115,11 +556,32 @@ impl Metadata {
             return None;
         }
         let free_count = u32::from_le_bytes(bytes[36..40].try_into().ok()?) as usize;
-        let max_ids = (PAGE_SIZE - METADATA_HEADER_SIZE) / 8;
-        let ids_to_read = free_count.min(max_ids);
+        let (freelist_root, compaction, generation) = match magic {
+            METADATA_MAGIC_V2 => (
+                u64::from_le_bytes(bytes[44..52].try_into().ok()?),
+                CompactionProgress {
+                    pending: bytes[52] != 0,
+                    runs: u64::from_le_bytes(bytes[56..64].try_into().ok()?),
+                    pages_moved: u64::from_le_bytes(bytes[64..72].try_into().ok()?),
+                    pages_freed: u64::from_le_bytes(bytes[72..80].try_into().ok()?),
+                    bytes_reclaimed: u64::from_le_bytes(bytes[80..88].try_into().ok()?),
+                    last_run: None,
+                },
+                u64::from_le_bytes(bytes[88..96].try_into().ok()?),
+            ),
+            _ => (0, CompactionProgress::default(), 0),
+        };
+        if freelist_root != 0 && (freelist_root < 2 || freelist_root >= next_page_id) {
+            return None;
+        }
+        let max_ids = (page_size.saturating_sub(header_size)) / 8;
+        let ids_to_read = match freelist_root {
+            0 => free_count.min(max_ids),
+            _ => 0,
+        };
         let mut free_page_ids = Vec::with_capacity(ids_to_read);
         for i in 0..ids_to_read {
-            let offset = METADATA_HEADER_SIZE + i * 8;
+            let offset = header_size + i * 8;
             if offset + 8 <= bytes.len() {
                 let id = u64::from_le_bytes(bytes[offset..offset + 8].try_into().ok()?);
                 free_page_ids.push(id);
@@ -131,8 +593,13 @@ impl Metadata {
         Some(Metadata {
             root_page_id,
             next_page_id,
-            wal_sequence,
+            lsn,
+            generation,
+            page_size,
+            freelist_root,
+            free_page_count: free_count as u64,
             free_page_ids,
+            compaction,
         })
     }
 }
@@ -140,24 +607,287 @@ impl Metadata {
 impl SikioDB {
     #[wasm_bindgen(js_name = open)]
     pub async fn js_open(db_name: &str) -> std::result::Result<SikioDB, JsValue> {
-        Self::open_internal(db_name)
+        Self::open_internal(db_name, &DatabaseConfig::default())
             .await
             .map_err(|e| JsValue::from_str(&e.to_string()))
     }
-    async fn open_internal(db_name: &str) -> Result<SikioDB> {
-        let storage = OPFSStorage::open(db_name)
+    #[wasm_bindgen(js_name = openWithOptions)]
+    pub async fn js_open_with_options(
+        db_name: &str,
+        options: JsValue,
+    ) -> std::result::Result<SikioDB, JsValue> {
+        let config = DatabaseConfig::from_js(&options)?;
+        Self::open_internal(db_name, &config)
+            .await
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    #[wasm_bindgen(js_name = openInMemory)]
+    pub fn js_open_in_memory(options: JsValue) -> std::result::Result<SikioDB, JsValue> {
+        let config = DatabaseConfig::from_js(&options)?;
+        Self::open_in_memory_with_config(&config).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    async fn open_internal(db_name: &str, config: &DatabaseConfig) -> Result<SikioDB> {
+        validate_page_size(config.page_size)?;
+        // One spare slot so a new segment can be written before the oldest is
+        // dropped.
+        let slots = config
+            .wal_archive
+            .as_ref()
+            .map_or(0, |archive| archive.retain_segments.max(1) + 1);
+        let storage = OPFSStorage::open_with_archive(db_name, config.page_size, slots)
             .await
             .map_err(|e| SikioError::IoError(format!("{:?}", e)))?;
-        let mut db = SikioDB {
-            storage,
-            btree: BTree::new(),
-            cache: PageCache::new(),
-            wal_sequence: 0,
-        };
-        db.recover()?;
+        Self::open_with_storage_config(Box::new(storage), config)
+    }
+    #[wasm_bindgen(js_name = openAsync)]
+    pub async fn js_open_async(
+        db_name: &str,
+        options: JsValue,
+    ) -> std::result::Result<SikioDB, JsValue> {
+        let config = DatabaseConfig::from_js(&options)?;
+        Self::open_async_internal(db_name, &config)
+            .await
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    async fn open_async_internal(db_name: &str, config: &DatabaseConfig) -> Result<SikioDB> {
+        validate_page_size(config.page_size)?;
+        let backend = AsyncOPFSStorage::open_with_page_size(db_name, config.page_size)
+            .await
+            .map_err(|e| SikioError::IoError(format!("{:?}", e)))?;
+        let storage = PagedStorage::new(backend);
+        let mut db = Self::open_paged(storage.clone(), config).await?;
+        storage.persist().await?;
+        db.paged = Some(storage);
         Ok(db)
     }
-    fn recover(&mut self) -> Result<()> {
+    #[wasm_bindgen(js_name = estimateUsage)]
+    pub fn estimate_usage(&self) -> js_sys::Promise {
+        let data_bytes = self.storage.data_size();
+        let wal_bytes = self.storage.wal_size();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let (usage, quota) = storage_estimate().await?;
+            let result = js_sys::Object::new();
+            js_sys::Reflect::set(&result, &"usage".into(), &JsValue::from_f64(usage))?;
+            js_sys::Reflect::set(&result, &"quota".into(), &JsValue::from_f64(quota))?;
+            js_sys::Reflect::set(
+                &result,
+                &"dataBytes".into(),
+                &JsValue::from_f64(data_bytes as f64),
+            )?;
+            js_sys::Reflect::set(
+                &result,
+                &"walBytes".into(),
+                &JsValue::from_f64(wal_bytes as f64),
+            )?;
+            Ok(result.into())
+        })
+    }
+    #[wasm_bindgen(js_name = vacuum)]
+    pub fn js_vacuum(&mut self, max_pages: Option<u32>) -> std::result::Result<JsValue, JsValue> {
+        let mut config = VacuumConfig::default();
+        if let Some(max_pages) = max_pages {
+            config.max_pages_per_run = max_pages as u64;
+        }
+        let stats = self
+            .vacuum_with_config(&config)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        compaction_stats_to_js(&stats)
+    }
+    #[wasm_bindgen(js_name = vacuumInto)]
+    pub fn js_vacuum_into(
+        &mut self,
+        target: &mut VacuumTarget,
+        swap: bool,
+    ) -> std::result::Result<JsValue, JsValue> {
+        let result = target.with_storage(|storage| {
+            let stats = self.vacuum_into_storage(storage)?;
+            if swap {
+                self.replace_with_image(storage)?;
+            }
+            Ok(stats)
+        });
+        let stats = result.map_err(|e| JsValue::from_str(&e.to_string()))?;
+        compaction_stats_to_js(&stats)
+    }
+    #[wasm_bindgen(js_name = restoreTo)]
+    pub fn js_restore_to(
+        &mut self,
+        target: &mut VacuumTarget,
+        point: JsValue,
+    ) -> std::result::Result<u64, JsValue> {
+        let point = RestorePoint::from_js(&point)?;
+        let base = target
+            .take_storage()
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let restored = self
+            .restore_to(base, point)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(restored.commit_lsn)
+    }
+    #[wasm_bindgen(js_name = compactionPending)]
+    pub fn compaction_pending(&self) -> bool {
+        self.compaction.pending
+    }
+    #[wasm_bindgen(js_name = runIdleCompaction)]
+    pub fn js_run_idle_compaction(&mut self) -> std::result::Result<JsValue, JsValue> {
+        match self.run_idle_compaction() {
+            Ok(Some(stats)) => compaction_stats_to_js(&stats),
+            Ok(None) => Ok(JsValue::NULL),
+            Err(e) => Err(JsValue::from_str(&e.to_string())),
+        }
+    }
+    #[wasm_bindgen(js_name = compactionStats)]
+    pub fn js_compaction_stats(&self) -> std::result::Result<JsValue, JsValue> {
+        let progress = &self.compaction;
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &"pending".into(), &progress.pending.into())?;
+        for (name, value) in [
+            ("runs", progress.runs),
+            ("pagesMoved", progress.pages_moved),
+            ("pagesFreed", progress.pages_freed),
+            ("bytesReclaimed", progress.bytes_reclaimed),
+        ] {
+            js_sys::Reflect::set(&result, &name.into(), &JsValue::from_f64(value as f64))?;
+        }
+        let last_run = match &progress.last_run {
+            Some(stats) => compaction_stats_to_js(stats)?,
+            None => JsValue::NULL,
+        };
+        js_sys::Reflect::set(&result, &"lastRun".into(), &last_run)?;
+        Ok(result.into())
+    }
+    #[wasm_bindgen(js_name = pageSize)]
+    pub fn page_size(&self) -> usize {
+        self.storage.page_size()
+    }
+    #[wasm_bindgen]
+    pub fn persist(&self) -> js_sys::Promise {
+        let paged = self.paged.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            if let Some(storage) = paged {
+                storage
+                    .persist()
+                    .await
+                    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            }
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+    #[wasm_bindgen]
+    pub fn load(&self) -> js_sys::Promise {
+        let paged = self.paged.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            if let Some(storage) = paged {
+                storage
+                    .load()
+                    .await
+                    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            }
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+    #[wasm_bindgen(js_name = beginOp)]
+    pub fn js_begin_op(&mut self) {
+        self.begin_op();
+    }
+    #[wasm_bindgen(js_name = endOp)]
+    pub fn js_end_op(&mut self, read_only: bool) -> bool {
+        self.end_op(read_only)
+    }
+    #[wasm_bindgen(js_name = prepareRetry)]
+    pub fn js_prepare_retry(&mut self) -> std::result::Result<bool, JsValue> {
+        self.prepare_retry()
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    fn check_page_size(&self) -> Result<()> {
+        let requested = self.storage.page_size();
+        let data_size = self.storage.data_size();
+        for slot_offset in std::iter::once(0).chain(SUPPORTED_PAGE_SIZES) {
+            if (slot_offset + METADATA_PROBE_SIZE) as u64 > data_size {
+                continue;
+            }
+            let header = self
+                .storage
+                .read_data(slot_offset as u64, METADATA_PROBE_SIZE)?;
+            match Metadata::stored_page_size(&header) {
+                Some(stored) if slot_offset == 0 || slot_offset == stored => {
+                    if stored != requested {
+                        return Err(SikioError::PageSizeMismatch { stored, requested });
+                    }
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    fn recover(&mut self, change_log: Option<ChangeLogConfig>) -> Result<()> {
+        self.check_page_size()?;
+        self.changes = None;
+        let mut changes = match change_log {
+            Some(config) => Some(self.replay_archived_changes(config)?),
+            None => None,
+        };
+        self.recovery = RecoveryReport::default();
+        let mut best_metadata = self.read_best_metadata();
+        let checkpointed = best_metadata.as_ref().map_or(0, |m| m.lsn);
+        let (wal_len, restored) = self.restore_page_images(checkpointed)?;
+        if wal_len < self.storage.wal_size() {
+            self.storage.truncate_wal_to(wal_len)?;
+        }
+        if restored {
+            best_metadata = self.read_best_metadata();
+        }
+        let mut has_valid_metadata = false;
+        if let Some(meta) = best_metadata {
+            if meta.page_size != self.storage.page_size() {
+                return Err(SikioError::PageSizeMismatch {
+                    stored: meta.page_size,
+                    requested: self.storage.page_size(),
+                });
+            }
+            if meta.root_page_id > 0 {
+                self.btree = BTree::with_root(meta.root_page_id, meta.next_page_id);
+                let free_list = match meta.freelist_root {
+                    0 => FreeList::from_ids(meta.page_size, meta.free_page_ids),
+                    root => match FreeList::load(self.storage.as_ref(), root) {
+                        Ok(free_list) => free_list,
+                        Err(e) => {
+                            self.recovery.free_list_error = Some(e);
+                            self.rebuild_free_list()?
+                        }
+                    },
+                };
+                self.btree.set_free_list(free_list);
+                self.lsn = meta.lsn;
+                self.generation = meta.generation;
+                self.compaction = meta.compaction;
+                has_valid_metadata = true;
+            }
+        }
+        if !has_valid_metadata {
+            self.initialize_empty_db()?;
+        }
+        let replayed_lsn = match wal_len {
+            0 => self.lsn,
+            _ => self.replay_wal(wal_len, changes.as_mut())?,
+        };
+        self.changes = changes.map(|changes| changes.finish(replayed_lsn));
+        self.commit_lsn = self.lsn;
+        Ok(())
+    }
+    fn rebuild_free_list(&mut self) -> Result<FreeList> {
+        let live: HashSet<u64> = self
+            .collect_live_units()?
+            .into_iter()
+            .flat_map(|unit| unit.pages)
+            .collect();
+        let free_ids = (2..self.btree.next_page_id())
+            .filter(|id| !live.contains(id))
+            .collect();
+        Ok(FreeList::from_ids(self.storage.page_size(), free_ids))
+    }
+    fn read_best_metadata(&self) -> Option<Metadata> {
         let mut best_metadata: Option<Metadata> = None;
         if self.storage.data_page_count() > 0 {
             if let Ok(bytes_0) = self.storage.read_page(METADATA_PAGE_ID_1) {
@@ -171,7 +901,7 @@ impl SikioDB {
                 if let Some(meta_1) = Metadata::from_bytes(&bytes_1) {
                     match best_metadata {
                         Some(ref current) => {
-                            if meta_1.wal_sequence > current.wal_sequence {
+                            if (meta_1.generation, meta_1.lsn) > (current.generation, current.lsn) {
                                 best_metadata = Some(meta_1);
                             }
                         }
@@ -182,107 +912,212 @@ impl SikioDB {
                 }
             }
         }
-        let mut has_valid_metadata = false;
-        if let Some(meta) = best_metadata {
-            if meta.root_page_id > 0 {
-                self.btree = BTree::with_root(meta.root_page_id, meta.next_page_id);
-                self.btree.set_free_page_ids(meta.free_page_ids);
-                self.wal_sequence = meta.wal_sequence;
-                has_valid_metadata = true;
+        best_metadata
+    }
+    fn restore_page_images(&mut self, checkpointed: u64) -> Result<(u64, bool)> {
+        let mut restored = HashSet::new();
+        let mut cursor = WalCursor::new(self.storage.wal_size());
+        let mut valid_len = 0;
+        while let Some(entry_result) = cursor.next_entry(self.storage.as_ref()) {
+            let entry = match entry_result {
+                Ok(entry) => entry,
+                Err(SikioError::IoError(reason)) => return Err(SikioError::IoError(reason)),
+                Err(e) => {
+                    let mid_log = cursor.more_entries_follow(self.storage.as_ref());
+                    if mid_log && self.fail_on_corrupt_wal {
+                        return Err(e);
+                    }
+                    self.recovery.stopped_at = Some(valid_len);
+                    self.recovery.stop_reason = Some(e);
+                    self.recovery.bytes_discarded = self.storage.wal_size() - valid_len;
+                    self.recovery.mid_log = mid_log;
+                    break;
+                }
+            };
+            valid_len = cursor.position();
+            if entry.sequence <= checkpointed {
+                continue;
+            }
+            if let (Some(page_id), Some(image)) = (entry.page_id(), &entry.value) {
+                if restored.insert(page_id) {
+                    self.storage.write_page(page_id, image)?;
+                }
             }
         }
-        if !has_valid_metadata {
-            self.initialize_empty_db()?;
-        }
-        if self.storage.wal_size() > 0 {
-            self.replay_wal()?;
-        }
-        Ok(())
+        Ok((valid_len, !restored.is_empty()))
     }
-    fn replay_wal(&mut self) -> Result<()> {
-        let wal_size = self.storage.wal_size() as usize;
-        if wal_size == 0 {
-            return Ok(());
-        }
-        let wal_data = self.storage.read_wal(0, wal_size)?;
-        let reader = WalReader::new(&wal_data);
-        let mut committed_ops: Vec<WalEntry> = Vec::new();
+    // Returns the last replayed LSN, before the checkpoint that ends replay.
+    fn replay_wal(
+        &mut self,
+        wal_len: u64,
+        mut changes: Option<&mut ChangeLogReplay>,
+    ) -> Result<u64> {
+        let mut cursor = WalCursor::new(wal_len);
         let mut pending_ops: Vec<WalEntry> = Vec::new();
-        let mut _last_checkpoint_seq = 0u64;
-        for entry_result in reader {
-            match entry_result {
-                Ok(entry) => match entry.operation {
-                    WalOperation::Checkpoint => {
-                        _last_checkpoint_seq = entry.sequence;
-                        committed_ops.clear();
-                        pending_ops.clear();
-                    }
-                    WalOperation::Commit => {
-                        committed_ops.append(&mut pending_ops);
-                    }
-                    WalOperation::Put | WalOperation::Delete => {
-                        pending_ops.push(entry);
-                    }
-                },
+        let mut replayed_lsn = self.lsn;
+        while let Some(entry_result) = cursor.next_entry(self.storage.as_ref()) {
+            let entry = match entry_result {
+                Ok(entry) => entry,
+                Err(SikioError::IoError(reason)) => return Err(SikioError::IoError(reason)),
                 Err(_) => break,
+            };
+            replayed_lsn = replayed_lsn.max(entry.sequence);
+            if let Some(changes) = changes.as_deref_mut() {
+                changes.feed(&entry);
             }
-        }
-        for entry in committed_ops {
             match entry.operation {
-                WalOperation::Put => {
-                    if let Some(value) = entry.value {
-                        self.apply_put(&entry.key, &value)?;
-                    }
+                WalOperation::Checkpoint => {
+                    self.recovery.record_discarded(&pending_ops);
+                    pending_ops.clear();
                 }
-                WalOperation::Delete => {
-                    self.apply_delete(&entry.key)?;
+                WalOperation::Commit => {
+                    self.recovery
+                        .record_commit(entry.sequence, pending_ops.len());
+                    for op in std::mem::take(&mut pending_ops) {
+                        self.redo(op)?;
+                    }
                 }
-                _ => {}
+                WalOperation::Put | WalOperation::Delete => pending_ops.push(entry),
+                WalOperation::PageImage => {}
             }
         }
+        self.recovery.record_discarded(&pending_ops);
+        self.lsn = replayed_lsn;
         self.checkpoint()?;
+        Ok(replayed_lsn)
+    }
+    fn redo(&mut self, entry: WalEntry) -> Result<()> {
+        if self.leaf_lsn(&entry.key)? >= entry.sequence {
+            return Ok(());
+        }
+        self.lsn = entry.sequence;
+        match entry.operation {
+            WalOperation::Put => {
+                if let Some(value) = entry.value {
+                    self.apply_put(&entry.key, &value)?;
+                }
+            }
+            WalOperation::Delete => {
+                self.apply_delete(&entry.key)?;
+            }
+            _ => {}
+        }
         Ok(())
     }
+    fn leaf_lsn(&mut self, key: &[u8]) -> Result<u64> {
+        let mut page_id = self.btree.root_page_id();
+        loop {
+            let node = self.load_node(page_id)?;
+            if node.is_leaf {
+                return Ok(self.cache.get(page_id).map_or(0, |page| page.header.lsn));
+            }
+            match node.children.get(child_index(&node, key)) {
+                Some(&child) => page_id = child,
+                None => return Ok(0),
+            }
+        }
+    }
+    // A key's version is the highest LSN stamped on the pages that lead to
+    // it. Any write into [start, end] restamps at least one page on those
+    // paths, including splits and merges, so versions survive a reopen and a
+    // write elsewhere only conflicts when it shares a page.
+    fn range_lsn(&mut self, start: &[u8], end: &[u8]) -> Result<u64> {
+        let mut lsn = 0;
+        let mut stack = vec![self.btree.root_page_id()];
+        while let Some(page_id) = stack.pop() {
+            let node = self.load_node(page_id)?;
+            lsn = lsn.max(self.cache.get(page_id).map_or(0, |page| page.header.lsn));
+            if node.is_leaf || node.children.is_empty() {
+                continue;
+            }
+            let last = node.children.len() - 1;
+            let first = child_index(&node, start).min(last);
+            stack.extend(&node.children[first..=child_index(&node, end).min(last)]);
+        }
+        Ok(lsn)
+    }
     fn initialize_empty_db(&mut self) -> Result<()> {
+        self.btree
+            .set_free_list(FreeList::new(self.storage.page_size()));
         let root_id = self.btree.allocate_page();
         let root = BTreeNode::new_leaf(root_id);
-        let page = root.to_page()?;
+        let page = root.to_page(self.storage.page_size())?;
         self.storage.write_page(root_id, &page.to_bytes())?;
+        self.storage.flush_data()?;
         self.btree.set_root(root_id);
-        let meta = Metadata {
-            root_page_id: self.btree.root_page_id(),
-            next_page_id: self.btree.next_page_id(),
-            wal_sequence: self.wal_sequence,
-            free_page_ids: self.btree.free_page_ids().to_vec(),
-        };
-        let bytes = meta.to_bytes();
+        let bytes = self.current_metadata().to_bytes();
         self.storage.write_page(METADATA_PAGE_ID_1, &bytes)?;
         self.storage.write_page(METADATA_PAGE_ID_2, &bytes)?;
+        self.storage.flush_data()?;
         Ok(())
     }
-    fn write_metadata(&mut self) -> Result<()> {
-        let meta = Metadata {
+    fn current_metadata(&self) -> Metadata {
+        Metadata {
             root_page_id: self.btree.root_page_id(),
             next_page_id: self.btree.next_page_id(),
-            wal_sequence: self.wal_sequence,
-            free_page_ids: self.btree.free_page_ids().to_vec(),
-        };
+            lsn: self.lsn,
+            generation: self.generation,
+            page_size: self.storage.page_size(),
+            freelist_root: self.btree.free_list().root(),
+            free_page_count: self.btree.free_page_count(),
+            free_page_ids: Vec::new(),
+            compaction: self.compaction.clone(),
+        }
+    }
+    fn write_metadata(&mut self) -> Result<()> {
+        let mut meta = self.current_metadata();
+        meta.generation += 1;
         let bytes = meta.to_bytes();
-        let target_page = if self.wal_sequence % 2 == 0 {
+        self.storage.write_page(self.metadata_slot(), &bytes)?;
+        self.storage.flush_data()?;
+        self.generation = meta.generation;
+        Ok(())
+    }
+    fn metadata_slot(&self) -> u64 {
+        if self.generation.is_multiple_of(2) {
             METADATA_PAGE_ID_1
         } else {
             METADATA_PAGE_ID_2
-        };
-        self.storage.write_page(target_page, &bytes)?;
-        Ok(())
+        }
     }
-    #[wasm_bindgen]
-    pub fn verify_integrity(&mut self) -> std::result::Result<Box<[u64]>, JsValue> {
-        let count = self.storage.data_page_count();
-        let mut corrupted_pages = Vec::new();
-        for page_id in 2..count {
+    fn log_page_images(&mut self, page_ids: &[u64]) -> Result<()> {
+        let page_count = self.storage.data_page_count();
+        let mut buffer = Vec::new();
+        let mut imaged = Vec::new();
+        let mut failed = None;
+        // Keep reading after a miss so paged storage learns every missing page.
+        for page_id in std::iter::once(self.metadata_slot()).chain(page_ids.iter().copied()) {
+            if page_id >= page_count || self.imaged_pages.contains(&page_id) {
+                continue;
+            }
             match self.storage.read_page(page_id) {
-                Ok(bytes) => {
+                Ok(image) => {
+                    WalEntry::new_page_image(self.lsn, page_id, image).write_to_buffer(&mut buffer);
+                    imaged.push(page_id);
+                }
+                Err(e) => {
+                    failed.get_or_insert(e);
+                }
+            }
+        }
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        self.storage.append_wal(&buffer)?;
+        self.storage.flush_wal()?;
+        self.imaged_pages.extend(imaged);
+        Ok(())
+    }
+    #[wasm_bindgen]
+    pub fn verify_integrity(&mut self) -> std::result::Result<Box<[u64]>, JsValue> {
+        let count = self.storage.data_page_count();
+        let mut corrupted_pages = Vec::new();
+        for page_id in 2..count {
+            match self.storage.read_page(page_id) {
+                Ok(bytes) => {
                     if Page::from_bytes(&bytes).is_err() && OverflowPage::from_bytes(&bytes).is_err()
                     {
                         corrupted_pages.push(page_id);
@@ -308,7 +1143,7 @@ impl SikioDB {
         value: &[u8],
         ttl_ms: u64,
     ) -> std::result::Result<(), JsValue> {
-        let now = js_sys::Date::now() as u64;
+        let now = now_ms();
         let expiry = now + ttl_ms;
         let wrapped = wrap_ttl_value(value, expiry);
         self.put_internal(key, &wrapped)
@@ -319,20 +1154,73 @@ impl SikioDB {
         self.put_batch_internal(data)
             .map_err(|e| JsValue::from_str(&e.to_string()))
     }
-    #[wasm_bindgen(js_name = putNoSync)]
-    pub fn put_no_sync(&mut self, key: &[u8], value: &[u8]) -> std::result::Result<(), JsValue> {
-        let wrapped = wrap_raw_value(value);
-        self.put_internal_no_sync(key, &wrapped)
-            .map_err(|e| JsValue::from_str(&e.to_string()))
-    }
     #[wasm_bindgen]
     pub fn flush(&mut self) -> std::result::Result<(), JsValue> {
-        self.storage
-            .flush_wal()
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
-        self.storage
-            .flush_data()
+        self.flush_internal()
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    #[wasm_bindgen(js_name = flushIfDue)]
+    pub fn js_flush_if_due(&mut self) -> std::result::Result<bool, JsValue> {
+        self.flush_if_due()
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    #[wasm_bindgen(js_name = commitGroup)]
+    pub fn js_commit_group(&mut self) -> std::result::Result<(), JsValue> {
+        self.commit_group()
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    #[wasm_bindgen(js_name = setDurability)]
+    pub fn js_set_durability(&mut self, durability: JsValue) -> std::result::Result<(), JsValue> {
+        let durability = Durability::from_js(&durability)?
+            .ok_or_else(|| JsValue::from_str("Invalid durability"))?;
+        self.set_durability(durability)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    #[wasm_bindgen(getter = commitVersion)]
+    pub fn commit_version(&self) -> u64 {
+        self.commit_lsn
+    }
+    #[wasm_bindgen(js_name = changesSince)]
+    pub fn js_changes_since(&self, lsn: u64, limit: u32) -> std::result::Result<JsValue, JsValue> {
+        let batch = self
+            .changes_since(lsn, limit as usize)
             .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let changes = js_sys::Array::new();
+        for change in &batch.changes {
+            let entry = js_sys::Object::new();
+            let op = match change.op {
+                ChangeOp::Put => "put",
+                ChangeOp::Delete => "delete",
+            };
+            let value = match &change.value {
+                Some(value) => js_sys::Uint8Array::from(value.as_slice()).into(),
+                None => JsValue::NULL,
+            };
+            let key = js_sys::Uint8Array::from(&change.key[..]);
+            js_sys::Reflect::set(&entry, &"key".into(), &key)?;
+            js_sys::Reflect::set(&entry, &"op".into(), &op.into())?;
+            js_sys::Reflect::set(&entry, &"value".into(), &value)?;
+            js_sys::Reflect::set(&entry, &"lsn".into(), &JsValue::from_f64(change.lsn as f64))?;
+            changes.push(&entry);
+        }
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &"changes".into(), &changes)?;
+        let resume_token = JsValue::from_f64(batch.resume_token as f64);
+        js_sys::Reflect::set(&result, &"resumeToken".into(), &resume_token)?;
+        Ok(result.into())
+    }
+    #[wasm_bindgen(js_name = recoveryReport)]
+    pub fn js_recovery_report(&self) -> std::result::Result<JsValue, JsValue> {
+        recovery_report_to_js(&self.recovery)
+    }
+    #[wasm_bindgen(getter = pendingCommitBytes)]
+    pub fn pending_commit_bytes(&self) -> usize {
+        self.pending_wal.len()
+    }
+    fn flush_internal(&mut self) -> Result<()> {
+        self.commit_group()?;
+        self.flush_wal()?;
+        self.storage.flush_data()?;
         Ok(())
     }
     #[wasm_bindgen]
@@ -354,12 +1242,18 @@ impl SikioDB {
         let results = js_sys::Array::new();
         let mut count = 0u32;
 
-        cursor_seek(&mut state, start_key, root, &self.storage, &mut self.cache)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        cursor_seek(
+            &mut state,
+            start_key,
+            root,
+            self.storage.as_ref(),
+            &mut self.cache,
+        )
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
         while state.valid() && count < limit {
             if let (Some(key), Some(value)) = (state.key(), state.value()) {
-                if &key[..] > end_key {
+                if key > end_key {
                     break;
                 }
 
@@ -377,7 +1271,7 @@ impl SikioDB {
                                 let expiry = u64::from_le_bytes(
                                     stored_value[1..9].try_into().unwrap_or([0; 8]),
                                 );
-                                let now = js_sys::Date::now() as u64;
+                                let now = now_ms();
                                 if now <= expiry {
                                     Some(stored_value[9..].to_vec())
                                 } else {
@@ -393,7 +1287,7 @@ impl SikioDB {
 
                 if let Some(val) = user_value {
                     let entry = js_sys::Object::new();
-                    let key_arr = js_sys::Uint8Array::from(&key[..]);
+                    let key_arr = js_sys::Uint8Array::from(key);
                     let val_arr = js_sys::Uint8Array::from(&val[..]);
 
                     js_sys::Reflect::set(&entry, &"key".into(), &key_arr)?;
@@ -403,7 +1297,7 @@ impl SikioDB {
                     count += 1;
                 }
             }
-            cursor_next(&mut state, &self.storage, &mut self.cache)
+            cursor_next(&mut state, self.storage.as_ref(), &mut self.cache)
                 .map_err(|e| JsValue::from_str(&e.to_string()))?;
         }
         Ok(results)
@@ -412,7 +1306,6 @@ impl SikioDB {
         let mut offset = 0;
         let mut count = 0;
         let len = data.len();
-        let mut wal_buffer = Vec::with_capacity(len + (len / 10));
         let mut pairs = Vec::with_capacity(BATCH_PAIRS_INITIAL_CAPACITY);
         while offset < len {
             if offset + 4 > len {
@@ -449,63 +1342,457 @@ impl SikioDB {
             pairs.push((key.to_vec(), wrapped));
             count += 1;
         }
+        self.put_pairs(pairs)?;
+        Ok(count)
+    }
+    fn put_pairs(&mut self, pairs: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
         for (key, value) in &pairs {
             validate_key_value(key, value)?;
-            self.wal_sequence += 1;
-            WalEntry::serialize_put(self.wal_sequence, key, value, &mut wal_buffer);
         }
-        self.wal_sequence += 1;
-        WalEntry::serialize_commit(self.wal_sequence, &mut wal_buffer);
-        self.storage.append_wal(&wal_buffer)?;
-        self.storage.flush_wal()?;
-        pairs.sort_by(|a, b| a.0.cmp(&b.0));
-        for (key, value) in &pairs {
-            self.apply_put(key, value)?;
+        let len: usize = pairs.iter().map(|(k, v)| k.len() + v.len()).sum();
+        let stored = self.with_rollback(|db| {
+            let mut wal_buffer = Vec::with_capacity(len + (len / 10));
+            for (key, value) in &pairs {
+                db.lsn += 1;
+                WalEntry::serialize_put(db.lsn, key, value, &mut wal_buffer);
+            }
+            db.lsn += 1;
+            WalEntry::serialize_commit(db.lsn, &mut wal_buffer);
+            let stored = pairs
+                .iter()
+                .map(|(_, value)| db.store_value(value))
+                .collect::<Result<Vec<_>>>()?;
+            db.append_wal_committed(&wal_buffer)?;
+            Ok(stored)
+        })?;
+        self.commit_lsn = self.lsn;
+        let changes: Option<Vec<_>> = self.changes.is_some().then(|| {
+            pairs
+                .iter()
+                .map(|(key, value)| self.change(ChangeOp::Put, key.clone(), Some(value)))
+                .collect()
+        });
+        let mut entries: Vec<_> = pairs.into_iter().map(|(key, _)| key).zip(stored).collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        self.apply_entries(entries)?;
+        if let Some(changes) = changes {
+            self.record_changes(changes);
+        }
+        self.maybe_checkpoint()
+    }
+    fn apply_entries(&mut self, mut entries: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        if entries.len() >= BULK_LOAD_MIN_ENTRIES {
+            entries.reverse();
+            entries.dedup_by(|a, b| a.0 == b.0);
+            entries.reverse();
+            if let Some(spine) = self.append_spine(&entries[0].0)? {
+                return self.bulk_append(spine, entries);
+            }
+            let last = &entries[entries.len() - 1].0;
+            if self.fits_in_gap(&entries[0].0, last)? {
+                return self.bulk_graft(entries);
+            }
         }
-        if self.storage.wal_size() > WAL_CHECKPOINT_THRESHOLD {
-            self.checkpoint()?;
+        for (key, value) in &entries {
+            self.apply_put_value(key, value)?;
         }
-        Ok(count)
+        Ok(())
+    }
+    fn append_spine(&mut self, first_key: &[u8]) -> Result<Option<Vec<BTreeNode>>> {
+        let mut spine = Vec::new();
+        let mut page_id = self.btree.root_page_id();
+        loop {
+            let node = self.load_node(page_id)?;
+            let child = node.children.last().copied();
+            let is_leaf = node.is_leaf;
+            spine.push(node);
+            match child {
+                Some(child) if !is_leaf => page_id = child,
+                _ => break,
+            }
+        }
+        spine.reverse();
+        let appendable = match spine[0].keys.last() {
+            Some(last) => last.as_slice() < first_key,
+            None => spine.len() == 1,
+        };
+        Ok(appendable.then_some(spine))
+    }
+    fn bulk_append(
+        &mut self,
+        spine: Vec<BTreeNode>,
+        entries: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<()> {
+        let first_page_id = self.btree.next_page_id();
+        let mut sink = CacheSink {
+            cache: &mut self.cache,
+            page_size: self.storage.page_size(),
+            lsn: self.lsn,
+        };
+        let mut builder = BulkBuilder::with_spine(&mut sink, first_page_id, spine);
+        for (key, value) in entries {
+            builder.add(key, value)?;
+        }
+        let (root_page_id, next_page_id) = builder.finish()?;
+        self.btree.set_root(root_page_id);
+        self.btree.set_next_page_id(next_page_id);
+        Ok(())
+    }
+    fn fits_in_gap(&mut self, first: &[u8], last: &[u8]) -> Result<bool> {
+        let mut page_id = self.btree.root_page_id();
+        loop {
+            let node = self.load_node(page_id)?;
+            let pos = node.find_key_position(first);
+            if node.keys.get(pos).is_some_and(|key| key.as_slice() <= last) {
+                return Ok(false);
+            }
+            match node.children.get(pos) {
+                Some(&child) if !node.is_leaf => page_id = child,
+                _ => return Ok(true),
+            }
+        }
+    }
+    // Cuts the tree at the gap, builds the entries and joins the pieces.
+    fn bulk_graft(&mut self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        let first = entries[0].0.clone();
+        let (left, right) = self.split_subtree(self.btree.root_page_id(), &first)?;
+        let mut sink = CacheSink {
+            cache: &mut self.cache,
+            page_size: self.storage.page_size(),
+            lsn: self.lsn,
+        };
+        let mut builder = BulkBuilder::new(&mut sink, self.btree.next_page_id());
+        for (key, value) in entries {
+            builder.add(key, value)?;
+        }
+        let (root_page_id, next_page_id) = builder.finish()?;
+        self.btree.set_next_page_id(next_page_id);
+        let mut tree = self.subtree(root_page_id)?;
+        if let Some(left) = left {
+            let left = self.subtree(left)?;
+            tree = self.join_trees(left, first, tree)?;
+        }
+        if let Some((bound, right)) = right {
+            let right = self.subtree(right)?;
+            tree = self.join_trees(tree, bound, right)?;
+        }
+        self.btree.set_root(tree.0);
+        Ok(())
+    }
+    // Splits at `key` into two subtrees as tall as the original.
+    fn split_subtree(&mut self, page_id: u64, key: &[u8]) -> Result<SubtreeSplit> {
+        let mut node = self.load_node(page_id)?;
+        let pos = node.find_key_position(key);
+        let (mut right, bound) = if node.is_leaf {
+            let mut right = BTreeNode::new_leaf(0);
+            right.keys = node.keys.split_off(pos);
+            right.values = node.values.split_off(pos);
+            let bound = right.keys.first().cloned().unwrap_or_default();
+            (right, bound)
+        } else {
+            let child = node
+                .children
+                .get(pos)
+                .copied()
+                .ok_or(SikioError::PageCorrupted {
+                    page_id,
+                    reason: "Internal node missing children".into(),
+                })?;
+            let (inner_left, inner_right) = self.split_subtree(child, key)?;
+            let mut right = BTreeNode::new_internal(0);
+            right.children = node.children.split_off(pos + 1);
+            right.keys = node.keys.split_off(pos);
+            node.children.pop();
+            match inner_left {
+                Some(child) => node.children.push(child),
+                None => {
+                    node.keys.pop();
+                }
+            }
+            let bound = match inner_right {
+                Some((bound, child)) => {
+                    right.children.insert(0, child);
+                    bound
+                }
+                None if right.keys.is_empty() => Vec::new(),
+                None => right.keys.remove(0),
+            };
+            (right, bound)
+        };
+        let left_empty = node.keys.is_empty() && node.children.is_empty();
+        let right_empty = right.keys.is_empty() && right.children.is_empty();
+        match (left_empty, right_empty) {
+            (true, true) => {
+                self.free_page(page_id);
+                Ok((None, None))
+            }
+            (true, false) => {
+                right.page_id = page_id;
+                self.save_node(&right)?;
+                Ok((None, Some((bound, page_id))))
+            }
+            (false, true) => {
+                self.save_node(&node)?;
+                Ok((Some(page_id), None))
+            }
+            (false, false) => {
+                right.page_id = self.btree.allocate_page();
+                self.save_node(&node)?;
+                self.save_node(&right)?;
+                Ok((Some(page_id), Some((bound, right.page_id))))
+            }
+        }
+    }
+    fn subtree(&mut self, mut page_id: u64) -> Result<(u64, usize)> {
+        let mut node = self.load_node(page_id)?;
+        while !node.is_leaf && node.children.len() == 1 {
+            self.free_page(page_id);
+            page_id = node.children[0];
+            node = self.load_node(page_id)?;
+        }
+        let mut height = 0;
+        while let Some(&child) = node.children.first() {
+            node = self.load_node(child)?;
+            height += 1;
+        }
+        Ok((page_id, height))
+    }
+    // Hangs the shorter subtree off the facing edge of the taller one.
+    fn join_trees(
+        &mut self,
+        left: (u64, usize),
+        separator: Vec<u8>,
+        right: (u64, usize),
+    ) -> Result<(u64, usize)> {
+        let (root_id, height, split) = match left.1.cmp(&right.1) {
+            Ordering::Less => {
+                let split = self.graft_edge(right.0, right.1, separator, left, false)?;
+                (right.0, right.1, split)
+            }
+            Ordering::Greater => {
+                let split = self.graft_edge(left.0, left.1, separator, right, true)?;
+                (left.0, left.1, split)
+            }
+            Ordering::Equal => (left.0, left.1, Some((separator, right.0))),
+        };
+        let Some((separator, right_id)) = split else {
+            return Ok((root_id, height));
+        };
+        let new_root_id = self.btree.allocate_page();
+        let mut new_root = BTreeNode::new_internal(new_root_id);
+        new_root.keys.push(separator);
+        new_root.children.push(root_id);
+        new_root.children.push(right_id);
+        self.save_node(&new_root)?;
+        Ok((new_root_id, height + 1))
+    }
+    fn graft_edge(
+        &mut self,
+        page_id: u64,
+        height: usize,
+        separator: Vec<u8>,
+        subtree: (u64, usize),
+        append: bool,
+    ) -> Result<Option<(Vec<u8>, u64)>> {
+        let mut node = self.load_node(page_id)?;
+        if height == subtree.1 + 1 {
+            match append {
+                true => {
+                    node.keys.push(separator);
+                    node.children.push(subtree.0);
+                }
+                false => {
+                    node.keys.insert(0, separator);
+                    node.children.insert(0, subtree.0);
+                }
+            }
+        } else {
+            let edge = match append {
+                true => node.children.last(),
+                false => node.children.first(),
+            };
+            let edge = *edge.ok_or(SikioError::PageCorrupted {
+                page_id,
+                reason: "Internal node missing children".into(),
+            })?;
+            if let Some((key, child_id)) =
+                self.graft_edge(edge, height - 1, separator, subtree, append)?
+            {
+                let pos = if append { node.keys.len() } else { 0 };
+                node.insert_internal(pos, key, child_id);
+            }
+        }
+        if node.needs_split(self.storage.page_size()) {
+            let (separator, mut right) = node.split();
+            right.page_id = self.btree.allocate_page();
+            self.save_node(&node)?;
+            self.save_node(&right)?;
+            return Ok(Some((separator, right.page_id)));
+        }
+        self.save_node(&node)?;
+        Ok(None)
     }
     fn put_internal(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
         validate_key_value(key, value)?;
-        self.wal_sequence += 1;
-        let wal_entry = WalEntry::new_put(self.wal_sequence, key.to_vec(), value.to_vec());
-        self.storage.append_wal(&wal_entry.to_bytes())?;
-        self.wal_sequence += 1;
-        let commit = WalEntry::new_commit(self.wal_sequence);
-        self.storage.append_wal(&commit.to_bytes())?;
-        self.storage.flush_wal()?;
-        self.apply_put(key, value)?;
-        if self.storage.wal_size() > WAL_CHECKPOINT_THRESHOLD {
-            self.checkpoint()?;
+        let stored = self.log_put(key, value)?;
+        self.apply_put_value(key, &stored)?;
+        self.record_put(key, value);
+        self.maybe_checkpoint()
+    }
+    fn log_put(&mut self, key: &[u8], value: &[u8]) -> Result<Vec<u8>> {
+        let stored = self.with_rollback(|db| {
+            let stored = db.store_value(value)?;
+            db.lsn += 1;
+            let wal_entry = WalEntry::new_put(db.lsn, key.to_vec(), value.to_vec());
+            db.append_wal_grouped(&wal_entry.to_bytes())?;
+            db.lsn += 1;
+            let commit = WalEntry::new_commit(db.lsn);
+            db.append_wal_grouped(&commit.to_bytes())?;
+            if !db.grouping() {
+                db.sync_wal()?;
+            }
+            Ok(stored)
+        })?;
+        self.commit_lsn = self.lsn;
+        Ok(stored)
+    }
+    fn record_put(&mut self, key: &[u8], value: &[u8]) {
+        if self.changes.is_some() {
+            let change = self.change(ChangeOp::Put, key.to_vec(), Some(value));
+            self.record_changes(vec![change]);
+        }
+    }
+    fn change(&self, op: ChangeOp, key: Vec<u8>, value: Option<&[u8]>) -> Change {
+        Change {
+            lsn: self.commit_lsn,
+            op,
+            key,
+            value: value.and_then(unwrap_value),
+        }
+    }
+    fn record_changes(&mut self, changes: Vec<Change>) {
+        if self.changes.is_none() {
+            return;
+        }
+        match self.pending_wal.is_empty() {
+            true => self.publish_changes(self.commit_lsn, changes),
+            false => self.pending_changes.push((self.commit_lsn, changes)),
+        }
+    }
+    fn publish_changes(&mut self, lsn: u64, changes: Vec<Change>) {
+        if let Some(log) = &mut self.changes {
+            log.record(lsn, changes);
+        }
+    }
+    fn publish_group(&mut self) {
+        for (lsn, changes) in std::mem::take(&mut self.pending_changes) {
+            self.publish_changes(lsn, changes);
+        }
+    }
+    fn grouping(&self) -> bool {
+        self.group_commit.is_some() && self.durability == Durability::Full
+    }
+    fn append_wal_grouped(&mut self, bytes: &[u8]) -> Result<()> {
+        match self.grouping() {
+            true => self.pending_wal.extend_from_slice(bytes),
+            false => {
+                self.storage.append_wal(bytes)?;
+            }
         }
         Ok(())
     }
-    fn put_internal_no_sync(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
-        validate_key_value(key, value)?;
-        self.wal_sequence += 1;
-        let wal_entry = WalEntry::new_put(self.wal_sequence, key.to_vec(), value.to_vec());
-        self.storage.append_wal(&wal_entry.to_bytes())?;
-        self.wal_sequence += 1;
-        let commit = WalEntry::new_commit(self.wal_sequence);
-        self.storage.append_wal(&commit.to_bytes())?;
-        self.apply_put(key, value)?;
-        if self.storage.wal_size() > WAL_CHECKPOINT_THRESHOLD {
-            self.checkpoint()?;
+    fn append_wal_committed(&mut self, bytes: &[u8]) -> Result<()> {
+        if !self.pending_wal.is_empty() {
+            self.storage.append_wal(&self.pending_wal)?;
         }
+        self.storage.append_wal(bytes)?;
+        self.sync_wal()?;
+        self.pending_wal.clear();
+        self.publish_group();
         Ok(())
     }
-    fn apply_put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
-        if value.len() > OVERFLOW_THRESHOLD {
+    fn sync_wal(&mut self) -> Result<()> {
+        match self.durability {
+            Durability::Full => {}
+            Durability::Periodic(interval) if now_ms() >= self.last_wal_flush + interval => {}
+            Durability::Periodic(_) | Durability::None => return Ok(()),
+        }
+        self.flush_wal()
+    }
+    fn flush_wal(&mut self) -> Result<()> {
+        self.storage.flush_wal()?;
+        self.last_wal_flush = now_ms();
+        Ok(())
+    }
+    fn reload(&mut self) -> Result<()> {
+        let change_log = self.changes.as_ref().map(|log| log.config().clone());
+        self.reload_with(change_log)
+    }
+    fn reload_with(&mut self, change_log: Option<ChangeLogConfig>) -> Result<()> {
+        self.cache = PageCache::new();
+        self.imaged_pages.clear();
+        self.btree = BTree::new();
+        self.lsn = 0;
+        self.recover(change_log)
+    }
+    fn with_rollback<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let wal_len = self.storage.wal_size();
+        let pending_len = self.pending_wal.len();
+        let lsn = self.lsn;
+        let next_page_id = self.btree.next_page_id();
+        let free_list = self.btree.free_list().clone();
+        let result = f(self);
+        if result.is_err() {
+            let _ = self.storage.truncate_wal_to(wal_len);
+            let _ = self.storage.flush_wal();
+            self.pending_wal.truncate(pending_len);
+            self.lsn = lsn;
+            self.btree.set_next_page_id(next_page_id);
+            self.btree.set_free_list(free_list);
+        }
+        result
+    }
+    fn maybe_checkpoint(&mut self) -> Result<()> {
+        if let Some(group) = &self.group_commit {
+            if self.pending_wal.len() >= group.max_bytes {
+                self.commit_group()?;
+            }
+        }
+        if !self.checkpoint_due() {
+            return Ok(());
+        }
+        match self.checkpoint() {
+            Err(SikioError::QuotaExceeded) => Ok(()),
+            result => result,
+        }
+    }
+    fn checkpoint_due(&self) -> bool {
+        let policy = &self.checkpoint_policy;
+        if self.storage.wal_size() > policy.wal_bytes {
+            return true;
+        }
+        let dirty = self.cache.dirty_page_count();
+        if policy.dirty_pages.is_some_and(|limit| dirty >= limit) {
+            return true;
+        }
+        dirty > 0
+            && policy
+                .interval_ms
+                .is_some_and(|interval| now_ms().saturating_sub(self.last_checkpoint) >= interval)
+    }
+    fn store_value(&mut self, value: &[u8]) -> Result<Vec<u8>> {
+        if value.len() > overflow_threshold(self.storage.page_size()) {
             let compressed = compress(value);
             let (start_page, total_len) = self.write_overflow_chain(&compressed)?;
-            let marker = Self::encode_overflow_marker(start_page, total_len);
-            self.apply_put_value(key, &marker)
+            Ok(Self::encode_overflow_marker(start_page, total_len))
         } else {
-            self.apply_put_value(key, value)
+            Ok(value.to_vec())
         }
     }
+    fn apply_put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let stored = self.store_value(value)?;
+        self.apply_put_value(key, &stored)
+    }
     fn apply_put_value(&mut self, key: &[u8], stored_value: &[u8]) -> Result<()> {
         let root_id = self.btree.root_page_id();
         let result = self.insert_recursive(root_id, key, stored_value)?;
@@ -515,8 +1802,7 @@ impl SikioDB {
             new_root.keys.push(separator);
             new_root.children.push(root_id);
             new_root.children.push(new_right_id);
-            let page = new_root.to_page()?;
-            self.cache.insert(page, true);
+            self.save_node(&new_root)?;
             self.btree.set_root(new_root_id);
         }
         Ok(())
@@ -535,7 +1821,7 @@ impl SikioDB {
             } else {
                 node.insert_at(pos, key.to_vec(), value.to_vec());
             }
-            if node.needs_split() {
+            if node.needs_split(self.storage.page_size()) {
                 let (separator, mut right) = node.split();
                 let right_id = self.btree.allocate_page();
                 right.page_id = right_id;
@@ -561,7 +1847,7 @@ impl SikioDB {
             let child_result = self.insert_recursive(child_id, key, value)?;
             if let Some((separator, new_child_id)) = child_result {
                 node.insert_internal(child_idx, separator, new_child_id);
-                if node.needs_split() {
+                if node.needs_split(self.storage.page_size()) {
                     let (sep, mut right) = node.split();
                     let right_id = self.btree.allocate_page();
                     right.page_id = right_id;
@@ -614,7 +1900,7 @@ impl SikioDB {
                         .try_into()
                         .map_err(|_| SikioError::Corrupted("Invalid TTL expiry".into()))?,
                 );
-                let now = js_sys::Date::now() as u64;
+                let now = now_ms();
                 if now > expiry {
                     Ok(None)
                 } else {
@@ -627,6 +1913,10 @@ impl SikioDB {
             }),
         }
     }
+    fn contains_key(&mut self, key: &[u8]) -> Result<bool> {
+        let root_id = self.btree.root_page_id();
+        Ok(self.search_recursive(root_id, key)?.is_some())
+    }
     fn search_recursive(&mut self, page_id: u64, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let node = self.load_node(page_id)?;
         let pos = node.find_key_position(key);
@@ -672,7 +1962,7 @@ impl SikioDB {
         let (start, end) = prefix_to_range(prefix);
         let raw_pairs = self.range_internal(&start, &end)?;
         let mut clean_pairs = Vec::with_capacity(raw_pairs.len());
-        let now = js_sys::Date::now() as u64;
+        let now = now_ms();
         for (key, val) in raw_pairs {
             if val.is_empty() {
                 continue;
@@ -713,8 +2003,14 @@ impl SikioDB {
         let mut state = CursorState::new();
 
         let started = match start.start_key() {
-            Some(sk) => cursor_seek(&mut state, sk, root_id, &self.storage, &mut self.cache)?,
-            None => cursor_first(&mut state, root_id, &self.storage, &mut self.cache)?,
+            Some(sk) => cursor_seek(
+                &mut state,
+                sk,
+                root_id,
+                self.storage.as_ref(),
+                &mut self.cache,
+            )?,
+            None => cursor_first(&mut state, root_id, self.storage.as_ref(), &mut self.cache)?,
         };
 
         if !started {
@@ -731,7 +2027,7 @@ impl SikioDB {
                     results.push((key.to_vec(), resolved));
                 }
             }
-            cursor_next(&mut state, &self.storage, &mut self.cache)?;
+            cursor_next(&mut state, self.storage.as_ref(), &mut self.cache)?;
         }
 
         Ok(results)
@@ -757,17 +2053,29 @@ impl SikioDB {
             .map_err(|e| JsValue::from_str(&e.to_string()))
     }
     fn delete_internal(&mut self, key: &[u8]) -> Result<bool> {
-        self.wal_sequence += 1;
-        let wal_entry = WalEntry::new_delete(self.wal_sequence, key.to_vec());
-        self.storage.append_wal(&wal_entry.to_bytes())?;
-        self.wal_sequence += 1;
-        let commit = WalEntry::new_commit(self.wal_sequence);
-        self.storage.append_wal(&commit.to_bytes())?;
-        self.storage.flush_wal()?;
+        if !self.contains_key(key)? {
+            return Ok(false);
+        }
+        self.with_rollback(|db| {
+            db.lsn += 1;
+            let wal_entry = WalEntry::new_delete(db.lsn, key.to_vec());
+            db.append_wal_grouped(&wal_entry.to_bytes())?;
+            db.lsn += 1;
+            let commit = WalEntry::new_commit(db.lsn);
+            db.append_wal_grouped(&commit.to_bytes())?;
+            match db.grouping() {
+                true => Ok(()),
+                false => db.sync_wal(),
+            }
+        })?;
+        self.commit_lsn = self.lsn;
         let deleted = self.apply_delete(key)?;
-        if self.storage.wal_size() > WAL_CHECKPOINT_THRESHOLD {
-            self.checkpoint()?;
+        let mut changes = Vec::new();
+        if deleted && self.changes.is_some() {
+            changes.push(self.change(ChangeOp::Delete, key.to_vec(), None));
         }
+        self.record_changes(changes);
+        self.maybe_checkpoint()?;
         Ok(deleted)
     }
     fn apply_delete(&mut self, key: &[u8]) -> Result<bool> {
@@ -777,6 +2085,7 @@ impl SikioDB {
             let root = self.load_node(root_id)?;
             if !root.is_leaf && root.keys.is_empty() && root.children.len() == 1 {
                 self.btree.set_root(root.children[0]);
+                self.free_page(root_id);
             }
         }
         Ok(deleted)
@@ -852,6 +2161,7 @@ impl SikioDB {
             merged.merge_with(child, separator);
             merged.page_id = left_sibling_id;
             self.save_node(&merged)?;
+            self.free_page(child_id);
         } else if child_idx < parent.children.len() - 1 {
             let right_sibling_id = parent.children[child_idx + 1];
             let right_sibling = self.load_node(right_sibling_id)?;
@@ -859,9 +2169,14 @@ impl SikioDB {
             parent.children.remove(child_idx + 1);
             child.merge_with(right_sibling, separator);
             self.save_node(&child)?;
+            self.free_page(right_sibling_id);
         }
         Ok(())
     }
+    fn free_page(&mut self, page_id: u64) {
+        self.cache.remove(page_id);
+        self.btree.reclaim_page(page_id);
+    }
     fn load_node(&mut self, page_id: u64) -> Result<BTreeNode> {
         if let Some(page) = self.cache.get(page_id) {
             return BTreeNode::from_page(page);
@@ -873,7 +2188,8 @@ impl SikioDB {
         Ok(node)
     }
     fn save_node(&mut self, node: &BTreeNode) -> Result<()> {
-        let page = node.to_page()?;
+        let mut page = node.to_page(self.storage.page_size())?;
+        page.header.lsn = self.lsn;
         self.cache.insert(page, true);
         Ok(())
     }
@@ -883,13 +2199,14 @@ impl SikioDB {
                 "Cannot write empty overflow chain".into(),
             ));
         }
-        let max_per_page = OverflowPage::max_data_per_page();
+        let page_size = self.storage.page_size();
+        let max_per_page = OverflowPage::max_data_per_page(page_size);
         let mut remaining = data;
         let mut pages: Vec<OverflowPage> = Vec::new();
         while !remaining.is_empty() {
             let chunk_size = remaining.len().min(max_per_page);
             let page_id = self.btree.allocate_page();
-            let mut page = OverflowPage::new(page_id);
+            let mut page = OverflowPage::new(page_id, page_size);
             page.data = remaining[..chunk_size].to_vec();
             page.data_length = chunk_size as u32;
             pages.push(page);
@@ -939,28 +2256,15 @@ impl SikioDB {
     fn is_overflow_marker(data: &[u8]) -> bool {
         data.len() >= OVERFLOW_MARKER_SIZE && data[0] == OVERFLOW_MARKER_PREFIX
     }
-    fn checkpoint(&mut self) -> Result<()> {
-        let dirty_ids = self.cache.dirty_pages();
-        let mut sorted_ids = dirty_ids;
-        sorted_ids.sort();
-        for page_id in sorted_ids {
-            if let Some(page) = self.cache.get(page_id) {
-                let bytes = page.to_bytes();
-                self.storage.write_page(page_id, &bytes)?;
-            }
-        }
-        self.storage.flush_data()?;
-        self.write_metadata()?;
-        self.storage.flush_data()?;
-        self.storage.truncate_wal()?;
-        self.wal_sequence = 0;
-        self.cache.clear_dirty();
-        Ok(())
+    #[wasm_bindgen(js_name = checkpoint)]
+    pub fn js_checkpoint(&mut self) -> std::result::Result<(), JsValue> {
+        self.checkpoint()
+            .map_err(|e| JsValue::from_str(&e.to_string()))
     }
 
     #[wasm_bindgen(js_name = beginWriteTxn)]
     pub fn js_begin_write(&mut self) -> JsWriteTransaction {
-        JsWriteTransaction::new(self.wal_sequence)
+        JsWriteTransaction::new(self.lsn)
     }
 
     #[wasm_bindgen(js_name = commitTxn)]
@@ -972,148 +2276,2751 @@ impl SikioDB {
             return Err(JsValue::from_str("Transaction already finished"));
         }
 
-        if txn.ops_count() == 0 {
-            txn.abort();
-            return Ok(());
-        }
+        self.commit_transaction(&mut txn.inner)
+            .map_err(|e| error_to_js(&e))
+    }
+
+    #[wasm_bindgen(js_name = putIfAbsent)]
+    pub fn js_put_if_absent(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+    ) -> std::result::Result<bool, JsValue> {
+        self.put_if_absent(key, value)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
 
-        let new_sequence = txn.prepare_wal(self.wal_sequence);
+    #[wasm_bindgen(js_name = compareAndSwap)]
+    pub fn js_compare_and_swap(
+        &mut self,
+        key: &[u8],
+        expected: Option<Vec<u8>>,
+        new_value: &[u8],
+    ) -> std::result::Result<bool, JsValue> {
+        self.compare_and_swap(key, expected.as_deref(), new_value)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
 
-        self.storage
-            .append_wal(&txn.get_wal_bytes())
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
-        self.storage
-            .flush_wal()
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    #[wasm_bindgen(js_name = deleteIfEquals)]
+    pub fn js_delete_if_equals(
+        &mut self,
+        key: &[u8],
+        expected: &[u8],
+    ) -> std::result::Result<bool, JsValue> {
+        self.delete_if_equals(key, expected)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
 
-        self.wal_sequence = new_sequence;
+    #[wasm_bindgen(js_name = getAndSet)]
+    pub fn js_get_and_set(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+    ) -> std::result::Result<Option<Vec<u8>>, JsValue> {
+        self.get_and_set(key, value)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
 
-        let ops = txn.take_ops_internal();
-        for op in ops {
-            match op {
-                TransactionOp::Put { key, value } => {
-                    self.apply_put(&key, &value)
-                        .map_err(|e| JsValue::from_str(&e.to_string()))?;
-                }
-                TransactionOp::Delete { key } => {
-                    self.apply_delete(&key)
-                        .map_err(|e| JsValue::from_str(&e.to_string()))?;
-                }
-            }
-        }
+    #[wasm_bindgen(js_name = keyVersion)]
+    pub fn js_key_version(&mut self, key: &[u8]) -> std::result::Result<u64, JsValue> {
+        self.key_version(key)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
 
-        if self.storage.wal_size() > WAL_CHECKPOINT_THRESHOLD {
-            self.checkpoint()
-                .map_err(|e| JsValue::from_str(&e.to_string()))?;
-        }
+    #[wasm_bindgen(js_name = txnGet)]
+    pub fn js_txn_get(
+        &mut self,
+        txn: &mut JsWriteTransaction,
+        key: &[u8],
+    ) -> std::result::Result<Option<Vec<u8>>, JsValue> {
+        self.txn_get(&mut txn.inner, key)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
 
-        Ok(())
+    #[wasm_bindgen(js_name = txnScan)]
+    pub fn js_txn_scan(
+        &mut self,
+        txn: &mut JsWriteTransaction,
+        start_key: &[u8],
+        end_key: &[u8],
+        limit: u32,
+    ) -> std::result::Result<js_sys::Array, JsValue> {
+        let pairs = self
+            .txn_scan(&mut txn.inner, start_key, end_key, limit as usize)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let results = js_sys::Array::new();
+        for (key, value) in pairs {
+            let entry = js_sys::Object::new();
+            let key_arr = js_sys::Uint8Array::from(&key[..]);
+            let val_arr = js_sys::Uint8Array::from(&value[..]);
+            js_sys::Reflect::set(&entry, &"key".into(), &key_arr)?;
+            js_sys::Reflect::set(&entry, &"value".into(), &val_arr)?;
+            results.push(&entry);
+        }
+        Ok(results)
     }
 }
 
 impl SikioDB {
-    pub fn begin_write(&mut self) -> WriteTransaction {
-        WriteTransaction::new(self.wal_sequence)
+    pub fn open_with_storage(storage: Box<dyn StorageBackend>) -> Result<SikioDB> {
+        Self::open_with_storage_config(storage, &DatabaseConfig::default())
     }
 
-    pub fn begin_read(&self) -> ReadTransaction {
-        ReadTransaction::new(self.btree.root_page_id())
+    pub fn open_with_storage_config(
+        storage: Box<dyn StorageBackend>,
+        config: &DatabaseConfig,
+    ) -> Result<SikioDB> {
+        validate_page_size(storage.page_size())?;
+        if config.wal_archive.is_some() {
+            storage.archived_wal_segments()?;
+        }
+        let mut db = SikioDB {
+            storage,
+            btree: BTree::new(),
+            cache: PageCache::new(),
+            lsn: 0,
+            commit_lsn: 0,
+            generation: 0,
+            imaged_pages: HashSet::new(),
+            auto_compaction: config.auto_compaction,
+            vacuum_config: config.vacuum.clone(),
+            compaction: CompactionProgress::default(),
+            compacting: false,
+            group_commit: config.group_commit.clone(),
+            pending_wal: Vec::new(),
+            pending_changes: Vec::new(),
+            durability: config.durability,
+            last_wal_flush: now_ms(),
+            checkpoint_policy: config.checkpoint.clone(),
+            last_checkpoint: now_ms(),
+            wal_archive: config.wal_archive.clone(),
+            changes: None,
+            recovery: RecoveryReport::default(),
+            fail_on_corrupt_wal: config.fail_on_corrupt_wal,
+            paged: None,
+            op: None,
+        };
+        db.recover(config.change_log.clone())?;
+        Ok(db)
     }
 
-    pub fn commit_transaction(&mut self, txn: &mut WriteTransaction) -> Result<()> {
-        if !txn.is_active() {
-            return Err(SikioError::Corrupted("Transaction already finished".into()));
+    // No group commit: a retried write reloads from the WAL in storage.
+    pub async fn open_paged<A: AsyncStorageBackend + 'static>(
+        storage: PagedStorage<A>,
+        config: &DatabaseConfig,
+    ) -> Result<SikioDB> {
+        let mut handle = storage.clone();
+        loop {
+            handle.begin_op();
+            let result = Self::open_with_storage_config(Box::new(storage.clone()), config);
+            if !handle.needs_load() {
+                handle.end_op();
+                let mut db = result?;
+                db.group_commit = None;
+                return Ok(db);
+            }
+            drop(result);
+            handle.undo_op();
+            storage.load().await?;
         }
+    }
 
-        if txn.ops_count() == 0 {
-            txn.abort();
-            return Ok(());
+    pub async fn run_paged<A: AsyncStorageBackend, T>(
+        &mut self,
+        storage: &PagedStorage<A>,
+        read_only: bool,
+        mut op: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        loop {
+            self.begin_op();
+            let result = op(self);
+            if self.end_op(read_only) {
+                return result;
+            }
+            drop(result);
+            loop {
+                storage.load().await?;
+                if self.prepare_retry()? {
+                    break;
+                }
+            }
         }
+    }
 
-        let new_sequence = txn.prepare_wal(self.wal_sequence);
+    pub fn begin_op(&mut self) {
+        if let Some(mark) = &mut self.op {
+            if mark.retrying {
+                mark.retrying = false;
+                return;
+            }
+        }
+        self.storage.begin_op();
+        self.op = Some(OpMark {
+            compaction: self.compaction.clone(),
+            latest: self.changes.as_ref().map(ChangeLog::latest),
+            retrying: false,
+            undone: None,
+        });
+    }
 
-        self.storage.append_wal(txn.wal_bytes())?;
-        self.storage.flush_wal()?;
+    pub fn end_op(&mut self, read_only: bool) -> bool {
+        if !self.storage.needs_load() {
+            self.storage.end_op();
+            self.op = None;
+            return true;
+        }
+        self.storage.undo_op();
+        if let (false, Some(mark)) = (read_only, &mut self.op) {
+            let mut changes = self.changes.take();
+            if let (Some(log), Some(latest)) = (&mut changes, mark.latest) {
+                log.truncate_after(latest);
+            }
+            mark.undone = Some((changes, std::mem::take(&mut self.recovery)));
+        }
+        false
+    }
+
+    pub fn prepare_retry(&mut self) -> Result<bool> {
+        self.storage.begin_op();
+        let Some(mark) = self.op.as_mut() else {
+            return Ok(true);
+        };
+        let Some((changes, recovery)) = mark.undone.take() else {
+            mark.retrying = true;
+            return Ok(true);
+        };
+        let compaction = mark.compaction.clone();
+        let result = self.reload_with(None);
+        if self.storage.needs_load() {
+            self.storage.undo_op();
+            if let Some(mark) = &mut self.op {
+                mark.undone = Some((changes, recovery));
+            }
+            return Ok(false);
+        }
+        if let Err(e) = result {
+            self.storage.undo_op();
+            self.op = None;
+            return Err(e);
+        }
+        self.changes = changes;
+        self.recovery = recovery;
+        self.compaction = compaction;
+        if let Some(mark) = &mut self.op {
+            mark.retrying = true;
+        }
+        Ok(true)
+    }
+
+    pub fn needs_load(&self) -> bool {
+        self.storage.needs_load()
+    }
+
+    pub fn open_in_memory() -> Result<SikioDB> {
+        Self::open_in_memory_with_config(&DatabaseConfig::default())
+    }
+
+    pub fn open_in_memory_with_config(config: &DatabaseConfig) -> Result<SikioDB> {
+        validate_page_size(config.page_size)?;
+        Self::open_with_storage_config(
+            Box::new(MemoryStorage::with_page_size(config.page_size)),
+            config,
+        )
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_path(path: impl AsRef<std::path::Path>) -> Result<SikioDB> {
+        Self::open_path_with_config(path, &DatabaseConfig::default())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_path_with_config(
+        path: impl AsRef<std::path::Path>,
+        config: &DatabaseConfig,
+    ) -> Result<SikioDB> {
+        validate_page_size(config.page_size)?;
+        let storage = crate::fs_storage::FileStorage::open_with_page_size(path, config.page_size)?;
+        Self::open_with_storage_config(Box::new(storage), config)
+    }
+
+    pub fn bulk_load(
+        &mut self,
+        entries: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    ) -> Result<u32> {
+        let pairs: Vec<_> = entries
+            .into_iter()
+            .map(|(key, value)| (key, wrap_raw_value(&value)))
+            .collect();
+        let count = pairs
+            .iter()
+            .map(|(key, _)| key.as_slice())
+            .collect::<HashSet<_>>()
+            .len() as u32;
+        self.put_pairs(pairs)?;
+        Ok(count)
+    }
+
+    pub fn put_if_absent(&mut self, key: &[u8], value: &[u8]) -> Result<bool> {
+        self.compare_and_swap(key, None, value)
+    }
+
+    pub fn compare_and_swap(
+        &mut self,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new_value: &[u8],
+    ) -> Result<bool> {
+        if self.get_internal(key)?.as_deref() != expected {
+            return Ok(false);
+        }
+        self.put_internal(key, &wrap_raw_value(new_value))?;
+        Ok(true)
+    }
+
+    pub fn delete_if_equals(&mut self, key: &[u8], expected: &[u8]) -> Result<bool> {
+        if self.get_internal(key)?.as_deref() != Some(expected) {
+            return Ok(false);
+        }
+        self.delete_internal(key)
+    }
+
+    pub fn get_and_set(&mut self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        let previous = self.get_internal(key)?;
+        self.put_internal(key, &wrap_raw_value(value))?;
+        Ok(previous)
+    }
+
+    pub fn begin_write(&mut self) -> WriteTransaction {
+        WriteTransaction::new(self.lsn)
+    }
+
+    pub fn checkpoint(&mut self) -> Result<()> {
+        self.commit_group()?;
+        self.btree.free_list_mut().prepare_checkpoint();
+        for page in self.btree.free_list_mut().take_dirty_pages() {
+            self.cache.insert(page, true);
+        }
+        let dirty_ids = self.cache.dirty_pages();
+        let mut sorted_ids = dirty_ids;
+        sorted_ids.sort();
+        if !sorted_ids.is_empty() || self.storage.wal_size() > 0 {
+            self.lsn += 1;
+            self.log_page_images(&sorted_ids)?;
+        }
+        for page_id in sorted_ids {
+            if let Some(page) = self.cache.get(page_id) {
+                let bytes = page.to_bytes();
+                self.storage.write_page(page_id, &bytes)?;
+            }
+        }
+        self.storage.flush_data()?;
+        if self.auto_compaction
+            && !self.compacting
+            && should_compact(&self.btree, self.vacuum_config.fragmentation_threshold)
+        {
+            self.compaction.pending = true;
+        }
+        self.write_metadata()?;
+        self.archive_wal()?;
+        self.storage.truncate_wal()?;
+        self.imaged_pages.clear();
+        self.btree.free_list_mut().finish_checkpoint();
+        self.cache.clear_dirty();
+        self.last_checkpoint = now_ms();
+        Ok(())
+    }
+
+    pub fn restore_to(
+        &mut self,
+        base: Box<dyn StorageBackend>,
+        point: RestorePoint,
+    ) -> Result<SikioDB> {
+        self.commit_group()?;
+        let mut restored = SikioDB::open_with_storage(base)?;
+        let cursors = self.wal_cursors()?;
+        let live = cursors.len() - 1;
+        let mut previous = restored.lsn;
+        let mut pending_ops: Vec<WalEntry> = Vec::new();
+        'logs: for (index, mut cursor) in cursors.into_iter().enumerate() {
+            while let Some(entry) = cursor.next_entry(self.storage.as_ref()) {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(SikioError::IoError(reason)) => return Err(SikioError::IoError(reason)),
+                    Err(_) if index == live => break 'logs,
+                    Err(e) => return Err(e),
+                };
+                if entry.sequence > previous + 1 {
+                    return Err(SikioError::WalGap {
+                        expected: previous + 1,
+                        found: entry.sequence,
+                    });
+                }
+                previous = previous.max(entry.sequence);
+                match entry.operation {
+                    WalOperation::Checkpoint => pending_ops.clear(),
+                    WalOperation::Commit => {
+                        let past = match point {
+                            RestorePoint::Lsn(lsn) => entry.sequence > lsn,
+                            RestorePoint::Timestamp(time) => {
+                                entry.commit_time().is_some_and(|t| t > time)
+                            }
+                        };
+                        if past {
+                            break 'logs;
+                        }
+                        let ops = std::mem::take(&mut pending_ops);
+                        if entry.sequence <= restored.lsn {
+                            continue;
+                        }
+                        for op in ops {
+                            restored.redo(op)?;
+                        }
+                        restored.lsn = entry.sequence;
+                    }
+                    WalOperation::Put | WalOperation::Delete => pending_ops.push(entry),
+                    WalOperation::PageImage => {}
+                }
+            }
+            pending_ops.clear();
+        }
+        restored.commit_lsn = restored.lsn;
+        restored.checkpoint()?;
+        Ok(restored)
+    }
+
+    // Cursors over the archived segments oldest first, followed by the live
+    // WAL. Nothing is read until a cursor is advanced.
+    fn wal_cursors(&self) -> Result<Vec<WalCursor>> {
+        let mut cursors = Vec::new();
+        if self.wal_archive.is_some() {
+            for segment in self.storage.archived_wal_segments()? {
+                let size = self.storage.archived_wal_size(segment)?;
+                cursors.push(WalCursor::archived(segment, size));
+            }
+        }
+        cursors.push(WalCursor::new(self.storage.wal_size()));
+        Ok(cursors)
+    }
+
+    fn replay_archived_changes(&self, config: ChangeLogConfig) -> Result<ChangeLogReplay> {
+        let mut cursors = self.wal_cursors()?;
+        cursors.pop();
+        let (mut count, mut bytes) = (0, 0);
+        let mut first = cursors.len();
+        while first > 0 && count <= config.max_changes && bytes <= config.max_bytes {
+            first -= 1;
+            let (mut pending_count, mut pending_bytes) = (0, 0);
+            let cursor = &mut cursors[first];
+            while let Some(entry) = cursor.next_entry(self.storage.as_ref()) {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(SikioError::IoError(reason)) => return Err(SikioError::IoError(reason)),
+                    Err(_) => break,
+                };
+                match entry.operation {
+                    WalOperation::Checkpoint => (pending_count, pending_bytes) = (0, 0),
+                    WalOperation::Commit => {
+                        count += std::mem::take(&mut pending_count);
+                        bytes += std::mem::take(&mut pending_bytes);
+                    }
+                    WalOperation::Put | WalOperation::Delete => {
+                        pending_count += 1;
+                        pending_bytes += wal_change(entry.sequence, &entry).size();
+                    }
+                    WalOperation::PageImage => {}
+                }
+            }
+        }
+        let mut replay = ChangeLogReplay::new(config);
+        let mut cursors = self.wal_cursors()?;
+        cursors.pop();
+        for mut cursor in cursors.into_iter().skip(first) {
+            while let Some(entry) = cursor.next_entry(self.storage.as_ref()) {
+                match entry {
+                    Ok(entry) => replay.feed(&entry),
+                    Err(SikioError::IoError(reason)) => return Err(SikioError::IoError(reason)),
+                    Err(_) => break,
+                }
+            }
+        }
+        Ok(replay)
+    }
+
+    fn archive_wal(&mut self) -> Result<()> {
+        let retain = match &self.wal_archive {
+            Some(archive) => archive.retain_segments,
+            None => return Ok(()),
+        };
+        if self.storage.wal_size() == 0 {
+            return Ok(());
+        }
+        let mut segments = self.storage.archived_wal_segments()?;
+        let segment = segments.last().map_or(1, |last| last + 1);
+        self.storage.archive_wal(segment)?;
+        segments.push(segment);
+        let excess = segments.len().saturating_sub(retain.max(1));
+        for &old in &segments[..excess] {
+            self.storage.remove_archived_wal(old)?;
+        }
+        Ok(())
+    }
+
+    pub fn changes_since(&self, lsn: u64, limit: usize) -> Result<ChangeBatch> {
+        match &self.changes {
+            Some(changes) => changes.changes_since(lsn, limit),
+            None => Err(SikioError::ChangeLogDisabled),
+        }
+    }
+
+    pub fn recovery_report(&self) -> &RecoveryReport {
+        &self.recovery
+    }
+
+    pub fn commit_group(&mut self) -> Result<()> {
+        if self.pending_wal.is_empty() {
+            return Ok(());
+        }
+        let wal_len = self.storage.wal_size();
+        let pending = std::mem::take(&mut self.pending_wal);
+        let result = match self.storage.append_wal(&pending) {
+            Ok(_) => self.flush_wal(),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            let _ = self.storage.truncate_wal_to(wal_len);
+            let _ = self.storage.flush_wal();
+            self.pending_changes.clear();
+            self.reload()?;
+            return Err(e);
+        }
+        self.publish_group();
+        Ok(())
+    }
+
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    pub fn flush_if_due(&mut self) -> Result<bool> {
+        match self.durability {
+            Durability::Periodic(interval) if now_ms() >= self.last_wal_flush + interval => {
+                self.flush_wal()?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    pub fn set_durability(&mut self, durability: Durability) -> Result<()> {
+        self.commit_group()?;
+        self.flush_wal()?;
+        self.durability = durability;
+        Ok(())
+    }
+
+    pub fn begin_read(&self) -> ReadTransaction {
+        ReadTransaction::new(self.btree.root_page_id())
+    }
+
+    pub fn txn_get(&mut self, txn: &mut WriteTransaction, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let version = self.key_version(key)?;
+        txn.record_read(key, version);
+        let root = self.btree.root_page_id();
+        txn.get(key, root, self.storage.as_ref(), &mut self.cache)
+    }
+
+    pub fn txn_scan(
+        &mut self,
+        txn: &mut WriteTransaction,
+        start_key: &[u8],
+        end_key: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let root = self.btree.root_page_id();
+        let pairs = txn.scan(
+            start_key,
+            end_key,
+            limit,
+            root,
+            self.storage.as_ref(),
+            &mut self.cache,
+        )?;
+        let scanned_to = match pairs.last() {
+            Some((last, _)) if pairs.len() >= limit => last.as_slice(),
+            _ => end_key,
+        };
+        let version = self.range_lsn(start_key, scanned_to)?;
+        txn.record_range(start_key, scanned_to, version);
+        Ok(pairs)
+    }
+
+    pub fn commit_transaction(&mut self, txn: &mut WriteTransaction) -> Result<()> {
+        if !txn.is_active() {
+            return Err(SikioError::Corrupted("Transaction already finished".into()));
+        }
+
+        if let Err(e) = self.validate_reads(txn) {
+            if !self.storage.needs_load() {
+                txn.abort();
+            }
+            return Err(e);
+        }
+
+        if txn.ops_count() == 0 {
+            txn.abort();
+            return Ok(());
+        }
+
+        let keep = self.effective_ops(txn.ops())?;
+        txn.retain_ops(&keep);
+        if txn.ops_count() == 0 {
+            txn.take_ops();
+            return Ok(());
+        }
+
+        let new_sequence = txn.prepare_wal(self.lsn);
+
+        let stored = self.with_rollback(|db| {
+            let mut stored = Vec::with_capacity(txn.ops_count());
+            for op in txn.ops() {
+                match op {
+                    TransactionOp::Put { value, .. } => stored.push(db.store_value(value)?),
+                    TransactionOp::Delete { .. } => stored.push(Vec::new()),
+                }
+            }
+            db.append_wal_committed(txn.wal_bytes())?;
+            Ok(stored)
+        })?;
+
+        self.lsn = new_sequence;
+        self.commit_lsn = new_sequence;
+
+        let applied = self.apply_ops(txn.ops(), &stored);
+        if applied.is_err() && self.storage.needs_load() {
+            return applied.map(drop);
+        }
+        let ops = txn.take_ops();
+        let recording = self.changes.is_some();
+        let mut changes = Vec::new();
+        for (op, applied) in ops.into_iter().zip(applied?) {
+            if !recording || !applied {
+                continue;
+            }
+            match op {
+                TransactionOp::Put { key, value } => {
+                    changes.push(self.change(ChangeOp::Put, key, Some(&value)));
+                }
+                TransactionOp::Delete { key } => {
+                    changes.push(self.change(ChangeOp::Delete, key, None));
+                }
+            }
+        }
+        self.record_changes(changes);
+
+        self.maybe_checkpoint()
+    }
+
+    fn apply_ops(&mut self, ops: &[TransactionOp], stored: &[Vec<u8>]) -> Result<Vec<bool>> {
+        let mut applied = Vec::with_capacity(ops.len());
+        for (op, stored_value) in ops.iter().zip(stored) {
+            match op {
+                TransactionOp::Put { key, .. } => {
+                    self.apply_put_value(key, stored_value)?;
+                    applied.push(true);
+                }
+                TransactionOp::Delete { key } => applied.push(self.apply_delete(key)?),
+            }
+        }
+        Ok(applied)
+    }
+
+    fn effective_ops(&mut self, ops: &[TransactionOp]) -> Result<Vec<bool>> {
+        let mut present: HashMap<&[u8], bool> = HashMap::new();
+        let mut keep = Vec::with_capacity(ops.len());
+        for op in ops {
+            match op {
+                TransactionOp::Put { key, .. } => {
+                    present.insert(key, true);
+                    keep.push(true);
+                }
+                TransactionOp::Delete { key } => {
+                    let exists = match present.get(key.as_slice()) {
+                        Some(&exists) => exists,
+                        None => self.contains_key(key)?,
+                    };
+                    present.insert(key, false);
+                    keep.push(exists);
+                }
+            }
+        }
+        Ok(keep)
+    }
+
+    fn validate_reads(&mut self, txn: &WriteTransaction) -> Result<()> {
+        let ranges = txn
+            .reads()
+            .map(|(key, read)| (key, key, read))
+            .chain(txn.ranges());
+        for (start, end, read) in ranges {
+            let current = self.range_lsn(start, end)?;
+            if current > read {
+                return Err(SikioError::TransactionConflict {
+                    key: start.to_vec(),
+                    read,
+                    current,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn key_version(&mut self, key: &[u8]) -> Result<u64> {
+        self.range_lsn(key, key)
+    }
+
+    pub fn vacuum(&mut self) -> Result<CompactionStats> {
+        self.vacuum_with_config(&VacuumConfig::default())
+    }
+
+    pub fn vacuum_with_config(&mut self, config: &VacuumConfig) -> Result<CompactionStats> {
+        self.checkpoint()?;
+        let page_size = self.storage.page_size() as u64;
+        let data_size = self.storage.data_size();
+        let units = self.collect_live_units()?;
+        let live: HashSet<u64> = units.iter().flat_map(|u| u.pages.iter().copied()).collect();
+        let trunk_pages = self.btree.free_list().trunk_page_ids();
+        let free_ids: Vec<u64> = (2..self.btree.next_page_id())
+            .filter(|id| !live.contains(id) && !trunk_pages.contains(id))
+            .collect();
+        let unit_pages: Vec<Vec<u64>> = units.iter().map(|u| u.pages.clone()).collect();
+        let plan = plan_relocations(&unit_pages, &free_ids, config.max_pages_per_run);
+
+        let mut moved = HashMap::new();
+        for (index, dests) in &plan {
+            let unit = &units[*index];
+            match unit.owner {
+                PageOwner::Overflow { leaf, index } => {
+                    self.relocate_overflow_chain(&unit.pages, dests, leaf, index, &moved)?
+                }
+                _ => self.relocate_node(unit.pages[0], dests[0], &unit.owner, &moved)?,
+            }
+            for (&from, &to) in unit.pages.iter().zip(dests) {
+                moved.insert(from, to);
+            }
+        }
+
+        let new_end = live
+            .iter()
+            .map(|id| moved.get(id).copied().unwrap_or(*id))
+            .max()
+            .map_or(2, |id| id + 1);
+        let used: HashSet<u64> = moved.values().copied().collect();
+        let mut pool: Vec<u64> = free_ids
+            .into_iter()
+            .filter(|id| !used.contains(id))
+            .chain(moved.keys().copied())
+            .chain(trunk_pages.iter().copied())
+            .filter(|&id| id < new_end)
+            .collect();
+        pool.sort_unstable_by(|a, b| b.cmp(a));
+        for &page_id in &trunk_pages {
+            self.cache.remove(page_id);
+        }
+        self.btree
+            .set_free_list(FreeList::from_ids(page_size as usize, pool));
+        self.btree.set_next_page_id(new_end);
+        self.checkpoint()?;
+        let new_size = new_end * page_size;
+        if new_size < data_size {
+            self.storage.truncate_data(new_size)?;
+        }
+
+        let mut stats = CompactionStats::new();
+        stats.pages_scanned = live.len() as u64;
+        stats.pages_moved = moved.len() as u64;
+        stats.pages_freed = (data_size / page_size).saturating_sub(new_end);
+        stats.bytes_reclaimed = data_size.saturating_sub(self.storage.data_size());
+        Ok(stats)
+    }
+
+    pub fn compaction_progress(&self) -> &CompactionProgress {
+        &self.compaction
+    }
+
+    pub fn run_idle_compaction(&mut self) -> Result<Option<CompactionStats>> {
+        if !self.compaction.pending {
+            return Ok(None);
+        }
+        let config = self.vacuum_config.clone();
+        let deadline = now_ms() + config.time_budget_ms;
+        let mut stats = CompactionStats::new();
+        self.compacting = true;
+        let result = loop {
+            let step = match self.vacuum_with_config(&config) {
+                Ok(step) => step,
+                Err(e) => break Err(e),
+            };
+            stats.pages_scanned = step.pages_scanned;
+            stats.pages_moved += step.pages_moved;
+            stats.pages_freed += step.pages_freed;
+            stats.bytes_reclaimed += step.bytes_reclaimed;
+            let fragmented = should_compact(&self.btree, config.fragmentation_threshold);
+            if step.pages_moved == 0 || !fragmented {
+                self.compaction.pending = false;
+                break Ok(());
+            }
+            if now_ms() >= deadline {
+                break Ok(());
+            }
+        };
+        if result.is_ok() {
+            self.compaction.record(&stats);
+        }
+        let result = result.and_then(|_| self.checkpoint());
+        self.compacting = false;
+        result.map(|_| Some(stats))
+    }
+
+    pub fn vacuum_into_storage(
+        &mut self,
+        target: &mut dyn StorageBackend,
+    ) -> Result<CompactionStats> {
+        if target.data_size() > 0 || target.wal_size() > 0 {
+            return Err(SikioError::IoError("Vacuum target is not empty".into()));
+        }
+        self.checkpoint()?;
+        let page_size = target.page_size();
+        let mut builder = BulkBuilder::new(target, 2);
+        let mut stack = vec![self.btree.root_page_id()];
+        while let Some(page_id) = stack.pop() {
+            let node = self.load_node(page_id)?;
+            if !node.is_leaf {
+                stack.extend(node.children.iter().rev());
+                continue;
+            }
+            for (key, stored) in node.keys.into_iter().zip(node.values) {
+                let value = self.get_value_resolved(&stored)?;
+                let value = if value.len() > overflow_threshold(page_size) {
+                    let (start_page, total_len) = builder.write_overflow(&compress(&value))?;
+                    Self::encode_overflow_marker(start_page, total_len)
+                } else {
+                    value
+                };
+                builder.add(key, value)?;
+            }
+        }
+        let (root_page_id, next_page_id) = builder.finish()?;
+        let meta = Metadata {
+            root_page_id,
+            next_page_id,
+            lsn: self.lsn,
+            generation: 0,
+            page_size,
+            freelist_root: 0,
+            free_page_count: 0,
+            free_page_ids: Vec::new(),
+            compaction: CompactionProgress::default(),
+        };
+        let bytes = meta.to_bytes();
+        target.write_page(METADATA_PAGE_ID_1, &bytes)?;
+        target.write_page(METADATA_PAGE_ID_2, &bytes)?;
+        target.flush_data()?;
+
+        let mut stats = CompactionStats::new();
+        stats.pages_scanned = self.storage.data_page_count();
+        stats.pages_moved = next_page_id - 2;
+        stats.pages_freed = stats.pages_scanned.saturating_sub(next_page_id);
+        stats.bytes_reclaimed = self.storage.data_size().saturating_sub(target.data_size());
+        Ok(stats)
+    }
+
+    pub fn replace_with_image(&mut self, image: &dyn StorageBackend) -> Result<()> {
+        let page_size = self.storage.page_size();
+        if image.page_size() != page_size {
+            return Err(SikioError::PageSizeMismatch {
+                stored: image.page_size(),
+                requested: page_size,
+            });
+        }
+        let meta = Metadata::from_bytes(&image.read_page(METADATA_PAGE_ID_1)?)
+            .ok_or_else(|| SikioError::Corrupted("Vacuum image has no valid metadata".into()))?;
+        self.checkpoint()?;
+        self.lsn += 1;
+        let page_ids: Vec<u64> = (2..meta.next_page_id).collect();
+        for chunk in page_ids.chunks(IMAGE_BATCH_PAGES) {
+            self.log_page_images(chunk)?;
+        }
+        for &page_id in &page_ids {
+            let bytes = image.read_page(page_id)?;
+            self.storage.write_page(page_id, &bytes)?;
+        }
+        self.storage.flush_data()?;
+        self.cache.clear();
+        self.btree = BTree::with_root(meta.root_page_id, meta.next_page_id);
+        self.btree.set_free_list(FreeList::new(page_size));
+        self.compaction.pending = false;
+        self.write_metadata()?;
+        self.archive_wal()?;
+        self.storage.truncate_wal()?;
+        self.imaged_pages.clear();
+        let new_size = meta.next_page_id * page_size as u64;
+        if new_size < self.storage.data_size() {
+            self.storage.truncate_data(new_size)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn vacuum_into(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        swap: bool,
+    ) -> Result<CompactionStats> {
+        let page_size = self.storage.page_size();
+        let mut target = crate::fs_storage::FileStorage::open_with_page_size(&path, page_size)?;
+        let stats = self.vacuum_into_storage(&mut target)?;
+        if swap {
+            self.replace_with_image(&target)?;
+            target.remove()?;
+        }
+        Ok(stats)
+    }
+
+    fn collect_live_units(&mut self) -> Result<Vec<LiveUnit>> {
+        let mut units = Vec::new();
+        let mut stack = vec![(self.btree.root_page_id(), PageOwner::Root)];
+        while let Some((page_id, owner)) = stack.pop() {
+            let node = self.load_node(page_id)?;
+            if node.is_leaf {
+                for (index, value) in node.values.iter().enumerate() {
+                    if let Some((start_page, total_len)) = Self::decode_overflow_marker(value) {
+                        units.push(LiveUnit {
+                            pages: self.overflow_chain_pages(start_page, total_len)?,
+                            owner: PageOwner::Overflow {
+                                leaf: page_id,
+                                index,
+                            },
+                        });
+                    }
+                }
+            } else {
+                for (index, &child) in node.children.iter().enumerate() {
+                    stack.push((
+                        child,
+                        PageOwner::Child {
+                            parent: page_id,
+                            index,
+                        },
+                    ));
+                }
+            }
+            units.push(LiveUnit {
+                pages: vec![page_id],
+                owner,
+            });
+        }
+        Ok(units)
+    }
+
+    fn overflow_chain_pages(&self, start_page: u64, total_len: u32) -> Result<Vec<u64>> {
+        let mut pages = Vec::new();
+        let mut remaining = total_len as usize;
+        let mut current_page_id = start_page;
+        while current_page_id != 0 && remaining > 0 {
+            if pages.len() as u64 >= self.storage.data_page_count() {
+                return Err(SikioError::Corrupted("Overflow chain is cyclic".into()));
+            }
+            let page = OverflowPage::from_bytes(&self.storage.read_page(current_page_id)?)?;
+            pages.push(current_page_id);
+            remaining = remaining.saturating_sub(page.data.len());
+            current_page_id = page.next_page;
+        }
+        Ok(pages)
+    }
+
+    fn relocate_node(
+        &mut self,
+        from: u64,
+        to: u64,
+        owner: &PageOwner,
+        moved: &HashMap<u64, u64>,
+    ) -> Result<()> {
+        let mut node = self.load_node(from)?;
+        node.page_id = to;
+        self.save_node(&node)?;
+        self.cache.remove(from);
+        match *owner {
+            PageOwner::Child { parent, index } => {
+                let parent_id = moved.get(&parent).copied().unwrap_or(parent);
+                let mut parent = self.load_node(parent_id)?;
+                parent.children[index] = to;
+                self.save_node(&parent)?;
+            }
+            _ => self.btree.set_root(to),
+        }
+        Ok(())
+    }
+
+    fn relocate_overflow_chain(
+        &mut self,
+        pages: &[u64],
+        dests: &[u64],
+        leaf: u64,
+        index: usize,
+        moved: &HashMap<u64, u64>,
+    ) -> Result<()> {
+        for (i, (&from, &to)) in pages.iter().zip(dests).enumerate() {
+            let mut page = OverflowPage::from_bytes(&self.storage.read_page(from)?)?;
+            page.page_id = to;
+            page.next_page = dests.get(i + 1).copied().unwrap_or(0);
+            self.storage.write_page(to, &page.to_bytes())?;
+        }
+        let leaf_id = moved.get(&leaf).copied().unwrap_or(leaf);
+        let mut node = self.load_node(leaf_id)?;
+        node.values[index][1..9].copy_from_slice(&dests[0].to_le_bytes());
+        self.save_node(&node)
+    }
+}
+
+#[wasm_bindgen]
+pub struct VacuumTarget {
+    sync: Option<OPFSStorage>,
+    paged: Option<PagedStorage<AsyncOPFSStorage>>,
+}
+
+#[wasm_bindgen]
+impl VacuumTarget {
+    #[wasm_bindgen(js_name = open)]
+    pub async fn js_open(
+        name: &str,
+        page_size: usize,
+        sync_access: bool,
+    ) -> std::result::Result<VacuumTarget, JsValue> {
+        validate_page_size(page_size).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        if sync_access {
+            let storage = OPFSStorage::open_with_page_size(name, page_size).await?;
+            return Ok(VacuumTarget {
+                sync: Some(storage),
+                paged: None,
+            });
+        }
+        let backend = AsyncOPFSStorage::open_with_page_size(name, page_size).await?;
+        Ok(VacuumTarget {
+            sync: None,
+            paged: Some(PagedStorage::new(backend)),
+        })
+    }
+
+    #[wasm_bindgen(js_name = beginOp)]
+    pub fn begin_op(&mut self) {
+        if let Some(storage) = &mut self.paged {
+            storage.begin_op();
+        }
+    }
+
+    #[wasm_bindgen(js_name = endOp)]
+    pub fn end_op(&mut self, undo: bool) {
+        if let Some(storage) = &mut self.paged {
+            match undo {
+                true => storage.undo_op(),
+                false => storage.end_op(),
+            }
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn persist(&self) -> js_sys::Promise {
+        let paged = self.paged.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            if let Some(storage) = paged {
+                storage
+                    .persist()
+                    .await
+                    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            }
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    #[wasm_bindgen]
+    pub fn close(&mut self) {
+        if let Some(storage) = self.sync.take() {
+            storage.close();
+        }
+        self.paged = None;
+    }
+}
+
+impl VacuumTarget {
+    fn with_storage<T>(
+        &mut self,
+        f: impl FnOnce(&mut dyn StorageBackend) -> Result<T>,
+    ) -> Result<T> {
+        match (&mut self.sync, &mut self.paged) {
+            (Some(storage), _) => f(storage),
+            (None, Some(storage)) => f(storage),
+            (None, None) => Err(SikioError::IoError("Vacuum target is closed".into())),
+        }
+    }
+
+    fn take_storage(&mut self) -> Result<Box<dyn StorageBackend>> {
+        if let Some(storage) = self.sync.take() {
+            return Ok(Box::new(storage));
+        }
+        match &self.paged {
+            Some(storage) => Ok(Box::new(storage.clone())),
+            None => Err(SikioError::IoError("Vacuum target is closed".into())),
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct JsWriteTransaction {
+    inner: WriteTransaction,
+}
+
+#[wasm_bindgen]
+impl JsWriteTransaction {
+    #[wasm_bindgen(constructor)]
+    pub fn new(sequence: u64) -> Self {
+        JsWriteTransaction {
+            inner: WriteTransaction::new(sequence),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn put(&mut self, key: &[u8], value: &[u8]) -> std::result::Result<(), JsValue> {
+        self.inner
+            .put(key.to_vec(), value.to_vec())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen]
+    pub fn delete(&mut self, key: &[u8]) -> std::result::Result<(), JsValue> {
+        self.inner
+            .delete(key.to_vec())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen]
+    pub fn abort(&mut self) {
+        self.inner.abort();
+    }
+
+    #[wasm_bindgen]
+    pub fn savepoint(&mut self) -> std::result::Result<u32, JsValue> {
+        self.inner
+            .savepoint()
+            .map(|savepoint| savepoint.depth() as u32)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = rollbackTo)]
+    pub fn rollback_to(&mut self, savepoint: u32) -> std::result::Result<(), JsValue> {
+        let savepoint = self.savepoint_at(savepoint)?;
+        self.inner
+            .rollback_to(savepoint)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen]
+    pub fn release(&mut self, savepoint: u32) -> std::result::Result<(), JsValue> {
+        let savepoint = self.savepoint_at(savepoint)?;
+        self.inner
+            .release(savepoint)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = recordRead)]
+    pub fn record_read(&mut self, key: &[u8], version: u64) {
+        self.inner.record_read(key, version);
+    }
+
+    #[wasm_bindgen(js_name = isActive)]
+    pub fn is_active_js(&self) -> bool {
+        self.inner.is_active()
+    }
+}
+
+impl JsWriteTransaction {
+    pub fn is_active(&self) -> bool {
+        self.inner.is_active()
+    }
+
+    pub fn ops_count(&self) -> usize {
+        self.inner.ops_count()
+    }
+
+    pub fn prepare_wal(&mut self, sequence: u64) -> u64 {
+        self.inner.prepare_wal(sequence)
+    }
+
+    pub fn get_wal_bytes(&self) -> Vec<u8> {
+        self.inner.wal_bytes().to_vec()
+    }
+
+    pub fn take_ops_internal(&mut self) -> Vec<TransactionOp> {
+        self.inner.take_ops()
+    }
+
+    fn savepoint_at(&self, depth: u32) -> std::result::Result<Savepoint, JsValue> {
+        self.inner
+            .savepoint_at(depth as usize)
+            .ok_or_else(|| JsValue::from_str("Savepoint not active"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::WalReader;
+
+    #[test]
+    fn in_memory_put_get_delete() {
+        let mut db = SikioDB::open_in_memory().unwrap();
+        db.put_internal(b"a", &wrap_raw_value(b"1")).unwrap();
+        db.put_internal(b"b", &wrap_raw_value(b"2")).unwrap();
+        assert_eq!(db.get_internal(b"a").unwrap(), Some(b"1".to_vec()));
+        assert!(db.delete_internal(b"a").unwrap());
+        assert_eq!(db.get_internal(b"a").unwrap(), None);
+        assert_eq!(db.get_internal(b"b").unwrap(), Some(b"2".to_vec()));
+    }
+
+    #[test]
+    fn memory_storage_survives_reopen_through_wal_replay() {
+        let storage = MemoryStorage::new();
+        {
+            let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+            for i in 0..500u32 {
+                let key = format!("key{:05}", i);
+                db.put_internal(key.as_bytes(), &wrap_raw_value(&[7u8; 40]))
+                    .unwrap();
+            }
+            db.put_internal(b"big", &wrap_raw_value(&vec![3u8; 10_000]))
+                .unwrap();
+        }
+        assert!(!storage.wal_bytes().is_empty());
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        assert!(storage.wal_bytes().is_empty());
+        assert_eq!(db.get_internal(b"key00000").unwrap(), Some(vec![7u8; 40]));
+        assert_eq!(db.get_internal(b"key00499").unwrap(), Some(vec![7u8; 40]));
+        assert_eq!(db.get_internal(b"big").unwrap(), Some(vec![3u8; 10_000]));
+        let pairs = db.scan_prefix_internal(b"key001").unwrap();
+        assert_eq!(pairs.len(), 100);
+    }
+
+    #[test]
+    fn group_commit_shares_one_wal_flush() {
+        use crate::fault::FaultInjectingStorage;
+
+        let disk = MemoryStorage::new();
+        let storage = FaultInjectingStorage::new(disk.clone());
+        let faults = storage.handle();
+        let config = DatabaseConfig {
+            group_commit: Some(GroupCommitConfig::default()),
+            change_log: Some(ChangeLogConfig::default()),
+            ..DatabaseConfig::default()
+        };
+        let mut db = SikioDB::open_with_storage_config(Box::new(storage), &config).unwrap();
+        assert_eq!(db.get_internal(b"key000").unwrap(), None);
+        let start = db.commit_lsn;
+        let before = faults.io_count();
+        for i in 0..100u32 {
+            let key = format!("key{:03}", i);
+            db.put_internal(key.as_bytes(), &wrap_raw_value(b"v"))
+                .unwrap();
+        }
+        assert!(db.delete_internal(b"key000").unwrap());
+        assert_eq!(faults.io_count(), before);
+        assert_eq!(db.get_internal(b"key050").unwrap(), Some(b"v".to_vec()));
+        assert!(db.changes_since(start, 1000).unwrap().changes.is_empty());
+        db.commit_group().unwrap();
+        assert_eq!(faults.io_count(), before + 2);
+        assert_eq!(db.pending_commit_bytes(), 0);
+        assert_eq!(db.changes_since(start, 1000).unwrap().changes.len(), 101);
+
+        faults.fail_once_at(faults.io_count() + 2);
+        db.put_internal(b"lost", &wrap_raw_value(b"v")).unwrap();
+        assert!(db.commit_group().is_err());
+        assert_eq!(db.get_internal(b"lost").unwrap(), None);
+        let horizon = db.commit_lsn;
+        db.put_internal(b"kept", &wrap_raw_value(b"v")).unwrap();
+        db.commit_group().unwrap();
+        let changes = db.changes_since(horizon, 10).unwrap().changes;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].key, b"kept".to_vec());
+        assert_eq!(db.get_internal(b"key099").unwrap(), Some(b"v".to_vec()));
+
+        db.put_internal(b"unflushed", &wrap_raw_value(b"v"))
+            .unwrap();
+        faults.crash_at(faults.io_count() + 1);
+        drop(db);
+
+        let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+        assert_eq!(db.get_internal(b"unflushed").unwrap(), None);
+        assert_eq!(db.get_internal(b"key000").unwrap(), None);
+        for i in 1..100u32 {
+            let key = format!("key{:03}", i);
+            assert_eq!(
+                db.get_internal(key.as_bytes()).unwrap(),
+                Some(b"v".to_vec())
+            );
+        }
+    }
+
+    #[test]
+    fn durability_mode_decides_when_the_wal_is_flushed() {
+        use crate::fault::FaultInjectingStorage;
+
+        let disk = MemoryStorage::new();
+        let storage = FaultInjectingStorage::new(disk.clone());
+        let config = DatabaseConfig {
+            durability: Durability::None,
+            ..DatabaseConfig::default()
+        };
+        let mut db = SikioDB::open_with_storage_config(Box::new(storage), &config).unwrap();
+        let value = wrap_raw_value(b"v");
+        db.put_internal(b"none", &value).unwrap();
+        db.put_pairs(vec![(b"batch".to_vec(), value.clone())])
+            .unwrap();
+        let mut txn = db.begin_write();
+        txn.put(b"txn".to_vec(), b"v".to_vec()).unwrap();
+        db.commit_transaction(&mut txn).unwrap();
+        assert!(disk.wal_bytes().is_empty());
+
+        db.set_durability(Durability::Periodic(60_000)).unwrap();
+        let flushed = disk.wal_bytes().len();
+        assert!(flushed > 0);
+        assert!(db.delete_internal(b"batch").unwrap());
+        db.put_internal(b"periodic", &value).unwrap();
+        assert_eq!(disk.wal_bytes().len(), flushed);
+        assert!(!db.flush_if_due().unwrap());
+        db.last_wal_flush -= 60_000;
+        assert!(db.flush_if_due().unwrap());
+        assert!(disk.wal_bytes().len() > flushed);
+        db.put_internal(b"idle", &value).unwrap();
+        db.set_durability(Durability::None).unwrap();
+        let flushed = disk.wal_bytes().len();
+        db.put_internal(b"dropped", &value).unwrap();
+        drop(db);
+        assert_eq!(disk.wal_bytes().len(), flushed);
+
+        let storage = FaultInjectingStorage::new(disk.clone());
+        let faults = storage.handle();
+        let mut db = SikioDB::open_with_storage(Box::new(storage)).unwrap();
+        assert_eq!(db.get_internal(b"dropped").unwrap(), None);
+        db.put_internal(b"full", &value).unwrap();
+        faults.crash_at(faults.io_count() + 1);
+        drop(db);
+
+        let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+        for key in [&b"none"[..], b"txn", b"periodic", b"idle", b"full"] {
+            assert_eq!(db.get_internal(key).unwrap(), Some(b"v".to_vec()));
+        }
+        assert_eq!(db.get_internal(b"batch").unwrap(), None);
+    }
+
+    #[test]
+    fn quota_exceeded_rolls_back_and_keeps_database_usable() {
+        use crate::fault::FaultInjectingStorage;
+
+        let disk = MemoryStorage::new();
+        let storage = FaultInjectingStorage::new(disk.clone());
+        let faults = storage.handle();
+        let mut db = SikioDB::open_with_storage(Box::new(storage)).unwrap();
+        faults.set_quota(Some(64 * 1024));
+        let big: Vec<u8> = (0..20_000u32).map(|i| (i * 7919 % 251) as u8).collect();
+        let mut stored = 0;
+        let err = loop {
+            let key = format!("big{:02}", stored);
+            match db.put_internal(key.as_bytes(), &wrap_raw_value(&big)) {
+                Ok(()) => stored += 1,
+                Err(e) => break e,
+            }
+        };
+        assert!(matches!(err, SikioError::QuotaExceeded));
+        assert!(stored > 0);
+        let failed_key = format!("big{:02}", stored);
+        assert_eq!(db.get_internal(failed_key.as_bytes()).unwrap(), None);
+        assert_eq!(db.get_internal(b"big00").unwrap(), Some(big.clone()));
+        assert!(db.delete_internal(b"big00").unwrap());
+        drop(db);
+
+        let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+        assert_eq!(db.get_internal(b"big00").unwrap(), None);
+        assert_eq!(db.get_internal(failed_key.as_bytes()).unwrap(), None);
+        for i in 1..stored {
+            let key = format!("big{:02}", i);
+            assert_eq!(db.get_internal(key.as_bytes()).unwrap(), Some(big.clone()));
+        }
+    }
+
+    #[test]
+    fn failed_wal_flush_is_rolled_back() {
+        use crate::fault::FaultInjectingStorage;
+
+        let disk = MemoryStorage::new();
+        let storage = FaultInjectingStorage::new(disk.clone());
+        let faults = storage.handle();
+        let mut db = SikioDB::open_with_storage(Box::new(storage)).unwrap();
+        db.put_internal(b"a", &wrap_raw_value(b"1")).unwrap();
+        faults.fail_once_at(faults.io_count() + 3);
+        assert!(db.put_internal(b"b", &wrap_raw_value(b"2")).is_err());
+        assert_eq!(db.get_internal(b"b").unwrap(), None);
+        db.put_internal(b"c", &wrap_raw_value(b"3")).unwrap();
+        drop(db);
+
+        let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+        assert_eq!(db.get_internal(b"a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(db.get_internal(b"b").unwrap(), None);
+        assert_eq!(db.get_internal(b"c").unwrap(), Some(b"3".to_vec()));
+    }
+
+    #[test]
+    fn page_size_is_recorded_and_checked_on_open() {
+        let config = DatabaseConfig {
+            page_size: 16384,
+            ..DatabaseConfig::default()
+        };
+        let storage = MemoryStorage::with_page_size(config.page_size);
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        db.put_internal(b"doc", &wrap_raw_value(&[4u8; 3000]))
+            .unwrap();
+        db.checkpoint().unwrap();
+        assert_eq!(storage.data_bytes().len(), 3 * 16384);
+        drop(db);
+
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        assert_eq!(db.page_size(), 16384);
+        assert_eq!(db.get_internal(b"doc").unwrap(), Some(vec![4u8; 3000]));
+        drop(db);
+
+        let smaller = MemoryStorage::with_contents(4096, storage.data_bytes(), Vec::new());
+        assert!(matches!(
+            SikioDB::open_with_storage(Box::new(smaller)),
+            Err(SikioError::PageSizeMismatch {
+                stored: 16384,
+                requested: 4096
+            })
+        ));
+        let default_db = MemoryStorage::new();
+        drop(SikioDB::open_with_storage(Box::new(default_db.clone())).unwrap());
+        let larger = MemoryStorage::with_contents(32768, default_db.data_bytes(), Vec::new());
+        assert!(matches!(
+            SikioDB::open_with_storage(Box::new(larger)),
+            Err(SikioError::PageSizeMismatch {
+                stored: 4096,
+                requested: 32768
+            })
+        ));
+        assert!(matches!(
+            SikioDB::open_in_memory_with_config(&DatabaseConfig {
+                page_size: 6000,
+                ..DatabaseConfig::default()
+            }),
+            Err(SikioError::InvalidPageSize(6000))
+        ));
+    }
+
+    #[test]
+    fn free_list_spans_trunk_pages_and_survives_reopen() {
+        let storage = MemoryStorage::new();
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        let keys: Vec<Vec<u8>> = (0..3000u32)
+            .map(|i| format!("key{:05}", i).into_bytes())
+            .collect();
+        for key in &keys {
+            db.put_internal(key, &wrap_raw_value(&[1u8; 800])).unwrap();
+        }
+        for key in &keys {
+            db.delete_internal(key).unwrap();
+        }
+        db.checkpoint().unwrap();
+        let free_pages = db.btree.free_page_count();
+        let next_page_id = db.btree.next_page_id();
+        assert!(free_pages as usize > FreeList::ids_per_trunk(DEFAULT_PAGE_SIZE));
+        drop(db);
 
-        self.wal_sequence = new_sequence;
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        assert_eq!(db.btree.free_page_count(), free_pages);
+        assert!(db.verify_integrity().unwrap().is_empty());
+        let trunk_pages = db.btree.free_list().trunk_count() as u64;
+        for key in &keys {
+            db.put_internal(key, &wrap_raw_value(&[2u8; 800])).unwrap();
+        }
+        assert!(db.btree.next_page_id() <= next_page_id + trunk_pages);
+        assert_eq!(db.get_internal(&keys[42]).unwrap(), Some(vec![2u8; 800]));
+    }
 
-        let ops = txn.take_ops();
-        for op in ops {
-            match op {
-                TransactionOp::Put { key, value } => {
-                    self.apply_put(&key, &value)?;
+    #[test]
+    fn corrupt_free_list_is_reported_and_rebuilt_from_reachable_pages() {
+        let storage = MemoryStorage::new();
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        for i in 0..1500u32 {
+            let key = format!("key{:05}", (i * 7919) % 1500).into_bytes();
+            db.put_internal(&key, &wrap_raw_value(&[1u8; 800])).unwrap();
+        }
+        for i in (0..1500u32).filter(|i| i % 50 != 0) {
+            let key = format!("key{:05}", i).into_bytes();
+            db.delete_internal(&key).unwrap();
+        }
+        db.checkpoint().unwrap();
+        let free_pages = db.btree.free_page_count();
+        let root = db.btree.free_list().root();
+        assert!(root != 0);
+        drop(db);
+
+        let mut disk = storage.clone();
+        let garbage = vec![0xAB; DEFAULT_PAGE_SIZE];
+        StorageBackend::write_page(&mut disk, root, &garbage).unwrap();
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        assert!(db.recovery_report().free_list_error.is_some());
+        assert!(db.btree.free_page_count() >= free_pages);
+        assert_eq!(&*db.verify_integrity().unwrap(), &[root]);
+        db.checkpoint().unwrap();
+        let next_page_id = db.btree.next_page_id();
+        for i in 0..200u32 {
+            let key = format!("key{:05}", (i * 7919) % 200).into_bytes();
+            db.put_internal(&key, &wrap_raw_value(&[2u8; 800])).unwrap();
+        }
+        assert_eq!(db.btree.next_page_id(), next_page_id);
+        drop(db);
+
+        let db = SikioDB::open_with_storage(Box::new(storage)).unwrap();
+        assert!(db.recovery_report().free_list_error.is_none());
+    }
+
+    #[test]
+    fn vacuum_relocates_pages_and_truncates_data_file() {
+        let storage = MemoryStorage::new();
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        let big: Vec<u8> = (0..20_000u32).map(|i| (i * 7919 % 251) as u8).collect();
+        for i in 0..3000u32 {
+            let key = format!("key{:05}", i).into_bytes();
+            db.put_internal(&key, &wrap_raw_value(&[1u8; 800])).unwrap();
+        }
+        db.put_internal(b"big", &wrap_raw_value(&big)).unwrap();
+        for i in (0..3000u32).filter(|i| i % 25 != 0) {
+            let key = format!("key{:05}", i).into_bytes();
+            db.delete_internal(&key).unwrap();
+        }
+        db.checkpoint().unwrap();
+        let size_before = db.storage.data_size();
+
+        let config = VacuumConfig {
+            max_pages_per_run: 20,
+            ..VacuumConfig::default()
+        };
+        let stats = db.vacuum_with_config(&config).unwrap();
+        assert!(stats.pages_moved > 0 && stats.pages_moved <= 20);
+        assert!(db.verify_integrity().unwrap().is_empty());
+        while db.vacuum().unwrap().pages_moved > 0 {}
+        let size_after = db.storage.data_size();
+        assert!(size_after < size_before / 4);
+        assert!(db.verify_integrity().unwrap().is_empty());
+        drop(db);
+
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        assert_eq!(db.storage.data_size(), size_after);
+        assert!(db.verify_integrity().unwrap().is_empty());
+        assert_eq!(db.get_internal(b"big").unwrap(), Some(big));
+        for i in 0..3000u32 {
+            let expected = (i % 25 == 0).then(|| vec![1u8; 800]);
+            let key = format!("key{:05}", i).into_bytes();
+            assert_eq!(db.get_internal(&key).unwrap(), expected);
+        }
+        db.put_internal(b"after", &wrap_raw_value(b"ok")).unwrap();
+        assert_eq!(db.get_internal(b"after").unwrap(), Some(b"ok".to_vec()));
+    }
+
+    #[test]
+    fn idle_compaction_resumes_after_reopen() {
+        let storage = MemoryStorage::new();
+        let config = DatabaseConfig {
+            auto_compaction: true,
+            vacuum: VacuumConfig {
+                max_pages_per_run: 10,
+                time_budget_ms: 0,
+                ..VacuumConfig::default()
+            },
+            ..DatabaseConfig::default()
+        };
+        let open = || SikioDB::open_with_storage_config(Box::new(storage.clone()), &config);
+        let mut db = open().unwrap();
+        for i in 0..2000u32 {
+            let key = format!("key{:05}", i).into_bytes();
+            db.put_internal(&key, &wrap_raw_value(&[1u8; 800])).unwrap();
+        }
+        for i in (0..2000u32).filter(|i| i % 10 != 0) {
+            let key = format!("key{:05}", i).into_bytes();
+            db.delete_internal(&key).unwrap();
+        }
+        db.checkpoint().unwrap();
+        assert!(db.compaction_progress().pending);
+        let size_before = db.storage.data_size();
+        drop(db);
+
+        let mut db = open().unwrap();
+        assert!(db.compaction_progress().pending);
+        let stats = db.run_idle_compaction().unwrap().unwrap();
+        assert!(stats.pages_moved > 0 && stats.pages_moved <= 10);
+        drop(db);
+
+        let mut db = open().unwrap();
+        assert_eq!(db.compaction_progress().runs, 1);
+        assert!(db.compaction_progress().pending);
+        while db.run_idle_compaction().unwrap().is_some() {}
+        let progress = db.compaction_progress().clone();
+        assert!(progress.runs > 1);
+        assert!(progress.bytes_reclaimed > 0);
+        assert!(db.storage.data_size() < size_before);
+        assert!(db.verify_integrity().unwrap().is_empty());
+        drop(db);
+
+        let mut db = open().unwrap();
+        assert!(!db.compaction_progress().pending);
+        assert_eq!(db.compaction_progress().runs, progress.runs);
+        let key = b"key00990".to_vec();
+        assert_eq!(db.get_internal(&key).unwrap(), Some(vec![1u8; 800]));
+    }
+
+    #[test]
+    fn bulk_load_packs_leaves_and_appends_to_existing_tree() {
+        let key = |i: u32| format!("key{:06}", i).into_bytes();
+        let storage = MemoryStorage::new();
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        let mut batch: Vec<_> = (0..20_000u32)
+            .map(|i| (key((i * 7919) % 20_000), vec![1u8; 40]))
+            .collect();
+        batch.push((key(5), vec![2u8; 40]));
+        assert_eq!(db.bulk_load(batch).unwrap(), 20_000);
+        assert!(db.verify_integrity().unwrap().is_empty());
+        let bulk_pages = db.btree.next_page_id();
+
+        let mut baseline = SikioDB::open_in_memory().unwrap();
+        let value = wrap_raw_value(&[1u8; 40]);
+        for i in 0..20_000u32 {
+            baseline.put_internal(&key(i), &value).unwrap();
+        }
+        assert!(bulk_pages * 10 < baseline.btree.next_page_id() * 7);
+
+        let appended = (20_000..30_000u32).map(|i| (key(i), vec![3u8; 40]));
+        db.bulk_load(appended).unwrap();
+        let overlapping = (0..30_000u32).step_by(100).map(|i| (key(i), vec![4u8; 40]));
+        db.bulk_load(overlapping).unwrap();
+        assert!(db.verify_integrity().unwrap().is_empty());
+        drop(db);
+
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        assert!(db.verify_integrity().unwrap().is_empty());
+        for i in 0..30_000u32 {
+            let expected = match i {
+                _ if i % 100 == 0 => vec![4u8; 40],
+                5 => vec![2u8; 40],
+                _ if i < 20_000 => vec![1u8; 40],
+                _ => vec![3u8; 40],
+            };
+            assert_eq!(db.get_internal(&key(i)).unwrap(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn bulk_load_grafts_batches_that_fall_between_existing_keys() {
+        let key = |i: u32| format!("key{:05}", i).into_bytes();
+        let storage = MemoryStorage::new();
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        let mut expected = std::collections::BTreeMap::new();
+        let mut load = |db: &mut SikioDB, keys: Vec<Vec<u8>>, fill: u8| {
+            for key in &keys {
+                expected.insert(key.clone(), vec![fill; 40]);
+            }
+            let pages = db.btree.next_page_id();
+            let count = db.bulk_load(keys.into_iter().map(|key| (key, vec![fill; 40])));
+            assert!(db.verify_integrity().unwrap().is_empty());
+            (count.unwrap(), db.btree.next_page_id() - pages)
+        };
+        load(&mut db, (0..5000).map(key).collect(), 1);
+        let front = (0..2000)
+            .map(|i| format!("aaa{:05}", i).into_bytes())
+            .collect();
+        let (count, _) = load(&mut db, front, 2);
+        assert_eq!(count, 2000);
+        let middle = (0..3000)
+            .map(|i| format!("key02500-{:04}", i).into_bytes())
+            .collect();
+        let (_, pages) = load(&mut db, middle, 3);
+        assert!(pages < 70);
+        let short = (0..100)
+            .map(|i| format!("key04999-{:03}", i).into_bytes())
+            .collect();
+        load(&mut db, short, 4);
+
+        let check = |db: &mut SikioDB, expected: &std::collections::BTreeMap<_, _>| {
+            let scanned = db.scan_prefix_internal(b"").unwrap();
+            assert!(scanned.iter().map(|(k, v)| (k, v)).eq(expected.iter()));
+        };
+        check(&mut db, &expected);
+        for i in (0..5000).step_by(3) {
+            db.delete_internal(&key(i)).unwrap();
+            expected.remove(&key(i));
+        }
+        check(&mut db, &expected);
+        drop(db);
+        let mut db = SikioDB::open_with_storage(Box::new(storage)).unwrap();
+        check(&mut db, &expected);
+    }
+
+    #[test]
+    fn vacuum_into_rebuilds_densely_and_swaps_in_place() {
+        let storage = MemoryStorage::new();
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        let big: Vec<u8> = (0..20_000u32).map(|i| (i * 7919 % 251) as u8).collect();
+        for i in 0..4000u32 {
+            let key = format!("key{:05}", (i * 7919) % 4000).into_bytes();
+            db.put_internal(&key, &wrap_raw_value(&[3u8; 60])).unwrap();
+        }
+        db.put_internal(b"big", &wrap_raw_value(&big)).unwrap();
+        for i in (0..4000u32).filter(|i| i % 3 != 0) {
+            let key = format!("key{:05}", i).into_bytes();
+            db.delete_internal(&key).unwrap();
+        }
+        db.checkpoint().unwrap();
+        let size_before = db.storage.data_size();
+
+        let mut target = MemoryStorage::new();
+        let stats = db.vacuum_into_storage(&mut target).unwrap();
+        assert!(stats.bytes_reclaimed > 0);
+        assert!(target.data_size() < size_before);
+        let mut rebuilt = SikioDB::open_with_storage(Box::new(target.clone())).unwrap();
+        assert!(rebuilt.verify_integrity().unwrap().is_empty());
+        assert_eq!(rebuilt.get_internal(b"big").unwrap(), Some(big.clone()));
+        drop(rebuilt);
+
+        db.replace_with_image(&target).unwrap();
+        assert_eq!(db.storage.data_size(), target.data_size());
+        db.put_internal(b"after", &wrap_raw_value(b"ok")).unwrap();
+        drop(db);
+
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        assert!(db.verify_integrity().unwrap().is_empty());
+        assert_eq!(db.get_internal(b"big").unwrap(), Some(big));
+        assert_eq!(db.get_internal(b"after").unwrap(), Some(b"ok".to_vec()));
+        for i in 0..4000u32 {
+            let expected = (i % 3 == 0).then(|| vec![3u8; 60]);
+            let key = format!("key{:05}", i).into_bytes();
+            assert_eq!(db.get_internal(&key).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn lsn_keeps_growing_and_metadata_slots_alternate() {
+        let storage = MemoryStorage::new();
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        let mut versions = Vec::new();
+        for round in 0..3u8 {
+            db.put_internal(&[round], &wrap_raw_value(b"v")).unwrap();
+            versions.push(db.commit_version());
+            db.checkpoint().unwrap();
+        }
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+        let slots: Vec<Metadata> = [METADATA_PAGE_ID_1, METADATA_PAGE_ID_2]
+            .iter()
+            .map(|&id| Metadata::from_bytes(&storage.read_page(id).unwrap()).unwrap())
+            .collect();
+        assert_eq!(slots[0].generation.abs_diff(slots[1].generation), 1);
+        let newest = slots.iter().max_by_key(|meta| meta.generation).unwrap();
+        assert!(newest.lsn > versions[2]);
+        drop(db);
+
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        assert!(db.commit_version() > versions[2]);
+        let reopened = db.commit_version();
+        db.put_internal(b"next", &wrap_raw_value(b"v")).unwrap();
+        assert!(db.commit_version() > reopened);
+    }
+
+    #[test]
+    fn replay_skips_ops_already_stamped_on_pages() {
+        let mut storage = MemoryStorage::new();
+        let big = vec![9u8; 20_000];
+        let wal = {
+            let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+            for i in 0..200u32 {
+                let key = format!("key{:04}", i);
+                db.put_internal(key.as_bytes(), &wrap_raw_value(b"v"))
+                    .unwrap();
+            }
+            db.put_internal(b"big", &wrap_raw_value(&big)).unwrap();
+            assert!(db.delete_internal(b"key0007").unwrap());
+            let wal = storage.wal_bytes();
+            db.checkpoint().unwrap();
+            wal
+        };
+        let next_page_id = SikioDB::open_with_storage(Box::new(storage.clone()))
+            .unwrap()
+            .btree
+            .next_page_id();
+        storage.append_wal(&wal).unwrap();
+        storage.flush_wal().unwrap();
+
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        assert_eq!(db.btree.next_page_id(), next_page_id);
+        assert_eq!(db.get_internal(b"big").unwrap(), Some(big));
+        assert_eq!(db.get_internal(b"key0007").unwrap(), None);
+        assert_eq!(db.scan_prefix_internal(b"key").unwrap().len(), 199);
+    }
+
+    #[test]
+    fn recovery_report_tells_torn_tail_from_mid_log_corruption() {
+        use crate::wal::WAL_ENTRY_HEADER_SIZE;
+        fn entry_offset(wal: &[u8], key: &[u8]) -> usize {
+            let mut offset = 0;
+            for entry in WalReader::new(wal) {
+                let entry = entry.unwrap();
+                if entry.key == key {
+                    break;
                 }
-                TransactionOp::Delete { key } => {
-                    self.apply_delete(&key)?;
+                offset += entry.to_bytes().len();
+            }
+            offset
+        }
+        let build = |edit: &dyn Fn(&mut Vec<u8>)| {
+            let storage = MemoryStorage::new();
+            {
+                let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+                for key in [b"k0", b"k1", b"k2"] {
+                    db.put_internal(key, &wrap_raw_value(b"v")).unwrap();
                 }
             }
+            let mut wal = storage.wal_bytes();
+            edit(&mut wal);
+            let mut storage = storage;
+            storage.truncate_wal_to(0).unwrap();
+            storage.append_wal(&wal).unwrap();
+            storage.flush_wal().unwrap();
+            storage
+        };
+
+        let torn = build(&|wal| wal.truncate(wal.len() - 3));
+        let mut db = SikioDB::open_with_storage(Box::new(torn)).unwrap();
+        let report = db.recovery_report().clone();
+        assert_eq!(report.transactions_replayed, 2);
+        assert_eq!(report.ops_discarded, 1);
+        assert_eq!(report.replayed_commits, vec![2, 4]);
+        assert_eq!(report.discarded_keys, vec![b"k2".to_vec()]);
+        assert!(!report.mid_log);
+        assert!(matches!(
+            report.stop_reason,
+            Some(SikioError::WalCorrupted { .. })
+        ));
+        assert_eq!(db.get_internal(b"k1").unwrap(), Some(b"v".to_vec()));
+        assert_eq!(db.get_internal(b"k2").unwrap(), None);
+
+        let corrupted_at = std::cell::Cell::new(0);
+        let corrupt = |wal: &mut Vec<u8>| {
+            let offset = entry_offset(wal, b"k1");
+            wal[offset + WAL_ENTRY_HEADER_SIZE] ^= 0xff;
+            corrupted_at.set(offset as u64);
+        };
+        let strict = DatabaseConfig {
+            fail_on_corrupt_wal: true,
+            ..DatabaseConfig::default()
+        };
+        let storage = build(&corrupt);
+        let wal_size = storage.wal_size();
+        assert!(matches!(
+            SikioDB::open_with_storage_config(Box::new(storage.clone()), &strict),
+            Err(SikioError::WalCorrupted { .. })
+        ));
+        assert_eq!(storage.wal_size(), wal_size);
+
+        let mut db = SikioDB::open_with_storage(Box::new(storage)).unwrap();
+        let report = db.recovery_report().clone();
+        assert!(report.mid_log);
+        assert_eq!(report.transactions_replayed, 1);
+        assert_eq!(report.stopped_at, Some(corrupted_at.get()));
+        assert_eq!(db.get_internal(b"k0").unwrap(), Some(b"v".to_vec()));
+        assert_eq!(db.get_internal(b"k2").unwrap(), None);
+    }
+
+    #[test]
+    fn checkpoint_policy_triggers_on_wal_size_dirty_pages_and_time() {
+        let open = |checkpoint| {
+            let config = DatabaseConfig {
+                checkpoint,
+                ..DatabaseConfig::default()
+            };
+            SikioDB::open_in_memory_with_config(&config).unwrap()
+        };
+        let mut db = open(CheckpointPolicy {
+            wal_bytes: 1024,
+            ..CheckpointPolicy::default()
+        });
+        db.put_internal(b"a", &wrap_raw_value(b"v")).unwrap();
+        assert!(db.storage.wal_size() > 0);
+        db.put_internal(b"b", &wrap_raw_value(&[7u8; 2000]))
+            .unwrap();
+        assert_eq!(db.storage.wal_size(), 0);
+
+        let mut db = open(CheckpointPolicy {
+            dirty_pages: Some(1),
+            ..CheckpointPolicy::default()
+        });
+        db.put_internal(b"a", &wrap_raw_value(b"v")).unwrap();
+        assert_eq!(db.storage.wal_size(), 0);
+        assert_eq!(db.cache.dirty_page_count(), 0);
+
+        let mut db = open(CheckpointPolicy {
+            interval_ms: Some(60_000),
+            ..CheckpointPolicy::default()
+        });
+        db.put_internal(b"a", &wrap_raw_value(b"v")).unwrap();
+        assert!(db.storage.wal_size() > 0);
+        db.last_checkpoint -= 60_000;
+        db.put_internal(b"b", &wrap_raw_value(b"v")).unwrap();
+        assert_eq!(db.storage.wal_size(), 0);
+        db.put_internal(b"c", &wrap_raw_value(b"v")).unwrap();
+        assert!(db.storage.wal_size() > 0);
+        db.checkpoint().unwrap();
+        assert_eq!(db.storage.wal_size(), 0);
+    }
+
+    #[test]
+    fn archived_wal_restores_to_an_lsn_or_a_timestamp() {
+        let storage = MemoryStorage::new();
+        let config = DatabaseConfig {
+            wal_archive: Some(WalArchiveConfig { retain_segments: 3 }),
+            ..DatabaseConfig::default()
+        };
+        let mut db = SikioDB::open_with_storage_config(Box::new(storage.clone()), &config).unwrap();
+        db.put_internal(b"a", &wrap_raw_value(b"1")).unwrap();
+        let mut base = MemoryStorage::new();
+        db.vacuum_into_storage(&mut base).unwrap();
+        db.put_internal(b"b", &wrap_raw_value(b"2")).unwrap();
+        db.checkpoint().unwrap();
+        let before_bad_sync = db.commit_lsn;
+        db.put_internal(b"a", &wrap_raw_value(b"bad")).unwrap();
+        assert!(db.delete_internal(b"b").unwrap());
+        db.checkpoint().unwrap();
+        db.put_internal(b"c", &wrap_raw_value(b"3")).unwrap();
+
+        let copy = || {
+            Box::new(MemoryStorage::with_contents(
+                DEFAULT_PAGE_SIZE,
+                base.data_bytes(),
+                base.wal_bytes(),
+            ))
+        };
+        let target = RestorePoint::Lsn(before_bad_sync);
+        let mut restored = db.restore_to(copy(), target).unwrap();
+        assert_eq!(restored.get_internal(b"a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(restored.get_internal(b"b").unwrap(), Some(b"2".to_vec()));
+        assert_eq!(restored.commit_lsn, before_bad_sync);
+
+        let mut latest = db.restore_to(copy(), RestorePoint::Lsn(u64::MAX)).unwrap();
+        assert_eq!(latest.get_internal(b"a").unwrap(), Some(b"bad".to_vec()));
+        assert_eq!(latest.get_internal(b"b").unwrap(), None);
+        assert_eq!(latest.get_internal(b"c").unwrap(), Some(b"3".to_vec()));
+
+        let mut oldest = db.restore_to(copy(), RestorePoint::Timestamp(0)).unwrap();
+        assert_eq!(oldest.get_internal(b"a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(oldest.get_internal(b"b").unwrap(), None);
+
+        for key in [b"d", b"e", b"f"] {
+            db.put_internal(key, &wrap_raw_value(b"v")).unwrap();
+            db.checkpoint().unwrap();
         }
+        let segments = storage.archived_wal_segments().unwrap();
+        assert_eq!(segments.len(), 3);
+        assert!(segments.windows(2).all(|pair| pair[0] < pair[1]));
+    }
 
-        if self.storage.wal_size() > WAL_CHECKPOINT_THRESHOLD {
-            self.checkpoint()?;
+    #[test]
+    fn idle_checkpoints_keep_archived_segments() {
+        let storage = MemoryStorage::new();
+        let config = DatabaseConfig {
+            wal_archive: Some(WalArchiveConfig { retain_segments: 3 }),
+            ..DatabaseConfig::default()
+        };
+        let mut db = SikioDB::open_with_storage_config(Box::new(storage.clone()), &config).unwrap();
+        let mut base = MemoryStorage::new();
+        db.vacuum_into_storage(&mut base).unwrap();
+        db.put_internal(b"a", &wrap_raw_value(b"1")).unwrap();
+        db.checkpoint().unwrap();
+        let segments = storage.archived_wal_segments().unwrap();
+        for _ in 0..5 {
+            db.checkpoint().unwrap();
         }
+        assert_eq!(storage.archived_wal_segments().unwrap(), segments);
+        assert_eq!(db.storage.wal_size(), 0);
 
-        Ok(())
+        let base =
+            MemoryStorage::with_contents(DEFAULT_PAGE_SIZE, base.data_bytes(), base.wal_bytes());
+        let mut restored = db
+            .restore_to(Box::new(base), RestorePoint::Lsn(u64::MAX))
+            .unwrap();
+        assert_eq!(restored.get_internal(b"a").unwrap(), Some(b"1".to_vec()));
     }
-}
 
-#[wasm_bindgen]
-pub struct JsWriteTransaction {
-    inner: WriteTransaction,
-}
+    #[test]
+    fn restore_to_rejects_gaps_and_stops_at_a_torn_tail() {
+        let mut storage = MemoryStorage::new();
+        let config = DatabaseConfig {
+            wal_archive: Some(WalArchiveConfig { retain_segments: 1 }),
+            ..DatabaseConfig::default()
+        };
+        let mut db = SikioDB::open_with_storage_config(Box::new(storage.clone()), &config).unwrap();
+        db.put_internal(b"a", &wrap_raw_value(b"1")).unwrap();
+        let mut base = MemoryStorage::new();
+        db.vacuum_into_storage(&mut base).unwrap();
+        let copy = || {
+            Box::new(MemoryStorage::with_contents(
+                DEFAULT_PAGE_SIZE,
+                base.data_bytes(),
+                base.wal_bytes(),
+            ))
+        };
+        db.put_internal(b"b", &wrap_raw_value(b"2")).unwrap();
+        db.checkpoint().unwrap();
+        db.put_internal(b"c", &wrap_raw_value(b"3")).unwrap();
+        db.put_internal(b"d", &wrap_raw_value(b"4")).unwrap();
+        let mut restored = db.restore_to(copy(), RestorePoint::Lsn(u64::MAX)).unwrap();
+        assert_eq!(restored.get_internal(b"d").unwrap(), Some(b"4".to_vec()));
 
-#[wasm_bindgen]
-impl JsWriteTransaction {
-    #[wasm_bindgen(constructor)]
-    pub fn new(sequence: u64) -> Self {
-        JsWriteTransaction {
-            inner: WriteTransaction::new(sequence),
+        storage.truncate_wal_to(storage.wal_size() - 3).unwrap();
+        let mut restored = db.restore_to(copy(), RestorePoint::Lsn(u64::MAX)).unwrap();
+        assert_eq!(restored.get_internal(b"c").unwrap(), Some(b"3".to_vec()));
+        assert_eq!(restored.get_internal(b"d").unwrap(), None);
+
+        db.checkpoint().unwrap();
+        assert!(matches!(
+            db.restore_to(copy(), RestorePoint::Lsn(u64::MAX)),
+            Err(SikioError::WalGap { .. })
+        ));
+    }
+
+    #[test]
+    fn change_log_feeds_commits_across_checkpoints() {
+        let storage = MemoryStorage::new();
+        let config = DatabaseConfig {
+            wal_archive: Some(WalArchiveConfig { retain_segments: 2 }),
+            change_log: Some(ChangeLogConfig {
+                max_changes: 5,
+                ..ChangeLogConfig::default()
+            }),
+            ..DatabaseConfig::default()
+        };
+        let mut db =
+            SikioDB::open_with_storage_config(Box::new(storage.clone()), &config).unwrap();
+        let start = db.commit_lsn;
+        db.put_internal(b"a", &wrap_raw_value(b"1")).unwrap();
+        db.put_pairs(vec![
+            (b"b".to_vec(), wrap_raw_value(b"2")),
+            (b"c".to_vec(), wrap_raw_value(b"3")),
+        ])
+        .unwrap();
+        db.checkpoint().unwrap();
+        let mut txn = db.begin_write();
+        txn.put(b"d".to_vec(), wrap_raw_value(b"4")).unwrap();
+        txn.delete(b"a".to_vec()).unwrap();
+        txn.delete(b"missing".to_vec()).unwrap();
+        db.commit_transaction(&mut txn).unwrap();
+        assert!(!db.delete_internal(b"missing").unwrap());
+
+        let feed = db.changes_since(start, 100).unwrap().changes;
+        let commit_lsn = db.commit_lsn;
+        drop(db);
+        let mut db =
+            SikioDB::open_with_storage_config(Box::new(storage.clone()), &config).unwrap();
+        assert_eq!(db.changes_since(start, 100).unwrap().changes, feed);
+
+        let batch = db.changes_since(start, 2).unwrap();
+        let seen: Vec<_> = batch
+            .changes
+            .iter()
+            .map(|c| (c.key.clone(), c.op, c.value.clone()))
+            .collect();
+        assert_eq!(
+            seen,
+            vec![
+                (b"a".to_vec(), ChangeOp::Put, Some(b"1".to_vec())),
+                (b"b".to_vec(), ChangeOp::Put, Some(b"2".to_vec())),
+                (b"c".to_vec(), ChangeOp::Put, Some(b"3".to_vec())),
+            ]
+        );
+        let rest = db.changes_since(batch.resume_token, 100).unwrap();
+        assert_eq!(rest.changes.len(), 2);
+        assert_eq!(rest.changes[1].op, ChangeOp::Delete);
+        assert_eq!(rest.resume_token, commit_lsn);
+
+        db.put_internal(b"e", &wrap_raw_value(b"5")).unwrap();
+        assert!(matches!(
+            db.changes_since(start, 100),
+            Err(SikioError::ChangeLogExpired { .. })
+        ));
+        let tail = db.changes_since(rest.resume_token, 100).unwrap();
+        assert_eq!(tail.changes.len(), 1);
+    }
+
+    #[test]
+    fn change_log_is_rebuilt_from_the_wal_on_reopen() {
+        let storage = MemoryStorage::new();
+        let logged = DatabaseConfig {
+            change_log: Some(ChangeLogConfig::default()),
+            ..DatabaseConfig::default()
+        };
+        let config = DatabaseConfig {
+            wal_archive: Some(WalArchiveConfig { retain_segments: 2 }),
+            change_log: Some(ChangeLogConfig::default()),
+            ..DatabaseConfig::default()
+        };
+        let start;
+        {
+            let mut db =
+                SikioDB::open_with_storage_config(Box::new(storage.clone()), &config).unwrap();
+            start = db.commit_lsn;
+            db.put_internal(b"a", &wrap_raw_value(b"1")).unwrap();
+            db.checkpoint().unwrap();
+            let mut txn = db.begin_write();
+            txn.put(b"b".to_vec(), b"2".to_vec()).unwrap();
+            txn.delete(b"a".to_vec()).unwrap();
+            db.commit_transaction(&mut txn).unwrap();
         }
+        let db = SikioDB::open_with_storage_config(Box::new(storage.clone()), &config).unwrap();
+        let seen: Vec<_> = db
+            .changes_since(start, 100)
+            .unwrap()
+            .changes
+            .iter()
+            .map(|c| (c.key.clone(), c.op, c.value.clone()))
+            .collect();
+        assert_eq!(
+            seen,
+            vec![
+                (b"a".to_vec(), ChangeOp::Put, Some(b"1".to_vec())),
+                (b"b".to_vec(), ChangeOp::Put, Some(b"2".to_vec())),
+                (b"a".to_vec(), ChangeOp::Delete, None),
+            ]
+        );
+        drop(db);
+
+        let mut writer =
+            SikioDB::open_with_storage_config(Box::new(storage.clone()), &logged).unwrap();
+        let horizon = writer.commit_lsn;
+        writer.put_internal(b"c", &wrap_raw_value(b"3")).unwrap();
+        let unarchived =
+            SikioDB::open_with_storage_config(Box::new(storage.clone()), &logged).unwrap();
+        assert!(matches!(
+            unarchived.changes_since(start, 100),
+            Err(SikioError::ChangeLogExpired { .. })
+        ));
+        let changes = unarchived.changes_since(horizon, 100).unwrap().changes;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].key, b"c".to_vec());
+        drop(unarchived);
+        drop(writer);
+
+        let db = SikioDB::open_with_storage(Box::new(storage)).unwrap();
+        assert!(matches!(
+            db.changes_since(db.commit_lsn, 100),
+            Err(SikioError::ChangeLogDisabled)
+        ));
     }
 
-    #[wasm_bindgen]
-    pub fn put(&mut self, key: &[u8], value: &[u8]) -> std::result::Result<(), JsValue> {
-        self.inner
-            .put(key.to_vec(), value.to_vec())
-            .map_err(|e| JsValue::from_str(&e.to_string()))
+    #[test]
+    fn rebuilt_change_log_keeps_the_same_retention_window() {
+        let key = |i: u32| format!("key{:05}", i).into_bytes();
+        let storage = MemoryStorage::new();
+        let config = DatabaseConfig {
+            wal_archive: Some(WalArchiveConfig { retain_segments: 8 }),
+            change_log: Some(ChangeLogConfig {
+                max_changes: 3,
+                ..ChangeLogConfig::default()
+            }),
+            ..DatabaseConfig::default()
+        };
+        let mut db = SikioDB::open_with_storage_config(Box::new(storage.clone()), &config).unwrap();
+        for i in 0..6 {
+            db.put_internal(&key(i), &wrap_raw_value(b"v")).unwrap();
+            if i % 2 == 1 {
+                db.checkpoint().unwrap();
+            }
+        }
+        db.put_internal(&key(6), &wrap_raw_value(b"v")).unwrap();
+        let live = db.changes.clone().unwrap();
+        drop(db);
+
+        let reopened = SikioDB::open_with_storage_config(Box::new(storage), &config).unwrap();
+        let rebuilt = reopened.changes.as_ref().unwrap();
+        assert_eq!(rebuilt.horizon(), live.horizon());
+        let keys = |log: &ChangeLog| -> Vec<Vec<u8>> {
+            let batch = log.changes_since(log.horizon(), 100).unwrap();
+            batch.changes.into_iter().map(|change| change.key).collect()
+        };
+        assert_eq!(keys(rebuilt), vec![key(4), key(5), key(6)]);
+        assert_eq!(keys(rebuilt), keys(&live));
     }
 
-    #[wasm_bindgen]
-    pub fn delete(&mut self, key: &[u8]) -> std::result::Result<(), JsValue> {
-        self.inner
-            .delete(key.to_vec())
-            .map_err(|e| JsValue::from_str(&e.to_string()))
+    #[test]
+    fn write_transaction_reads_its_own_pending_writes() {
+        let mut db = SikioDB::open_in_memory().unwrap();
+        for key in [&b"a"[..], b"b", b"c", b"e"] {
+            db.put_internal(key, &wrap_raw_value(b"old")).unwrap();
+        }
+        db.put_internal(b"f", &wrap_ttl_value(b"gone", 1)).unwrap();
+
+        let mut txn = db.begin_write();
+        txn.put(b"b".to_vec(), b"new".to_vec()).unwrap();
+        txn.delete(b"c".to_vec()).unwrap();
+        txn.put(b"d".to_vec(), b"new".to_vec()).unwrap();
+        txn.delete(b"e".to_vec()).unwrap();
+        txn.put(b"e".to_vec(), b"again".to_vec()).unwrap();
+
+        assert_eq!(db.txn_get(&mut txn, b"a").unwrap(), Some(b"old".to_vec()));
+        assert_eq!(db.txn_get(&mut txn, b"b").unwrap(), Some(b"new".to_vec()));
+        assert_eq!(db.txn_get(&mut txn, b"c").unwrap(), None);
+        assert_eq!(db.txn_get(&mut txn, b"d").unwrap(), Some(b"new".to_vec()));
+        assert_eq!(db.get_internal(b"c").unwrap(), Some(b"old".to_vec()));
+        assert_eq!(db.get_internal(b"d").unwrap(), None);
+
+        let scanned = db.txn_scan(&mut txn, b"a", b"z", 100).unwrap();
+        let expected: Vec<(Vec<u8>, Vec<u8>)> = [
+            (&b"a"[..], &b"old"[..]),
+            (b"b", b"new"),
+            (b"d", b"new"),
+            (b"e", b"again"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_vec(), v.to_vec()))
+        .collect();
+        assert_eq!(scanned, expected);
+        let limited = db.txn_scan(&mut txn, b"b", b"d", 2).unwrap();
+        assert_eq!(limited, expected[1..3]);
+        assert!(db.txn_scan(&mut txn, b"z", b"a", 100).unwrap().is_empty());
+
+        db.commit_transaction(&mut txn).unwrap();
+        assert_eq!(db.scan_prefix_internal(b"").unwrap(), expected);
     }
 
-    #[wasm_bindgen]
-    pub fn abort(&mut self) {
-        self.inner.abort();
+    #[test]
+    fn write_transaction_reads_keys_across_split_leaves() {
+        let mut db = SikioDB::open_in_memory().unwrap();
+        for i in 0..2000u32 {
+            db.put_internal(&i.to_be_bytes(), &wrap_raw_value(&i.to_le_bytes()))
+                .unwrap();
+        }
+        let mut txn = db.begin_write();
+        for i in 0..2000u32 {
+            assert_eq!(
+                db.txn_get(&mut txn, &i.to_be_bytes()).unwrap(),
+                Some(i.to_le_bytes().to_vec())
+            );
+        }
     }
 
-    #[wasm_bindgen(js_name = isActive)]
-    pub fn is_active_js(&self) -> bool {
-        self.inner.is_active()
+    #[test]
+    fn commit_fails_when_a_key_read_in_the_transaction_changed() {
+        let mut db = SikioDB::open_in_memory().unwrap();
+        db.put_internal(b"doc", &wrap_raw_value(b"1")).unwrap();
+        let version = db.key_version(b"doc").unwrap();
+
+        let mut txn = db.begin_write();
+        assert_eq!(db.txn_get(&mut txn, b"doc").unwrap(), Some(b"1".to_vec()));
+        db.put_internal(b"doc", &wrap_raw_value(b"2")).unwrap();
+        assert!(db.key_version(b"doc").unwrap() > version);
+        txn.put(b"doc".to_vec(), b"1+1".to_vec()).unwrap();
+        let err = db.commit_transaction(&mut txn).unwrap_err();
+        let conflicted = match err {
+            SikioError::TransactionConflict { key, .. } => key,
+            other => panic!("unexpected error: {}", other),
+        };
+        assert_eq!(conflicted, b"doc");
+        assert!(!txn.is_active());
+        assert_eq!(db.get_internal(b"doc").unwrap(), Some(b"2".to_vec()));
+
+        let mut txn = db.begin_write();
+        db.txn_get(&mut txn, b"doc").unwrap();
+        db.txn_get(&mut txn, b"new").unwrap();
+        assert!(!db.delete_internal(b"new").unwrap());
+        txn.put(b"doc".to_vec(), b"3".to_vec()).unwrap();
+        db.commit_transaction(&mut txn).unwrap();
+        assert_eq!(db.get_internal(b"doc").unwrap(), Some(b"3".to_vec()));
+
+        let mut txn = db.begin_write();
+        assert_eq!(db.txn_scan(&mut txn, b"a", b"z", 10).unwrap().len(), 1);
+        let mut other = db.begin_write();
+        other.put(b"new".to_vec(), b"y".to_vec()).unwrap();
+        db.commit_transaction(&mut other).unwrap();
+        txn.put(b"doc".to_vec(), b"4".to_vec()).unwrap();
+        assert!(matches!(
+            db.commit_transaction(&mut txn),
+            Err(SikioError::TransactionConflict { .. })
+        ));
+    }
+
+    #[test]
+    fn read_versions_survive_a_reopen() {
+        let storage = MemoryStorage::new();
+        let (mut unchanged, mut changed);
+        {
+            let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+            db.put_internal(b"doc", &wrap_raw_value(b"1")).unwrap();
+            unchanged = db.begin_write();
+            db.txn_get(&mut unchanged, b"doc").unwrap();
+            changed = db.begin_write();
+            db.txn_get(&mut changed, b"doc").unwrap();
+        }
+        let mut db = SikioDB::open_with_storage(Box::new(storage)).unwrap();
+        unchanged.put(b"doc".to_vec(), b"2".to_vec()).unwrap();
+        db.commit_transaction(&mut unchanged).unwrap();
+        assert_eq!(db.get_internal(b"doc").unwrap(), Some(b"2".to_vec()));
+
+        changed.put(b"doc".to_vec(), b"3".to_vec()).unwrap();
+        assert!(matches!(
+            db.commit_transaction(&mut changed),
+            Err(SikioError::TransactionConflict { .. })
+        ));
+        assert_eq!(db.get_internal(b"doc").unwrap(), Some(b"2".to_vec()));
+    }
+
+    #[test]
+    fn scans_conflict_only_with_writes_to_their_pages() {
+        let key = |i: u32| format!("key{:05}", i).into_bytes();
+        let mut db = SikioDB::open_in_memory().unwrap();
+        for i in 0..1000 {
+            db.put_internal(&key(i), &wrap_raw_value(b"v")).unwrap();
+        }
+
+        let mut txn = db.begin_write();
+        let pairs = db.txn_scan(&mut txn, &key(10), &key(20), 100).unwrap();
+        assert_eq!(pairs.len(), 11);
+        db.put_internal(&key(900), &wrap_raw_value(b"w")).unwrap();
+        txn.put(key(10), b"x".to_vec()).unwrap();
+        db.commit_transaction(&mut txn).unwrap();
+
+        let mut txn = db.begin_write();
+        db.txn_scan(&mut txn, &key(10), &key(20), 100).unwrap();
+        db.put_internal(b"key00015a", &wrap_raw_value(b"w"))
+            .unwrap();
+        txn.put(key(10), b"z".to_vec()).unwrap();
+        assert!(matches!(
+            db.commit_transaction(&mut txn),
+            Err(SikioError::TransactionConflict { .. })
+        ));
+        assert_eq!(db.get_internal(&key(10)).unwrap(), Some(b"x".to_vec()));
+    }
+
+    #[test]
+    fn conditional_writes_check_the_current_value_and_log_one_op() {
+        let storage = MemoryStorage::new();
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        assert!(db.put_if_absent(b"lock", b"tab-1").unwrap());
+        assert!(!db.put_if_absent(b"lock", b"tab-2").unwrap());
+        assert_eq!(db.get_internal(b"lock").unwrap(), Some(b"tab-1".to_vec()));
+
+        let lsn = db.commit_lsn;
+        let stale = db.compare_and_swap(b"lock", Some(b"tab-2"), b"tab-3");
+        assert!(!stale.unwrap());
+        assert!(!db.delete_if_equals(b"lock", b"tab-2").unwrap());
+        assert_eq!(db.commit_lsn, lsn);
+        let swapped = db.compare_and_swap(b"lock", Some(b"tab-1"), b"tab-3");
+        assert!(swapped.unwrap());
+        assert_eq!(db.commit_lsn, lsn + 2);
+        assert!(db.compare_and_swap(b"fresh", None, b"1").unwrap());
+
+        let previous = db.get_and_set(b"fresh", b"2").unwrap();
+        assert_eq!(previous, Some(b"1".to_vec()));
+        assert_eq!(db.get_and_set(b"unset", b"x").unwrap(), None);
+        assert!(db.delete_if_equals(b"lock", b"tab-3").unwrap());
+        assert_eq!(db.get_internal(b"lock").unwrap(), None);
+
+        drop(db);
+        let mut db = SikioDB::open_with_storage(Box::new(storage)).unwrap();
+        assert_eq!(db.get_internal(b"fresh").unwrap(), Some(b"2".to_vec()));
+        assert_eq!(db.get_internal(b"lock").unwrap(), None);
+    }
+
+    #[test]
+    fn failed_conditional_writes_log_nothing() {
+        let storage = MemoryStorage::new();
+        let mut db = SikioDB::open_with_storage(Box::new(storage.clone())).unwrap();
+        assert!(db.put_if_absent(b"lock", b"tab-1").unwrap());
+        let wal_size = storage.wal_size();
+        let lsn = db.commit_lsn;
+
+        assert!(!db.put_if_absent(b"lock", b"tab-2").unwrap());
+        assert!(!db.compare_and_swap(b"lock", Some(b"tab-2"), b"x").unwrap());
+        assert!(!db.compare_and_swap(b"free", Some(b"tab-1"), b"x").unwrap());
+        assert!(!db.delete_if_equals(b"lock", b"tab-2").unwrap());
+        assert!(!db.delete_if_equals(b"free", b"tab-1").unwrap());
+        assert_eq!(storage.wal_size(), wal_size);
+        assert_eq!(db.commit_lsn, lsn);
+        assert_eq!(db.get_internal(b"lock").unwrap(), Some(b"tab-1".to_vec()));
+    }
+
+    #[test]
+    fn savepoints_roll_back_part_of_a_transaction() {
+        let mut db = SikioDB::open_in_memory().unwrap();
+        db.put_internal(b"a", &wrap_raw_value(b"0")).unwrap();
+
+        let mut txn = db.begin_write();
+        txn.put(b"a".to_vec(), b"1".to_vec()).unwrap();
+        let outer = txn.savepoint().unwrap();
+        txn.put(b"b".to_vec(), b"bad".to_vec()).unwrap();
+        txn.delete(b"a".to_vec()).unwrap();
+        let inner = txn.savepoint().unwrap();
+        txn.put(b"c".to_vec(), b"bad".to_vec()).unwrap();
+        assert_eq!(db.txn_get(&mut txn, b"a").unwrap(), None);
+
+        txn.rollback_to(outer).unwrap();
+        assert_eq!(txn.ops_count(), 1);
+        assert_eq!(db.txn_get(&mut txn, b"a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(db.txn_get(&mut txn, b"c").unwrap(), None);
+        assert!(txn.rollback_to(inner).is_err());
+
+        txn.put(b"d".to_vec(), b"4".to_vec()).unwrap();
+        let nested = txn.savepoint().unwrap();
+        txn.put(b"e".to_vec(), b"5".to_vec()).unwrap();
+        txn.release(nested).unwrap();
+        txn.release(outer).unwrap();
+        assert!(txn.rollback_to(outer).is_err());
+        db.commit_transaction(&mut txn).unwrap();
+
+        let keys: Vec<_> = db
+            .scan_prefix_internal(b"")
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec![b"a".to_vec(), b"d".to_vec(), b"e".to_vec()]);
+    }
+
+    #[test]
+    fn rolling_back_a_savepoint_drops_the_reads_made_after_it() {
+        let mut db = SikioDB::open_in_memory().unwrap();
+        db.put_internal(b"x", &wrap_raw_value(b"0")).unwrap();
+
+        let mut txn = db.begin_write();
+        txn.put(b"a".to_vec(), b"1".to_vec()).unwrap();
+        let savepoint = txn.savepoint().unwrap();
+        db.txn_get(&mut txn, b"x").unwrap();
+        db.txn_scan(&mut txn, b"w", b"y", 10).unwrap();
+        txn.rollback_to(savepoint).unwrap();
+        db.put_internal(b"x", &wrap_raw_value(b"1")).unwrap();
+        db.commit_transaction(&mut txn).unwrap();
+        assert_eq!(db.get_internal(b"a").unwrap(), Some(b"1".to_vec()));
+
+        let mut txn = db.begin_write();
+        db.txn_get(&mut txn, b"x").unwrap();
+        let savepoint = txn.savepoint().unwrap();
+        txn.rollback_to(savepoint).unwrap();
+        db.put_internal(b"x", &wrap_raw_value(b"2")).unwrap();
+        txn.put(b"a".to_vec(), b"2".to_vec()).unwrap();
+        assert!(matches!(
+            db.commit_transaction(&mut txn),
+            Err(SikioError::TransactionConflict { .. })
+        ));
+    }
+
+    #[test]
+    fn legacy_metadata_without_page_size_still_parses() {
+        let mut bytes = vec![0u8; DEFAULT_PAGE_SIZE];
+        bytes[0..8].copy_from_slice(&METADATA_MAGIC.to_le_bytes());
+        bytes[8..16].copy_from_slice(&2u64.to_le_bytes());
+        bytes[16..24].copy_from_slice(&9u64.to_le_bytes());
+        bytes[24..32].copy_from_slice(&17u64.to_le_bytes());
+        bytes[36..40].copy_from_slice(&1u32.to_le_bytes());
+        bytes[40..48].copy_from_slice(&5u64.to_le_bytes());
+        let checksum = Metadata::checksum_new(&bytes).unwrap();
+        bytes[32..36].copy_from_slice(&checksum.to_le_bytes());
+        let meta = Metadata::from_bytes(&bytes).unwrap();
+        assert_eq!(meta.page_size, DEFAULT_PAGE_SIZE);
+        assert_eq!(meta.root_page_id, 2);
+        assert_eq!(meta.lsn, 17);
+        assert_eq!(meta.free_page_ids, vec![5]);
+    }
+
+    #[test]
+    fn open_path_persists_and_locks() {
+        let dir = std::env::temp_dir().join(format!("sikiodb-open-path-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        {
+            let mut db = SikioDB::open_path(&dir).unwrap();
+            db.put_internal(b"a", &wrap_raw_value(b"1")).unwrap();
+            assert!(matches!(
+                SikioDB::open_path(&dir),
+                Err(SikioError::DatabaseLocked(_))
+            ));
+        }
+        let mut db = SikioDB::open_path(&dir).unwrap();
+        assert_eq!(db.get_internal(b"a").unwrap(), Some(b"1".to_vec()));
+        drop(db);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn vacuum_into_path_writes_standalone_copy() {
+        let dir = std::env::temp_dir().join(format!("sikiodb-vacuum-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut db = SikioDB::open_in_memory().unwrap();
+        db.put_internal(b"a", &wrap_raw_value(b"1")).unwrap();
+        db.vacuum_into(&dir, false).unwrap();
+        assert!(db.vacuum_into(&dir, false).is_err());
+        let mut copy = SikioDB::open_path(&dir).unwrap();
+        assert_eq!(copy.get_internal(b"a").unwrap(), Some(b"1".to_vec()));
+        drop(copy);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        db.vacuum_into(&dir, true).unwrap();
+        assert!(!dir.exists());
+        assert_eq!(db.get_internal(b"a").unwrap(), Some(b"1".to_vec()));
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), b"keep").unwrap();
+        db.vacuum_into(&dir, true).unwrap();
+        assert!(dir.join("notes.txt").exists());
+        assert!(!dir.join("data.sdb").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
 
-impl JsWriteTransaction {
-    pub fn is_active(&self) -> bool {
-        self.inner.is_active()
+#[cfg(test)]
+mod crash_tests {
+    use super::*;
+    use crate::fault::{FaultHandle, FaultInjectingStorage};
+    use std::collections::BTreeMap;
+
+    type Model = BTreeMap<Vec<u8>, Vec<u8>>;
+
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn new(seed: u64) -> Self {
+            XorShift(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+        }
+
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn bytes(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next() as u8).collect()
+        }
     }
 
-    pub fn ops_count(&self) -> usize {
-        self.inner.ops_count()
+    #[derive(Clone, Debug)]
+    enum CrashOp {
+        Txn(Vec<(Vec<u8>, Option<Vec<u8>>)>),
+        Checkpoint,
     }
 
-    pub fn prepare_wal(&mut self, sequence: u64) -> u64 {
-        self.inner.prepare_wal(sequence)
+    fn random_op(rng: &mut XorShift) -> CrashOp {
+        if rng.below(8) == 0 {
+            return CrashOp::Checkpoint;
+        }
+        let writes = (0..1 + rng.below(5))
+            .map(|_| {
+                let key = format!("key{:03}", rng.below(300)).into_bytes();
+                if rng.below(5) == 0 {
+                    (key, None)
+                } else if rng.below(20) == 0 {
+                    let len = 1500 + rng.below(3000) as usize;
+                    (key, Some(rng.bytes(len)))
+                } else {
+                    let len = rng.below(120) as usize;
+                    (key, Some(rng.bytes(len)))
+                }
+            })
+            .collect();
+        CrashOp::Txn(writes)
     }
 
-    pub fn get_wal_bytes(&self) -> Vec<u8> {
-        self.inner.wal_bytes().to_vec()
+    fn apply_to_model(model: &mut Model, op: &CrashOp) {
+        if let CrashOp::Txn(writes) = op {
+            for (key, value) in writes {
+                match value {
+                    Some(v) => model.insert(key.clone(), v.clone()),
+                    None => model.remove(key),
+                };
+            }
+        }
     }
 
-    pub fn take_ops_internal(&mut self) -> Vec<TransactionOp> {
-        self.inner.take_ops()
+    fn run_op(db: &mut SikioDB, op: &CrashOp) -> Result<()> {
+        match op {
+            CrashOp::Checkpoint => db.checkpoint(),
+            CrashOp::Txn(writes) if writes.len() == 1 => match &writes[0] {
+                (key, Some(value)) => db.put_internal(key, &wrap_raw_value(value)),
+                (key, None) => db.delete_internal(key).map(|_| ()),
+            },
+            CrashOp::Txn(writes) => {
+                let mut txn = db.begin_write();
+                for (key, value) in writes {
+                    match value {
+                        Some(v) => txn.put(key.clone(), v.clone())?,
+                        None => txn.delete(key.clone())?,
+                    }
+                }
+                db.commit_transaction(&mut txn)
+            }
+        }
+    }
+
+    fn settle(db: &mut SikioDB, model: &mut Model, in_flight: &mut Option<CrashOp>, seed: u64) {
+        let actual: Model = db.scan_prefix_internal(b"").unwrap().into_iter().collect();
+        if actual == *model {
+            *in_flight = None;
+            return;
+        }
+        let op = in_flight
+            .take()
+            .unwrap_or_else(|| panic!("seed {}: committed state diverged", seed));
+        apply_to_model(model, &op);
+        assert!(
+            actual == *model,
+            "seed {}: recovered state matches neither outcome of {:?}",
+            seed,
+            op
+        );
+    }
+
+    fn run_workload(seed: u64) {
+        let mut rng = XorShift::new(seed);
+        let disk = MemoryStorage::new();
+        let mut model = Model::new();
+        let mut in_flight: Option<CrashOp> = None;
+        for _ in 0..3 {
+            let faults = FaultHandle::new();
+            if rng.below(2) == 0 {
+                faults.tear_wal_appends(rng.below(64) as usize);
+            }
+            if rng.below(2) == 0 {
+                faults.tear_page_writes(1 + rng.below(4000) as usize);
+            }
+            if rng.below(4) == 0 {
+                faults.crash_at(1 + rng.below(20));
+            }
+            let storage = FaultInjectingStorage::with_handle(disk.clone(), faults.clone());
+            let mut db = match SikioDB::open_with_storage(Box::new(storage)) {
+                Ok(db) if !faults.crashed() => db,
+                _ => continue,
+            };
+            faults.disarm();
+            settle(&mut db, &mut model, &mut in_flight, seed);
+            let at = faults.io_count() + 1 + rng.below(250);
+            if rng.below(3) == 0 {
+                faults.fail_at(at);
+            } else {
+                faults.crash_at(at);
+            }
+            for _ in 0..40 {
+                let op = random_op(&mut rng);
+                if run_op(&mut db, &op).is_ok() {
+                    apply_to_model(&mut model, &op);
+                } else {
+                    in_flight = Some(op);
+                    break;
+                }
+            }
+        }
+        let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+        settle(&mut db, &mut model, &mut in_flight, seed);
+        for (key, value) in &model {
+            assert_eq!(db.get_internal(key).unwrap().as_ref(), Some(value));
+        }
+    }
+
+    #[test]
+    fn torn_checkpoint_pages_are_restored_from_wal_images() {
+        for offset in 1..40 {
+            let disk = MemoryStorage::new();
+            let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+            for i in 0..200u32 {
+                let key = format!("key{:04}", i).into_bytes();
+                db.put_internal(&key, &wrap_raw_value(b"old")).unwrap();
+            }
+            db.checkpoint().unwrap();
+            drop(db);
+            let faults = FaultHandle::new();
+            let storage = FaultInjectingStorage::with_handle(disk.clone(), faults.clone());
+            let mut db = SikioDB::open_with_storage(Box::new(storage)).unwrap();
+            for i in 0..200u32 {
+                let key = format!("key{:04}", i).into_bytes();
+                db.put_internal(&key, &wrap_raw_value(b"new")).unwrap();
+            }
+            faults.tear_page_writes(100);
+            faults.crash_at(faults.io_count() + offset);
+            let _ = db.checkpoint();
+            drop(db);
+            let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+            assert!(db.verify_integrity().unwrap().is_empty());
+            for i in 0..200u32 {
+                let key = format!("key{:04}", i).into_bytes();
+                assert_eq!(db.get_internal(&key).unwrap(), Some(b"new".to_vec()));
+            }
+        }
+    }
+
+    #[test]
+    fn crash_during_vacuum_swap_keeps_a_complete_database() {
+        for offset in (1..120).step_by(3) {
+            let disk = MemoryStorage::new();
+            let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+            for i in 0..600u32 {
+                let key = format!("key{:04}", i).into_bytes();
+                db.put_internal(&key, &wrap_raw_value(&[5u8; 100])).unwrap();
+            }
+            for i in (0..600u32).filter(|i| i % 4 != 0) {
+                let key = format!("key{:04}", i).into_bytes();
+                db.delete_internal(&key).unwrap();
+            }
+            db.checkpoint().unwrap();
+            drop(db);
+            let faults = FaultHandle::new();
+            let storage = FaultInjectingStorage::with_handle(disk.clone(), faults.clone());
+            let mut db = SikioDB::open_with_storage(Box::new(storage)).unwrap();
+            let mut image = MemoryStorage::new();
+            db.vacuum_into_storage(&mut image).unwrap();
+            faults.tear_page_writes(100);
+            faults.crash_at(faults.io_count() + offset);
+            let _ = db.replace_with_image(&image);
+            drop(db);
+            let mut db = SikioDB::open_with_storage(Box::new(disk.clone())).unwrap();
+            assert!(db.verify_integrity().unwrap().is_empty());
+            for i in 0..600u32 {
+                let key = format!("key{:04}", i).into_bytes();
+                let expected = (i % 4 == 0).then(|| vec![5u8; 100]);
+                assert_eq!(db.get_internal(&key).unwrap(), expected);
+            }
+        }
+    }
+
+    fn crash_seed_count(default: u64) -> u64 {
+        std::env::var("SIKIODB_CRASH_SEEDS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    }
+
+    #[test]
+    fn random_crashes_preserve_committed_transactions() {
+        for seed in 1..=crash_seed_count(1000) {
+            run_workload(seed);
+        }
+    }
+
+    #[test]
+    #[ignore = "long run, use --ignored"]
+    fn many_random_crashes_preserve_committed_transactions() {
+        for seed in 1..=crash_seed_count(10_000) {
+            run_workload(seed);
+        }
     }
 }
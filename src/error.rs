@@ -6,12 +6,20 @@ pub enum SikioError {
     ValueTooLarge { max: usize, actual: usize },
     PageCorrupted { page_id: u64, reason: String },
     WalCorrupted { sequence: u64, reason: String },
+    WalGap { expected: u64, found: u64 },
     IoError(String),
     ChecksumMismatch { expected: u32, actual: u32 },
     StorageNotInitialized,
     PageCacheFull,
     BTreeOverflow,
     Corrupted(String),
+    DatabaseLocked(String),
+    QuotaExceeded,
+    InvalidPageSize(usize),
+    PageSizeMismatch { stored: usize, requested: usize },
+    ChangeLogExpired { requested: u64, horizon: u64 },
+    ChangeLogDisabled,
+    TransactionConflict { key: Vec<u8>, read: u64, current: u64 },
 }
 impl fmt::Display for SikioError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -29,6 +37,11 @@ impl fmt::Display for SikioError {
             SikioError::WalCorrupted { sequence, reason } => {
                 write!(f, "WAL entry {} corrupted: {}", sequence, reason)
             }
+            SikioError::WalGap { expected, found } => write!(
+                f,
+                "WAL does not continue the restore base: expected entry {} but found {}",
+                expected, found
+            ),
             SikioError::IoError(msg) => write!(f, "IO error: {}", msg),
             SikioError::ChecksumMismatch { expected, actual } => {
                 write!(
@@ -41,6 +54,33 @@ impl fmt::Display for SikioError {
             SikioError::PageCacheFull => write!(f, "Page cache full"),
             SikioError::BTreeOverflow => write!(f, "B-Tree node overflow"),
             SikioError::Corrupted(msg) => write!(f, "Data corrupted: {}", msg),
+            SikioError::DatabaseLocked(path) => {
+                write!(f, "Database at {} is locked by another process", path)
+            }
+            SikioError::QuotaExceeded => write!(f, "Storage quota exceeded"),
+            SikioError::InvalidPageSize(size) => write!(
+                f,
+                "Invalid page size {}: must be 4096, 8192, 16384 or 32768",
+                size
+            ),
+            SikioError::PageSizeMismatch { stored, requested } => write!(
+                f,
+                "Database was created with page size {} but opened with page size {}",
+                stored, requested
+            ),
+            SikioError::ChangeLogExpired { requested, horizon } => write!(
+                f,
+                "Changes since {} are no longer retained (oldest available is {})",
+                requested, horizon
+            ),
+            SikioError::ChangeLogDisabled => write!(f, "Change log is disabled"),
+            SikioError::TransactionConflict { key, read, current } => write!(
+                f,
+                "Transaction conflict: key {:?} changed at version {} after it was read at version {}",
+                String::from_utf8_lossy(key),
+                current,
+                read
+            ),
         }
     }
 }
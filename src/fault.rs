@@ -0,0 +1,401 @@
+use crate::error::{Result, SikioError};
+use crate::storage::StorageBackend;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+#[derive(Default)]
+struct FaultState {
+    io_count: u64,
+    crash_at: Option<u64>,
+    fail_at: Option<u64>,
+    fail_once_at: Option<u64>,
+    torn_wal_len: Option<usize>,
+    torn_page_len: Option<usize>,
+    quota: Option<u64>,
+    crashed: bool,
+    failed: bool,
+}
+#[derive(Clone, Default)]
+pub struct FaultHandle {
+    state: Rc<RefCell<FaultState>>,
+}
+impl FaultHandle {
+    pub fn new() -> Self {
+        FaultHandle::default()
+    }
+    pub fn crash_at(&self, io_index: u64) {
+        self.state.borrow_mut().crash_at = Some(io_index);
+    }
+    pub fn fail_at(&self, io_index: u64) {
+        self.state.borrow_mut().fail_at = Some(io_index);
+    }
+    pub fn fail_once_at(&self, io_index: u64) {
+        self.state.borrow_mut().fail_once_at = Some(io_index);
+    }
+    pub fn tear_wal_appends(&self, durable_len: usize) {
+        self.state.borrow_mut().torn_wal_len = Some(durable_len);
+    }
+    pub fn tear_page_writes(&self, durable_len: usize) {
+        self.state.borrow_mut().torn_page_len = Some(durable_len);
+    }
+    pub fn set_quota(&self, bytes: Option<u64>) {
+        self.state.borrow_mut().quota = bytes;
+    }
+    pub fn disarm(&self) {
+        let mut state = self.state.borrow_mut();
+        state.crash_at = None;
+        state.fail_at = None;
+        state.fail_once_at = None;
+    }
+    pub fn io_count(&self) -> u64 {
+        self.state.borrow().io_count
+    }
+    pub fn crashed(&self) -> bool {
+        self.state.borrow().crashed
+    }
+    pub fn failed(&self) -> bool {
+        self.state.borrow().failed
+    }
+}
+enum Fault {
+    None,
+    Crash,
+    Fail,
+}
+pub struct FaultInjectingStorage<S: StorageBackend> {
+    inner: S,
+    pending_pages: RefCell<BTreeMap<u64, Vec<u8>>>,
+    pending_wal: RefCell<Vec<u8>>,
+    faults: FaultHandle,
+}
+impl<S: StorageBackend> FaultInjectingStorage<S> {
+    pub fn new(inner: S) -> Self {
+        Self::with_handle(inner, FaultHandle::new())
+    }
+    pub fn with_handle(inner: S, faults: FaultHandle) -> Self {
+        FaultInjectingStorage {
+            inner,
+            pending_pages: RefCell::new(BTreeMap::new()),
+            pending_wal: RefCell::new(Vec::new()),
+            faults,
+        }
+    }
+    pub fn handle(&self) -> FaultHandle {
+        self.faults.clone()
+    }
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+    fn next_io(&self) -> Result<Fault> {
+        let mut state = self.faults.state.borrow_mut();
+        if state.crashed {
+            return Err(SikioError::IoError("Injected crash".into()));
+        }
+        if state.failed {
+            return Err(SikioError::IoError("Injected I/O failure".into()));
+        }
+        state.io_count += 1;
+        if state.crash_at == Some(state.io_count) {
+            state.crashed = true;
+            return Ok(Fault::Crash);
+        }
+        if state.fail_at == Some(state.io_count) {
+            state.failed = true;
+            return Ok(Fault::Fail);
+        }
+        if state.fail_once_at == Some(state.io_count) {
+            return Ok(Fault::Fail);
+        }
+        Ok(Fault::None)
+    }
+    fn check_io(&self) -> Result<()> {
+        match self.next_io()? {
+            Fault::None => Ok(()),
+            Fault::Crash => {
+                self.drop_unflushed();
+                Err(SikioError::IoError("Injected crash".into()))
+            }
+            Fault::Fail => Err(SikioError::IoError("Injected I/O failure".into())),
+        }
+    }
+    fn drop_unflushed(&self) {
+        self.pending_pages.borrow_mut().clear();
+        self.pending_wal.borrow_mut().clear();
+    }
+    fn check_quota(&self, data_end: u64, wal_end: u64) -> Result<()> {
+        match self.faults.state.borrow().quota {
+            Some(quota) if data_end + wal_end > quota => Err(SikioError::QuotaExceeded),
+            _ => Ok(()),
+        }
+    }
+    fn torn_len(&self, for_wal: bool) -> Option<usize> {
+        let state = self.faults.state.borrow();
+        if for_wal {
+            state.torn_wal_len
+        } else {
+            state.torn_page_len
+        }
+    }
+}
+impl<S: StorageBackend> StorageBackend for FaultInjectingStorage<S> {
+    fn page_size(&self) -> usize {
+        self.inner.page_size()
+    }
+    fn read_data(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+        self.check_io()?;
+        let end = offset + length as u64;
+        if end > self.data_size() {
+            return Err(SikioError::IoError("Data read beyond size".into()));
+        }
+        let mut buffer = vec![0u8; length];
+        let durable = self.inner.data_size();
+        if offset < durable {
+            let part = self
+                .inner
+                .read_data(offset, (end.min(durable) - offset) as usize)?;
+            buffer[..part.len()].copy_from_slice(&part);
+        }
+        let page_size = self.page_size() as u64;
+        for (&page_id, bytes) in self.pending_pages.borrow().iter() {
+            let page_start = page_id * page_size;
+            let from = page_start.max(offset);
+            let to = (page_start + page_size).min(end);
+            if from < to {
+                buffer[(from - offset) as usize..(to - offset) as usize].copy_from_slice(
+                    &bytes[(from - page_start) as usize..(to - page_start) as usize],
+                );
+            }
+        }
+        Ok(buffer)
+    }
+    fn read_page(&self, page_id: u64) -> Result<Vec<u8>> {
+        self.check_io()?;
+        if let Some(bytes) = self.pending_pages.borrow().get(&page_id) {
+            return Ok(bytes.clone());
+        }
+        self.inner.read_page(page_id)
+    }
+    fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()> {
+        match self.next_io()? {
+            Fault::None => {}
+            Fault::Fail => return Err(SikioError::IoError("Injected I/O failure".into())),
+            Fault::Crash => {
+                self.drop_unflushed();
+                if let Some(len) = self.torn_len(false) {
+                    let mut page = self
+                        .inner
+                        .read_page(page_id)
+                        .unwrap_or_else(|_| vec![0u8; self.inner.page_size()]);
+                    let len = len.min(data.len().saturating_sub(1));
+                    page[..len].copy_from_slice(&data[..len]);
+                    self.inner.write_page(page_id, &page)?;
+                    self.inner.flush_data()?;
+                }
+                return Err(SikioError::IoError("Injected crash".into()));
+            }
+        }
+        if data.len() != self.page_size() {
+            return Err(SikioError::IoError(format!(
+                "Invalid page size: {}",
+                data.len()
+            )));
+        }
+        let data_end = self
+            .data_size()
+            .max((page_id + 1) * self.page_size() as u64);
+        self.check_quota(data_end, self.wal_size())?;
+        self.pending_pages
+            .borrow_mut()
+            .insert(page_id, data.to_vec());
+        Ok(())
+    }
+    fn append_wal(&mut self, data: &[u8]) -> Result<u64> {
+        match self.next_io()? {
+            Fault::None => {}
+            Fault::Fail => return Err(SikioError::IoError("Injected I/O failure".into())),
+            Fault::Crash => {
+                self.drop_unflushed();
+                if let Some(len) = self.torn_len(true) {
+                    let len = len.min(data.len().saturating_sub(1));
+                    self.inner.append_wal(&data[..len])?;
+                    self.inner.flush_wal()?;
+                }
+                return Err(SikioError::IoError("Injected crash".into()));
+            }
+        }
+        let offset = self.wal_size();
+        self.check_quota(self.data_size(), offset + data.len() as u64)?;
+        self.pending_wal.borrow_mut().extend_from_slice(data);
+        Ok(offset)
+    }
+    fn read_wal(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+        self.check_io()?;
+        if offset + length as u64 > self.wal_size() {
+            return Err(SikioError::IoError("WAL read beyond size".into()));
+        }
+        let durable = self.inner.wal_size();
+        let end = offset + length as u64;
+        let mut buffer = Vec::with_capacity(length);
+        if offset < durable {
+            let durable_end = end.min(durable);
+            buffer.extend(
+                self.inner
+                    .read_wal(offset, (durable_end - offset) as usize)?,
+            );
+        }
+        if end > durable {
+            let pending = self.pending_wal.borrow();
+            let start = offset.saturating_sub(durable) as usize;
+            buffer.extend_from_slice(&pending[start..(end - durable) as usize]);
+        }
+        Ok(buffer)
+    }
+    fn flush_data(&mut self) -> Result<()> {
+        self.check_io()?;
+        let pages = std::mem::take(&mut *self.pending_pages.borrow_mut());
+        for (page_id, bytes) in pages {
+            self.inner.write_page(page_id, &bytes)?;
+        }
+        self.inner.flush_data()
+    }
+    fn flush_wal(&mut self) -> Result<()> {
+        self.check_io()?;
+        let pending = std::mem::take(&mut *self.pending_wal.borrow_mut());
+        if !pending.is_empty() {
+            self.inner.append_wal(&pending)?;
+        }
+        self.inner.flush_wal()
+    }
+    fn truncate_wal_to(&mut self, length: u64) -> Result<()> {
+        self.check_io()?;
+        let durable = self.inner.wal_size();
+        if length >= durable {
+            let keep = (length - durable) as usize;
+            self.pending_wal.borrow_mut().truncate(keep);
+            Ok(())
+        } else {
+            self.pending_wal.borrow_mut().clear();
+            self.inner.truncate_wal_to(length)
+        }
+    }
+    fn truncate_data(&mut self, length: u64) -> Result<()> {
+        self.check_io()?;
+        let first_dropped = length.div_ceil(self.page_size() as u64);
+        self.pending_pages
+            .borrow_mut()
+            .retain(|&page_id, _| page_id < first_dropped);
+        self.inner.truncate_data(length)
+    }
+    fn data_size(&self) -> u64 {
+        let pending_end = self
+            .pending_pages
+            .borrow()
+            .keys()
+            .next_back()
+            .map_or(0, |&id| (id + 1) * self.page_size() as u64);
+        self.inner.data_size().max(pending_end)
+    }
+    fn wal_size(&self) -> u64 {
+        self.inner.wal_size() + self.pending_wal.borrow().len() as u64
+    }
+    fn archive_wal(&mut self, segment: u64) -> Result<()> {
+        self.check_io()?;
+        if !self.pending_wal.borrow().is_empty() {
+            return Err(SikioError::IoError(
+                "Cannot archive an unflushed WAL".into(),
+            ));
+        }
+        self.inner.archive_wal(segment)
+    }
+    fn archived_wal_segments(&self) -> Result<Vec<u64>> {
+        self.check_io()?;
+        self.inner.archived_wal_segments()
+    }
+    fn archived_wal_size(&self, segment: u64) -> Result<u64> {
+        self.check_io()?;
+        self.inner.archived_wal_size(segment)
+    }
+    fn read_archived_wal(&self, segment: u64, offset: u64, length: usize) -> Result<Vec<u8>> {
+        self.check_io()?;
+        self.inner.read_archived_wal(segment, offset, length)
+    }
+    fn remove_archived_wal(&mut self, segment: u64) -> Result<()> {
+        self.check_io()?;
+        self.inner.remove_archived_wal(segment)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page::DEFAULT_PAGE_SIZE;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn unflushed_writes_are_lost_on_crash() {
+        let disk = MemoryStorage::new();
+        let mut storage = FaultInjectingStorage::new(disk.clone());
+        let faults = storage.handle();
+        storage.write_page(2, &[1u8; DEFAULT_PAGE_SIZE]).unwrap();
+        storage.append_wal(b"committed").unwrap();
+        storage.flush_data().unwrap();
+        storage.flush_wal().unwrap();
+        storage.write_page(2, &[2u8; DEFAULT_PAGE_SIZE]).unwrap();
+        storage.append_wal(b"lost").unwrap();
+        assert_eq!(storage.read_page(2).unwrap(), vec![2u8; DEFAULT_PAGE_SIZE]);
+        assert_eq!(storage.read_wal(0, 13).unwrap(), b"committedlost".to_vec());
+        faults.crash_at(faults.io_count() + 1);
+        assert!(storage.flush_data().is_err());
+        assert!(faults.crashed());
+        assert!(storage.read_page(2).is_err());
+        assert_eq!(disk.data_bytes()[2 * DEFAULT_PAGE_SIZE], 1);
+        assert_eq!(disk.wal_bytes(), b"committed".to_vec());
+    }
+
+    #[test]
+    fn torn_page_write_keeps_only_prefix() {
+        let disk = MemoryStorage::new();
+        let mut storage = FaultInjectingStorage::new(disk.clone());
+        let faults = storage.handle();
+        storage.write_page(0, &[1u8; DEFAULT_PAGE_SIZE]).unwrap();
+        storage.flush_data().unwrap();
+        faults.tear_page_writes(100);
+        faults.crash_at(faults.io_count() + 1);
+        assert!(storage.write_page(0, &[2u8; DEFAULT_PAGE_SIZE]).is_err());
+        let page = disk.read_page(0).unwrap();
+        assert!(page[..100].iter().all(|&b| b == 2));
+        assert!(page[100..].iter().all(|&b| b == 1));
+    }
+
+    #[test]
+    fn fails_nth_call_and_everything_after() {
+        let mut storage = FaultInjectingStorage::new(MemoryStorage::new());
+        let faults = storage.handle();
+        faults.fail_at(3);
+        storage.append_wal(b"a").unwrap();
+        storage.append_wal(b"b").unwrap();
+        assert!(storage.append_wal(b"c").is_err());
+        assert!(faults.failed());
+        assert!(!faults.crashed());
+        assert!(storage.flush_wal().is_err());
+        assert_eq!(faults.io_count(), 3);
+    }
+
+    #[test]
+    fn archives_only_the_flushed_wal() {
+        let disk = MemoryStorage::new();
+        let mut storage = FaultInjectingStorage::new(disk.clone());
+        let faults = storage.handle();
+        storage.append_wal(b"segment").unwrap();
+        assert!(storage.archive_wal(1).is_err());
+        storage.flush_wal().unwrap();
+        storage.archive_wal(1).unwrap();
+        assert_eq!(disk.archived_wal_segments().unwrap(), vec![1]);
+        assert_eq!(
+            storage.read_archived_wal(1, 0, 7).unwrap(),
+            b"segment".to_vec()
+        );
+        faults.fail_at(faults.io_count() + 1);
+        assert!(storage.remove_archived_wal(1).is_err());
+        assert_eq!(disk.archived_wal_segments().unwrap(), vec![1]);
+    }
+}
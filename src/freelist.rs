@@ -0,0 +1,222 @@
+use crate::error::{Result, SikioError};
+use crate::page::{page_data_size, Page, PAGE_TYPE_FREELIST};
+use crate::storage::StorageBackend;
+use std::rc::Rc;
+const TRUNK_NEXT_SIZE: usize = 8;
+#[derive(Debug, Clone)]
+struct Trunk {
+    page_id: u64,
+    ids: Vec<u64>,
+    available: usize,
+    dirty: bool,
+}
+#[derive(Debug, Clone)]
+pub struct FreeList {
+    page_size: usize,
+    trunks: Vec<Rc<Trunk>>,
+    pending: Vec<u64>,
+    free_count: u64,
+}
+impl FreeList {
+    pub fn new(page_size: usize) -> Self {
+        FreeList {
+            page_size,
+            trunks: Vec::new(),
+            pending: Vec::new(),
+            free_count: 0,
+        }
+    }
+    pub fn from_ids(page_size: usize, ids: Vec<u64>) -> Self {
+        let mut list = FreeList::new(page_size);
+        for id in ids {
+            list.release(id);
+        }
+        list
+    }
+    pub fn load(storage: &dyn StorageBackend, root: u64) -> Result<Self> {
+        let mut list = FreeList::new(storage.page_size());
+        let max_trunks = storage.data_page_count();
+        let mut current = root;
+        while current != 0 {
+            if list.trunks.len() as u64 >= max_trunks {
+                return Err(SikioError::Corrupted("Free list chain is cyclic".into()));
+            }
+            let page = Page::from_bytes(&storage.read_page(current)?)?;
+            let count = page.header.item_count as usize;
+            if page.header.page_type != PAGE_TYPE_FREELIST
+                || TRUNK_NEXT_SIZE + count * 8 > page.data.len()
+            {
+                return Err(SikioError::PageCorrupted {
+                    page_id: current,
+                    reason: "Invalid free list trunk".into(),
+                });
+            }
+            let ids: Vec<u64> = page.data[TRUNK_NEXT_SIZE..TRUNK_NEXT_SIZE + count * 8]
+                .chunks_exact(8)
+                .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap_or([0; 8])))
+                .collect();
+            list.free_count += ids.len() as u64;
+            list.trunks.push(Rc::new(Trunk {
+                page_id: current,
+                available: ids.len(),
+                ids,
+                dirty: false,
+            }));
+            current =
+                u64::from_le_bytes(page.data[0..TRUNK_NEXT_SIZE].try_into().unwrap_or([0; 8]));
+        }
+        list.trunks.reverse();
+        Ok(list)
+    }
+    pub fn ids_per_trunk(page_size: usize) -> usize {
+        (page_data_size(page_size) - TRUNK_NEXT_SIZE) / 8
+    }
+    pub fn root(&self) -> u64 {
+        self.trunks.last().map_or(0, |trunk| trunk.page_id)
+    }
+    pub fn free_count(&self) -> u64 {
+        self.free_count
+    }
+    pub fn trunk_count(&self) -> usize {
+        self.trunks.len()
+    }
+    pub fn trunk_page_ids(&self) -> Vec<u64> {
+        self.trunks.iter().map(|trunk| trunk.page_id).collect()
+    }
+    pub fn allocate(&mut self) -> Option<u64> {
+        let mut index = self.trunks.len();
+        while index > 0 {
+            index -= 1;
+            if self.trunks[index].available > 0 {
+                let trunk = Rc::make_mut(&mut self.trunks[index]);
+                trunk.available -= 1;
+                let id = trunk.ids.remove(trunk.available);
+                trunk.dirty = true;
+                self.free_count -= 1;
+                return Some(id);
+            }
+            if self.trunks[index].ids.is_empty() {
+                let retired = self.trunks.remove(index);
+                if let Some(above) = self.trunks.get_mut(index) {
+                    Rc::make_mut(above).dirty = true;
+                }
+                self.release(retired.page_id);
+            }
+        }
+        None
+    }
+    pub fn release(&mut self, page_id: u64) {
+        self.pending.push(page_id);
+        self.free_count += 1;
+    }
+    pub fn prepare_checkpoint(&mut self) {
+        let capacity = Self::ids_per_trunk(self.page_size);
+        for id in std::mem::take(&mut self.pending) {
+            match self.trunks.last_mut() {
+                Some(head) if head.ids.len() < capacity => {
+                    let head = Rc::make_mut(head);
+                    head.ids.push(id);
+                    head.dirty = true;
+                }
+                _ => {
+                    self.trunks.push(Rc::new(Trunk {
+                        page_id: id,
+                        ids: Vec::new(),
+                        available: 0,
+                        dirty: true,
+                    }));
+                    self.free_count -= 1;
+                }
+            }
+        }
+    }
+    pub fn finish_checkpoint(&mut self) {
+        for trunk in &mut self.trunks {
+            if trunk.available != trunk.ids.len() {
+                let trunk = Rc::make_mut(trunk);
+                trunk.available = trunk.ids.len();
+            }
+        }
+    }
+    pub fn take_dirty_pages(&mut self) -> Vec<Page> {
+        let mut pages = Vec::new();
+        for index in 0..self.trunks.len() {
+            if !self.trunks[index].dirty {
+                continue;
+            }
+            let next = match index {
+                0 => 0,
+                _ => self.trunks[index - 1].page_id,
+            };
+            let trunk = Rc::make_mut(&mut self.trunks[index]);
+            trunk.dirty = false;
+            let mut page = Page::new(trunk.page_id, PAGE_TYPE_FREELIST, self.page_size);
+            page.header.item_count = trunk.ids.len() as u16;
+            page.data[0..TRUNK_NEXT_SIZE].copy_from_slice(&next.to_le_bytes());
+            for (i, id) in trunk.ids.iter().enumerate() {
+                let offset = TRUNK_NEXT_SIZE + i * 8;
+                page.data[offset..offset + 8].copy_from_slice(&id.to_le_bytes());
+            }
+            pages.push(page);
+        }
+        pages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page::DEFAULT_PAGE_SIZE;
+    use crate::storage::MemoryStorage;
+
+    fn persist(list: &mut FreeList, storage: &mut MemoryStorage) {
+        list.prepare_checkpoint();
+        for page in list.take_dirty_pages() {
+            storage
+                .write_page(page.header.page_id, &page.to_bytes())
+                .unwrap();
+        }
+        storage.flush_data().unwrap();
+        list.finish_checkpoint();
+    }
+
+    #[test]
+    fn released_pages_stay_locked_until_checkpoint() {
+        let mut list = FreeList::new(DEFAULT_PAGE_SIZE);
+        list.release(7);
+        list.release(8);
+        assert_eq!(list.allocate(), None);
+        list.prepare_checkpoint();
+        assert_eq!(list.allocate(), None);
+        list.finish_checkpoint();
+        assert_eq!(list.root(), 7);
+        assert_eq!(list.allocate(), Some(8));
+        assert_eq!(list.allocate(), None);
+        assert_eq!(list.free_count(), 1);
+    }
+
+    #[test]
+    fn trunk_chain_roundtrips_through_storage() {
+        let mut storage = MemoryStorage::new();
+        let per_trunk = FreeList::ids_per_trunk(DEFAULT_PAGE_SIZE) as u64;
+        let total = per_trunk * 3 + 10;
+        let mut list = FreeList::from_ids(DEFAULT_PAGE_SIZE, (2..2 + total).collect());
+        persist(&mut list, &mut storage);
+        assert_eq!(list.trunk_count(), 4);
+        assert_eq!(list.free_count(), total - 4);
+
+        let mut loaded = FreeList::load(&storage, list.root()).unwrap();
+        assert_eq!(loaded.free_count(), total - 4);
+        let mut allocated = Vec::new();
+        while let Some(id) = loaded.allocate() {
+            allocated.push(id);
+        }
+        allocated.sort_unstable();
+        allocated.dedup();
+        assert_eq!(allocated.len() as u64, total - 4);
+        assert_eq!(loaded.trunk_count(), 0);
+        assert_eq!(loaded.free_count(), 4);
+        persist(&mut loaded, &mut storage);
+        assert_eq!(loaded.allocate().map(|_| loaded.free_count()), Some(2));
+    }
+}
@@ -0,0 +1,272 @@
+use crate::error::{Result, SikioError};
+use crate::page::DEFAULT_PAGE_SIZE;
+use crate::storage::StorageBackend;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+pub struct FileStorage {
+    dir: PathBuf,
+    data_file: File,
+    wal_file: File,
+    _lock_file: File,
+    page_size: usize,
+    data_size: u64,
+    wal_size: u64,
+}
+impl FileStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with_page_size(path, DEFAULT_PAGE_SIZE)
+    }
+    pub fn open_with_page_size(path: impl AsRef<Path>, page_size: usize) -> Result<Self> {
+        let dir = path.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir).map_err(io_error)?;
+        let lock_file = open_file(&dir.join("LOCK"))?;
+        match lock_file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(SikioError::DatabaseLocked(dir.display().to_string()));
+            }
+            Err(TryLockError::Error(e)) => return Err(io_error(e)),
+        }
+        let data_file = open_file(&dir.join("data.sdb"))?;
+        let wal_file = open_file(&dir.join("wal.sdb"))?;
+        sync_dir(&dir)?;
+        let data_size = data_file.metadata().map_err(io_error)?.len();
+        let wal_size = wal_file.metadata().map_err(io_error)?.len();
+        Ok(FileStorage {
+            dir,
+            data_file,
+            wal_file,
+            _lock_file: lock_file,
+            page_size,
+            data_size,
+            wal_size,
+        })
+    }
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+    pub fn remove(self) -> Result<()> {
+        let dir = self.dir.clone();
+        drop(self);
+        for name in ["data.sdb", "wal.sdb", "LOCK"] {
+            std::fs::remove_file(dir.join(name)).map_err(io_error)?;
+        }
+        match std::fs::remove_dir(&dir) {
+            Err(e) if e.kind() == ErrorKind::DirectoryNotEmpty => Ok(()),
+            result => result.map_err(io_error),
+        }
+    }
+    fn segment_path(&self, segment: u64) -> PathBuf {
+        self.dir.join(format!("wal.{:06}.sdb", segment))
+    }
+}
+impl StorageBackend for FileStorage {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+    fn read_data(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+        if offset + length as u64 > self.data_size {
+            return Err(SikioError::IoError("Data read beyond size".into()));
+        }
+        let mut buffer = vec![0u8; length];
+        read_at(&self.data_file, offset, &mut buffer)?;
+        Ok(buffer)
+    }
+    fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()> {
+        if data.len() != self.page_size {
+            return Err(SikioError::IoError(format!(
+                "Invalid page size: {}",
+                data.len()
+            )));
+        }
+        let offset = page_id * self.page_size as u64;
+        write_at(&self.data_file, offset, data)?;
+        let new_end = offset + self.page_size as u64;
+        if new_end > self.data_size {
+            self.data_size = new_end;
+        }
+        Ok(())
+    }
+    fn append_wal(&mut self, data: &[u8]) -> Result<u64> {
+        let offset = self.wal_size;
+        write_at(&self.wal_file, offset, data)?;
+        self.wal_size += data.len() as u64;
+        Ok(offset)
+    }
+    fn read_wal(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+        if offset + length as u64 > self.wal_size {
+            return Err(SikioError::IoError("WAL read beyond size".into()));
+        }
+        let mut buffer = vec![0u8; length];
+        read_at(&self.wal_file, offset, &mut buffer)?;
+        Ok(buffer)
+    }
+    fn flush_data(&mut self) -> Result<()> {
+        self.data_file.sync_data().map_err(io_error)
+    }
+    fn flush_wal(&mut self) -> Result<()> {
+        self.wal_file.sync_data().map_err(io_error)
+    }
+    fn truncate_wal_to(&mut self, length: u64) -> Result<()> {
+        let length = length.min(self.wal_size);
+        self.wal_file.set_len(length).map_err(io_error)?;
+        self.wal_file.sync_all().map_err(io_error)?;
+        self.wal_size = length;
+        Ok(())
+    }
+    fn truncate_data(&mut self, length: u64) -> Result<()> {
+        let length = length.min(self.data_size);
+        self.data_file.set_len(length).map_err(io_error)?;
+        self.data_file.sync_all().map_err(io_error)?;
+        self.data_size = length;
+        Ok(())
+    }
+    fn data_size(&self) -> u64 {
+        self.data_size
+    }
+    fn wal_size(&self) -> u64 {
+        self.wal_size
+    }
+    fn archive_wal(&mut self, segment: u64) -> Result<()> {
+        let wal = self.read_wal(0, self.wal_size as usize)?;
+        let path = self.segment_path(segment);
+        let temp = path.with_extension("tmp");
+        let file = File::create(&temp).map_err(io_error)?;
+        write_at(&file, 0, &wal)?;
+        file.sync_all().map_err(io_error)?;
+        std::fs::rename(&temp, &path).map_err(io_error)?;
+        sync_dir(&self.dir)
+    }
+    fn archived_wal_segments(&self) -> Result<Vec<u64>> {
+        let mut segments = Vec::new();
+        for entry in std::fs::read_dir(&self.dir).map_err(io_error)? {
+            let name = entry.map_err(io_error)?.file_name();
+            let segment = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("wal."))
+                .and_then(|name| name.strip_suffix(".sdb"))
+                .and_then(|number| number.parse::<u64>().ok());
+            segments.extend(segment);
+        }
+        segments.sort_unstable();
+        Ok(segments)
+    }
+    fn archived_wal_size(&self, segment: u64) -> Result<u64> {
+        let metadata = std::fs::metadata(self.segment_path(segment)).map_err(io_error)?;
+        Ok(metadata.len())
+    }
+    fn read_archived_wal(&self, segment: u64, offset: u64, length: usize) -> Result<Vec<u8>> {
+        let file = File::open(self.segment_path(segment)).map_err(io_error)?;
+        let mut buffer = vec![0u8; length];
+        read_at(&file, offset, &mut buffer)?;
+        Ok(buffer)
+    }
+    fn remove_archived_wal(&mut self, segment: u64) -> Result<()> {
+        std::fs::remove_file(self.segment_path(segment)).map_err(io_error)?;
+        sync_dir(&self.dir)
+    }
+}
+fn io_error(e: std::io::Error) -> SikioError {
+    match e.kind() {
+        ErrorKind::StorageFull | ErrorKind::QuotaExceeded => SikioError::QuotaExceeded,
+        _ => SikioError::IoError(e.to_string()),
+    }
+}
+fn open_file(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(io_error)
+}
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<()> {
+    File::open(dir).and_then(|d| d.sync_all()).map_err(io_error)
+}
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<()> {
+    Ok(())
+}
+fn read_at(mut file: &File, offset: u64, buffer: &mut [u8]) -> Result<()> {
+    file.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+    file.read_exact(buffer).map_err(io_error)
+}
+fn write_at(mut file: &File, offset: u64, data: &[u8]) -> Result<()> {
+    file.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+    file.write_all(data).map_err(io_error)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "sikiodb-{}-{}-{}",
+            name,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn pages_and_wal_persist_across_reopen() {
+        let dir = temp_dir("reopen");
+        {
+            let mut storage = FileStorage::open(&dir).unwrap();
+            storage.write_page(3, &[9u8; DEFAULT_PAGE_SIZE]).unwrap();
+            storage.append_wal(b"hello").unwrap();
+            storage.append_wal(b"world").unwrap();
+            storage.flush_data().unwrap();
+            storage.flush_wal().unwrap();
+        }
+        let mut storage = FileStorage::open(&dir).unwrap();
+        assert_eq!(storage.data_page_count(), 4);
+        assert_eq!(storage.read_page(3).unwrap(), vec![9u8; DEFAULT_PAGE_SIZE]);
+        assert_eq!(storage.read_page(0).unwrap(), vec![0u8; DEFAULT_PAGE_SIZE]);
+        assert!(storage.read_page(4).is_err());
+        assert_eq!(storage.read_wal(0, 10).unwrap(), b"helloworld".to_vec());
+        storage.truncate_wal().unwrap();
+        assert_eq!(storage.wal_size(), 0);
+        drop(storage);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wal_segments_are_archived_next_to_the_log() {
+        let dir = temp_dir("archive");
+        let mut storage = FileStorage::open(&dir).unwrap();
+        storage.append_wal(b"first").unwrap();
+        storage.archive_wal(1).unwrap();
+        storage.truncate_wal().unwrap();
+        storage.append_wal(b"second").unwrap();
+        storage.archive_wal(12).unwrap();
+        assert!(dir.join("wal.000012.sdb").exists());
+        assert_eq!(storage.archived_wal_segments().unwrap(), vec![1, 12]);
+        assert_eq!(storage.archived_wal_size(12).unwrap(), 6);
+        assert_eq!(storage.read_archived_wal(1, 1, 3).unwrap(), b"irs".to_vec());
+        storage.remove_archived_wal(1).unwrap();
+        assert_eq!(storage.archived_wal_segments().unwrap(), vec![12]);
+        drop(storage);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn second_open_is_rejected_while_locked() {
+        let dir = temp_dir("lock");
+        let storage = FileStorage::open(&dir).unwrap();
+        assert!(matches!(
+            FileStorage::open(&dir),
+            Err(SikioError::DatabaseLocked(_))
+        ));
+        drop(storage);
+        assert!(FileStorage::open(&dir).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -17,7 +17,7 @@ impl SecondaryIndex {
     pub fn insert(&mut self, indexed_value: Vec<u8>, primary_key: Vec<u8>) {
         self.entries
             .entry(indexed_value)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(primary_key);
     }
     pub fn remove(&mut self, indexed_value: &[u8], primary_key: &[u8]) -> bool {
@@ -167,3 +167,8 @@ impl IndexRegistry {
         self.indexes.keys().map(|s| s.as_str()).collect()
     }
 }
+impl Default for IndexRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,11 +1,18 @@
+pub mod async_storage;
 pub mod btree;
+pub mod bulk;
 pub mod cache;
+pub mod changelog;
 pub mod compaction;
 pub mod compression;
 pub mod cursor;
 pub mod db;
 
 pub mod error;
+pub mod fault;
+pub mod freelist;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod fs_storage;
 pub mod index;
 pub mod page;
 pub mod range;
@@ -15,6 +22,7 @@ pub mod snapshot;
 pub mod stats;
 pub mod storage;
 pub mod sync;
+pub mod time;
 pub mod transaction;
 pub mod wal;
 use wasm_bindgen::prelude::*;
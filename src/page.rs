@@ -1,15 +1,15 @@
 use crate::error::{Result, SikioError};
-pub const PAGE_SIZE: usize = 4096;
+pub const DEFAULT_PAGE_SIZE: usize = 4096;
+pub const SUPPORTED_PAGE_SIZES: [usize; 4] = [4096, 8192, 16384, 32768];
 pub const PAGE_HEADER_SIZE: usize = 24;
-pub const PAGE_DATA_SIZE: usize = PAGE_SIZE - PAGE_HEADER_SIZE;
 pub const MAX_KEY_SIZE: usize = 1024;
 pub const MAX_VALUE_SIZE: usize = 64 * 1024 * 1024;
-pub const OVERFLOW_THRESHOLD: usize = 1024;
-pub const MAX_KV_SIZE: usize = MAX_KEY_SIZE + OVERFLOW_THRESHOLD;
 pub const PAGE_TYPE_INTERNAL: u8 = 1;
 pub const PAGE_TYPE_LEAF: u8 = 2;
 pub const PAGE_TYPE_OVERFLOW: u8 = 3;
+pub const PAGE_TYPE_FREELIST: u8 = 4;
 pub const PAGE_TYPE_FREE: u8 = 0;
+pub const MAX_PAGE_LSN: u64 = (1 << 56) - 1;
 #[derive(Debug, Clone)]
 pub struct PageHeader {
     pub page_id: u64,
@@ -17,15 +17,17 @@ pub struct PageHeader {
     pub item_count: u16,
     pub free_space_offset: u16,
     pub checksum: u32,
+    pub lsn: u64,
 }
 impl PageHeader {
-    pub fn new(page_id: u64, page_type: u8) -> Self {
+    pub fn new(page_id: u64, page_type: u8, page_size: usize) -> Self {
         PageHeader {
             page_id,
             page_type,
             item_count: 0,
-            free_space_offset: PAGE_DATA_SIZE as u16,
+            free_space_offset: page_data_size(page_size) as u16,
             checksum: 0,
+            lsn: 0,
         }
     }
 }
@@ -35,14 +37,14 @@ pub struct Page {
     pub data: Vec<u8>,
 }
 impl Page {
-    pub fn new(page_id: u64, page_type: u8) -> Self {
+    pub fn new(page_id: u64, page_type: u8, page_size: usize) -> Self {
         Page {
-            header: PageHeader::new(page_id, page_type),
-            data: vec![0u8; PAGE_DATA_SIZE],
+            header: PageHeader::new(page_id, page_type, page_size),
+            data: vec![0u8; page_data_size(page_size)],
         }
     }
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        if bytes.len() != PAGE_SIZE {
+        if validate_page_size(bytes.len()).is_err() {
             return Err(SikioError::PageCorrupted {
                 page_id: 0,
                 reason: format!("Invalid page size: {}", bytes.len()),
@@ -76,6 +78,10 @@ impl Page {
                     reason: "Invalid checksum bytes".into(),
                 }
             })?);
+        let mut lsn_bytes = [0u8; 8];
+        lsn_bytes[0..4].copy_from_slice(&header_bytes[20..24]);
+        lsn_bytes[4..7].copy_from_slice(&header_bytes[13..16]);
+        let lsn = u64::from_le_bytes(lsn_bytes);
         let mut hasher = crc32fast::Hasher::new();
         hasher.update(&bytes[0..16]);
         hasher.update(&[0u8; 4]);
@@ -93,16 +99,23 @@ impl Page {
             item_count,
             free_space_offset,
             checksum: stored_checksum,
+            lsn,
         };
         let data = bytes[PAGE_HEADER_SIZE..].to_vec();
         Ok(Page { header, data })
     }
+    pub fn page_size(&self) -> usize {
+        PAGE_HEADER_SIZE + self.data.len()
+    }
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![0u8; PAGE_SIZE];
+        let mut bytes = vec![0u8; self.page_size()];
         bytes[0..8].copy_from_slice(&self.header.page_id.to_le_bytes());
         bytes[8] = self.header.page_type;
         bytes[9..11].copy_from_slice(&self.header.item_count.to_le_bytes());
         bytes[11..13].copy_from_slice(&self.header.free_space_offset.to_le_bytes());
+        let lsn_bytes = self.header.lsn.min(MAX_PAGE_LSN).to_le_bytes();
+        bytes[13..16].copy_from_slice(&lsn_bytes[4..7]);
+        bytes[20..24].copy_from_slice(&lsn_bytes[0..4]);
         bytes[PAGE_HEADER_SIZE..].copy_from_slice(&self.data);
         let checksum = crc32fast::hash(&bytes);
         bytes[16..20].copy_from_slice(&checksum.to_le_bytes());
@@ -133,27 +146,28 @@ impl CellPointer {
     }
 }
 pub const OVERFLOW_HEADER_SIZE: usize = 24;
-pub const OVERFLOW_DATA_SIZE: usize = PAGE_SIZE - OVERFLOW_HEADER_SIZE;
 #[derive(Debug, Clone)]
 pub struct OverflowPage {
     pub page_id: u64,
     pub next_page: u64,
     pub data_length: u32,
     pub checksum: u32,
+    pub page_size: usize,
     pub data: Vec<u8>,
 }
 impl OverflowPage {
-    pub fn new(page_id: u64) -> Self {
+    pub fn new(page_id: u64, page_size: usize) -> Self {
         OverflowPage {
             page_id,
             next_page: 0,
             data_length: 0,
             checksum: 0,
+            page_size,
             data: Vec::new(),
         }
     }
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        if bytes.len() != PAGE_SIZE {
+        if validate_page_size(bytes.len()).is_err() {
             return Err(SikioError::PageCorrupted {
                 page_id: 0,
                 reason: "Invalid overflow page size".into(),
@@ -189,7 +203,7 @@ impl OverflowPage {
                 reason: "Invalid overflow checksum bytes".into(),
             }
         })?);
-        if OVERFLOW_HEADER_SIZE + data_length as usize > PAGE_SIZE {
+        if OVERFLOW_HEADER_SIZE + data_length as usize > bytes.len() {
             return Err(SikioError::PageCorrupted {
                 page_id,
                 reason: "Overflow data_length exceeds page".into(),
@@ -214,11 +228,12 @@ impl OverflowPage {
             next_page,
             data_length,
             checksum: stored_checksum,
+            page_size: bytes.len(),
             data,
         })
     }
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![0u8; PAGE_SIZE];
+        let mut bytes = vec![0u8; self.page_size];
         bytes[0..8].copy_from_slice(&self.page_id.to_le_bytes());
         bytes[8..16].copy_from_slice(&self.next_page.to_le_bytes());
         bytes[16..20].copy_from_slice(&self.data_length.to_le_bytes());
@@ -231,10 +246,23 @@ impl OverflowPage {
         bytes[20..24].copy_from_slice(&checksum.to_le_bytes());
         bytes
     }
-    pub fn max_data_per_page() -> usize {
-        OVERFLOW_DATA_SIZE
+    pub fn max_data_per_page(page_size: usize) -> usize {
+        page_size - OVERFLOW_HEADER_SIZE
+    }
+}
+pub fn validate_page_size(page_size: usize) -> Result<()> {
+    if SUPPORTED_PAGE_SIZES.contains(&page_size) {
+        Ok(())
+    } else {
+        Err(SikioError::InvalidPageSize(page_size))
     }
 }
+pub fn page_data_size(page_size: usize) -> usize {
+    page_size - PAGE_HEADER_SIZE
+}
+pub fn overflow_threshold(page_size: usize) -> usize {
+    page_size / 4
+}
 pub fn validate_key_value(key: &[u8], value: &[u8]) -> Result<()> {
     if key.len() > MAX_KEY_SIZE {
         return Err(SikioError::KeyTooLarge {
@@ -4,20 +4,21 @@ use crate::compression::decompress;
 use crate::cursor::{cursor_next, cursor_seek, CursorState};
 use crate::error::{Result, SikioError};
 use crate::page::Page;
-use crate::storage::OPFSStorage;
-use crate::page::{OverflowPage, OVERFLOW_DATA_SIZE};
+use crate::storage::StorageBackend;
+use crate::time::now_ms;
+use crate::page::OverflowPage;
 
 const VAL_TYPE_RAW: u8 = 0x00;
 const VAL_TYPE_TTL: u8 = 0x01;
 const OVERFLOW_MARKER_PREFIX: u8 = 0xFF;
 const OVERFLOW_MARKER_SIZE: usize = 13;
 pub struct ReadOnlyDatabase {
-    storage: OPFSStorage,
+    storage: Box<dyn StorageBackend>,
     cache: PageCache,
     root_page_id: u64,
 }
 impl ReadOnlyDatabase {
-    pub fn open(storage: OPFSStorage, root_page_id: u64) -> Self {
+    pub fn open(storage: Box<dyn StorageBackend>, root_page_id: u64) -> Self {
         ReadOnlyDatabase {
             storage,
             cache: PageCache::with_capacity(128),
@@ -59,7 +60,7 @@ impl ReadOnlyDatabase {
             &mut state,
             start,
             self.root_page_id,
-            &self.storage,
+            self.storage.as_ref(),
             &mut self.cache,
         )?;
         while state.valid() && results.len() < limit {
@@ -71,7 +72,7 @@ impl ReadOnlyDatabase {
                     results.push((key.to_vec(), processed));
                 }
             }
-            cursor_next(&mut state, &self.storage, &mut self.cache)?;
+            cursor_next(&mut state, self.storage.as_ref(), &mut self.cache)?;
         }
         Ok(results)
     }
@@ -105,7 +106,7 @@ impl ReadOnlyDatabase {
                     .map_err(|_| SikioError::Corrupted("Invalid overflow length".into()))?,
             ) as usize;
 
-            let data = read_overflow_chain(first_page_id, total_len, &self.storage)?;
+            let data = read_overflow_chain(first_page_id, total_len, self.storage.as_ref())?;
             let wrapped = decompress(&data)
                 .ok_or_else(|| SikioError::Corrupted("Failed to decompress overflow data".into()))?;
             return self.process_value(&wrapped);
@@ -122,7 +123,7 @@ impl ReadOnlyDatabase {
                         .try_into()
                         .map_err(|_| SikioError::Corrupted("Invalid TTL expiry".into()))?,
                 );
-                let now = js_sys::Date::now() as u64;
+                let now = now_ms();
                 if now > expiry {
                     Ok(None)
                 } else {
@@ -134,12 +135,16 @@ impl ReadOnlyDatabase {
     }
 }
 
-fn read_overflow_chain(first_page_id: u64, total_len: usize, storage: &OPFSStorage) -> Result<Vec<u8>> {
+fn read_overflow_chain(
+    first_page_id: u64,
+    total_len: usize,
+    storage: &dyn StorageBackend,
+) -> Result<Vec<u8>> {
     if total_len == 0 {
         return Ok(Vec::new());
     }
 
-    let mut data = Vec::with_capacity(total_len.min(OVERFLOW_DATA_SIZE));
+    let mut data = Vec::with_capacity(total_len);
     let mut current_page = first_page_id;
     while current_page != 0 && data.len() < total_len {
         let bytes = storage.read_page(current_page)?;
@@ -150,16 +155,12 @@ fn read_overflow_chain(first_page_id: u64, total_len: usize, storage: &OPFSStora
     data.truncate(total_len);
     Ok(data)
 }
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum AccessMode {
+    #[default]
     ReadWrite,
     ReadOnly,
 }
-impl Default for AccessMode {
-    fn default() -> Self {
-        AccessMode::ReadWrite
-    }
-}
 pub struct AccessGuard {
     mode: AccessMode,
 }
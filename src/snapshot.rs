@@ -4,7 +4,8 @@ use crate::compression::decompress;
 use crate::cursor::{cursor_next, cursor_seek, CursorState};
 use crate::error::{Result, SikioError};
 use crate::page::{OverflowPage, Page};
-use crate::storage::OPFSStorage;
+use crate::storage::StorageBackend;
+use crate::time::now_ms;
 const VAL_TYPE_RAW: u8 = 0x00;
 const VAL_TYPE_TTL: u8 = 0x01;
 const OVERFLOW_MARKER_PREFIX: u8 = 0xFF;
@@ -16,7 +17,7 @@ impl ReadSnapshot {
     pub fn new(root_page_id: u64) -> Self {
         ReadSnapshot {
             root_page_id,
-            created_at: js_sys::Date::now() as u64,
+            created_at: now_ms(),
         }
     }
     pub fn root_page_id(&self) -> u64 {
@@ -28,7 +29,7 @@ impl ReadSnapshot {
     pub fn get(
         &self,
         key: &[u8],
-        storage: &OPFSStorage,
+        storage: &dyn StorageBackend,
         cache: &mut PageCache,
     ) -> Result<Option<Vec<u8>>> {
         if self.root_page_id == 0 {
@@ -58,7 +59,7 @@ impl ReadSnapshot {
         start_key: &[u8],
         end_key: &[u8],
         limit: usize,
-        storage: &OPFSStorage,
+        storage: &dyn StorageBackend,
         cache: &mut PageCache,
     ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
         let mut results = Vec::new();
@@ -80,7 +81,7 @@ impl ReadSnapshot {
     fn process_stored_value(
         &self,
         stored_value: &[u8],
-        storage: &OPFSStorage,
+        storage: &dyn StorageBackend,
         _cache: &mut PageCache,
     ) -> Result<Option<Vec<u8>>> {
         if stored_value.is_empty() {
@@ -122,7 +123,7 @@ impl ReadSnapshot {
                         .try_into()
                         .map_err(|_| SikioError::Corrupted("Invalid TTL expiry".into()))?,
                 );
-                let now = js_sys::Date::now() as u64;
+                let now = now_ms();
                 if now > expiry {
                     return Ok(None);
                 }
@@ -132,7 +133,11 @@ impl ReadSnapshot {
         }
     }
 }
-fn load_node(page_id: u64, storage: &OPFSStorage, cache: &mut PageCache) -> Result<BTreeNode> {
+fn load_node(
+    page_id: u64,
+    storage: &dyn StorageBackend,
+    cache: &mut PageCache,
+) -> Result<BTreeNode> {
     if let Some(page) = cache.get(page_id) {
         return BTreeNode::from_page(page);
     }
@@ -145,7 +150,7 @@ fn load_node(page_id: u64, storage: &OPFSStorage, cache: &mut PageCache) -> Resu
 fn read_overflow_chain(
     first_page_id: u64,
     total_len: usize,
-    storage: &OPFSStorage,
+    storage: &dyn StorageBackend,
 ) -> Result<Vec<u8>> {
     let mut data = Vec::with_capacity(total_len);
     let mut current_page = first_page_id;
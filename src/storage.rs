@@ -1,17 +1,98 @@
 use crate::error::{Result, SikioError};
-use crate::page::PAGE_SIZE;
+use crate::page::DEFAULT_PAGE_SIZE;
 use js_sys::{Function, Object, Reflect, Uint8Array};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{FileSystemDirectoryHandle, FileSystemFileHandle};
+pub trait StorageBackend {
+    fn page_size(&self) -> usize;
+    fn read_data(&self, offset: u64, length: usize) -> Result<Vec<u8>>;
+    fn read_page(&self, page_id: u64) -> Result<Vec<u8>> {
+        let page_size = self.page_size() as u64;
+        let offset = page_id * page_size;
+        if offset + page_size > self.data_size() {
+            return Err(SikioError::IoError(format!(
+                "Page {} beyond file size",
+                page_id
+            )));
+        }
+        self.read_data(offset, page_size as usize)
+    }
+    fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()>;
+    fn append_wal(&mut self, data: &[u8]) -> Result<u64>;
+    fn read_wal(&self, offset: u64, length: usize) -> Result<Vec<u8>>;
+    fn flush_data(&mut self) -> Result<()>;
+    fn flush_wal(&mut self) -> Result<()>;
+    fn truncate_wal_to(&mut self, length: u64) -> Result<()>;
+    fn truncate_wal(&mut self) -> Result<()> {
+        self.truncate_wal_to(0)
+    }
+    fn truncate_data(&mut self, length: u64) -> Result<()>;
+    fn data_size(&self) -> u64;
+    fn data_page_count(&self) -> u64 {
+        self.data_size() / self.page_size() as u64
+    }
+    fn wal_size(&self) -> u64;
+    fn archive_wal(&mut self, _segment: u64) -> Result<()> {
+        Err(archive_unsupported())
+    }
+    fn archived_wal_segments(&self) -> Result<Vec<u64>> {
+        Err(archive_unsupported())
+    }
+    fn archived_wal_size(&self, _segment: u64) -> Result<u64> {
+        Err(archive_unsupported())
+    }
+    fn read_archived_wal(&self, _segment: u64, _offset: u64, _length: usize) -> Result<Vec<u8>> {
+        Err(archive_unsupported())
+    }
+    fn remove_archived_wal(&mut self, _segment: u64) -> Result<()> {
+        Err(archive_unsupported())
+    }
+    fn begin_op(&mut self) {}
+    fn undo_op(&mut self) {}
+    fn end_op(&mut self) {}
+    fn needs_load(&self) -> bool {
+        false
+    }
+}
+fn archive_unsupported() -> SikioError {
+    SikioError::IoError("WAL archiving is not supported by this storage".into())
+}
+const ARCHIVE_HEADER_SIZE: u64 = 8;
+// Sync access handles can only be created asynchronously, so archived WAL
+// segments live in a fixed set of slot files opened up front. A slot holds the
+// segment number followed by the WAL bytes; number 0 marks it free.
+struct ArchiveSlot {
+    handle: JsValue,
+    segment: Option<u64>,
+    size: u64,
+}
 pub struct OPFSStorage {
     data_handle: JsValue,
     wal_handle: JsValue,
+    page_size: usize,
     data_size: u64,
     wal_size: u64,
+    archive: Vec<ArchiveSlot>,
 }
 impl OPFSStorage {
     pub async fn open(db_name: &str) -> std::result::Result<Self, JsValue> {
+        Self::open_with_page_size(db_name, DEFAULT_PAGE_SIZE).await
+    }
+    pub async fn open_with_page_size(
+        db_name: &str,
+        page_size: usize,
+    ) -> std::result::Result<Self, JsValue> {
+        Self::open_with_archive(db_name, page_size, 0).await
+    }
+    pub async fn open_with_archive(
+        db_name: &str,
+        page_size: usize,
+        archive_slots: usize,
+    ) -> std::result::Result<Self, JsValue> {
         let root = get_opfs_root().await?;
         let db_dir = get_or_create_directory(&root, db_name).await?;
         let data_file = get_or_create_file(&db_dir, "data.sdb").await?;
@@ -20,93 +101,335 @@ impl OPFSStorage {
         let wal_handle = create_sync_handle(&wal_file).await?;
         let data_size = call_method_number(&data_handle, "getSize", &[])? as u64;
         let wal_size = call_method_number(&wal_handle, "getSize", &[])? as u64;
+        let mut archive = Vec::new();
+        loop {
+            let name = format!("archive.{:03}.sdb", archive.len());
+            let file = match archive.len() < archive_slots {
+                true => get_or_create_file(&db_dir, &name).await?,
+                false => match get_file(&db_dir, &name).await? {
+                    Some(file) => file,
+                    None => break,
+                },
+            };
+            let handle = create_sync_handle(&file).await?;
+            let size = call_method_number(&handle, "getSize", &[])? as u64;
+            let mut segment = None;
+            if size > ARCHIVE_HEADER_SIZE {
+                let header = read_handle(&handle, 0, ARCHIVE_HEADER_SIZE as usize)
+                    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+                let number = u64::from_le_bytes(header.try_into().unwrap_or_default());
+                segment = (number != 0).then_some(number);
+            }
+            archive.push(ArchiveSlot {
+                handle,
+                segment,
+                size,
+            });
+        }
         Ok(OPFSStorage {
             data_handle,
             wal_handle,
+            page_size,
             data_size,
             wal_size,
+            archive,
         })
     }
-    pub fn read_page(&self, page_id: u64) -> Result<Vec<u8>> {
-        let offset = page_id * PAGE_SIZE as u64;
-        if offset + PAGE_SIZE as u64 > self.data_size {
-            return Err(SikioError::IoError(format!(
-                "Page {} beyond file size",
-                page_id
-            )));
+    pub fn close(self) {
+        drop(self);
+    }
+    fn archive_slot(&self, segment: u64) -> Result<&ArchiveSlot> {
+        self.archive
+            .iter()
+            .find(|slot| slot.segment == Some(segment))
+            .ok_or_else(|| SikioError::IoError(format!("WAL segment {} not found", segment)))
+    }
+}
+impl StorageBackend for OPFSStorage {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+    fn read_data(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+        if offset + length as u64 > self.data_size {
+            return Err(SikioError::IoError("Data read beyond size".into()));
         }
-        let array = Uint8Array::new_with_length(PAGE_SIZE as u32);
+        let array = Uint8Array::new_with_length(length as u32);
         let options = create_at_options(offset);
         call_method(&self.data_handle, "read", &[array.clone().into(), options])
-            .map_err(|e| SikioError::IoError(format!("{:?}", e)))?;
-        let mut buffer = vec![0u8; PAGE_SIZE];
+            .map_err(storage_error)?;
+        let mut buffer = vec![0u8; length];
         array.copy_to(&mut buffer);
         Ok(buffer)
     }
-    pub fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()> {
-        if data.len() != PAGE_SIZE {
+    fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()> {
+        if data.len() != self.page_size {
             return Err(SikioError::IoError(format!(
                 "Invalid page size: {}",
                 data.len()
             )));
         }
-        let offset = page_id * PAGE_SIZE as u64;
+        let offset = page_id * self.page_size as u64;
         let array = Uint8Array::from(data);
         let options = create_at_options(offset);
-        call_method(&self.data_handle, "write", &[array.into(), options])
-            .map_err(|e| SikioError::IoError(format!("{:?}", e)))?;
-        let new_end = offset + PAGE_SIZE as u64;
+        call_method(&self.data_handle, "write", &[array.into(), options]).map_err(storage_error)?;
+        let new_end = offset + self.page_size as u64;
         if new_end > self.data_size {
             self.data_size = new_end;
         }
         Ok(())
     }
-    pub fn append_wal(&mut self, data: &[u8]) -> Result<u64> {
+    fn append_wal(&mut self, data: &[u8]) -> Result<u64> {
         let offset = self.wal_size;
         let array = Uint8Array::from(data);
         let options = create_at_options(offset);
-        call_method(&self.wal_handle, "write", &[array.into(), options])
-            .map_err(|e| SikioError::IoError(format!("{:?}", e)))?;
+        call_method(&self.wal_handle, "write", &[array.into(), options]).map_err(storage_error)?;
         self.wal_size += data.len() as u64;
         Ok(offset)
     }
-    pub fn read_wal(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+    fn read_wal(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
         if offset + length as u64 > self.wal_size {
             return Err(SikioError::IoError("WAL read beyond size".into()));
         }
         let array = Uint8Array::new_with_length(length as u32);
         let options = create_at_options(offset);
         call_method(&self.wal_handle, "read", &[array.clone().into(), options])
-            .map_err(|e| SikioError::IoError(format!("{:?}", e)))?;
+            .map_err(storage_error)?;
         let mut buffer = vec![0u8; length];
         array.copy_to(&mut buffer);
         Ok(buffer)
     }
-    pub fn flush_data(&self) -> Result<()> {
-        call_method(&self.data_handle, "flush", &[])
-            .map_err(|e| SikioError::IoError(format!("{:?}", e)))?;
+    fn flush_data(&mut self) -> Result<()> {
+        call_method(&self.data_handle, "flush", &[]).map_err(storage_error)?;
         Ok(())
     }
-    pub fn flush_wal(&self) -> Result<()> {
-        call_method(&self.wal_handle, "flush", &[])
-            .map_err(|e| SikioError::IoError(format!("{:?}", e)))?;
+    fn flush_wal(&mut self) -> Result<()> {
+        call_method(&self.wal_handle, "flush", &[]).map_err(storage_error)?;
         Ok(())
     }
-    pub fn truncate_wal(&mut self) -> Result<()> {
-        call_method(&self.wal_handle, "truncate", &[JsValue::from_f64(0.0)])
-            .map_err(|e| SikioError::IoError(format!("{:?}", e)))?;
-        self.wal_size = 0;
+    fn truncate_wal_to(&mut self, length: u64) -> Result<()> {
+        let length = length.min(self.wal_size);
+        call_method(
+            &self.wal_handle,
+            "truncate",
+            &[JsValue::from_f64(length as f64)],
+        )
+        .map_err(storage_error)?;
+        self.wal_size = length;
         Ok(())
     }
-    pub fn data_page_count(&self) -> u64 {
-        self.data_size / PAGE_SIZE as u64
+    fn truncate_data(&mut self, length: u64) -> Result<()> {
+        let length = length.min(self.data_size);
+        call_method(
+            &self.data_handle,
+            "truncate",
+            &[JsValue::from_f64(length as f64)],
+        )
+        .map_err(storage_error)?;
+        call_method(&self.data_handle, "flush", &[]).map_err(storage_error)?;
+        self.data_size = length;
+        Ok(())
     }
-    pub fn wal_size(&self) -> u64 {
+    fn data_size(&self) -> u64 {
+        self.data_size
+    }
+    fn wal_size(&self) -> u64 {
         self.wal_size
     }
-    pub fn close(self) {
+    fn archive_wal(&mut self, segment: u64) -> Result<()> {
+        let wal = self.read_wal(0, self.wal_size as usize)?;
+        let slot = self
+            .archive
+            .iter_mut()
+            .find(|slot| slot.segment.is_none())
+            .ok_or_else(|| SikioError::IoError("No free WAL archive slot".into()))?;
+        call_method(&slot.handle, "truncate", &[JsValue::from_f64(0.0)]).map_err(storage_error)?;
+        write_handle(&slot.handle, ARCHIVE_HEADER_SIZE, &wal)?;
+        call_method(&slot.handle, "flush", &[]).map_err(storage_error)?;
+        write_handle(&slot.handle, 0, &segment.to_le_bytes())?;
+        call_method(&slot.handle, "flush", &[]).map_err(storage_error)?;
+        slot.segment = Some(segment);
+        slot.size = ARCHIVE_HEADER_SIZE + wal.len() as u64;
+        Ok(())
+    }
+    fn archived_wal_segments(&self) -> Result<Vec<u64>> {
+        let mut segments: Vec<u64> = self
+            .archive
+            .iter()
+            .filter_map(|slot| slot.segment)
+            .collect();
+        segments.sort_unstable();
+        Ok(segments)
+    }
+    fn archived_wal_size(&self, segment: u64) -> Result<u64> {
+        Ok(self.archive_slot(segment)?.size - ARCHIVE_HEADER_SIZE)
+    }
+    fn read_archived_wal(&self, segment: u64, offset: u64, length: usize) -> Result<Vec<u8>> {
+        if offset + length as u64 > self.archived_wal_size(segment)? {
+            return Err(SikioError::IoError("WAL read beyond size".into()));
+        }
+        let slot = self.archive_slot(segment)?;
+        read_handle(&slot.handle, ARCHIVE_HEADER_SIZE + offset, length)
+    }
+    fn remove_archived_wal(&mut self, segment: u64) -> Result<()> {
+        let slot = self
+            .archive
+            .iter_mut()
+            .find(|slot| slot.segment == Some(segment))
+            .ok_or_else(|| SikioError::IoError(format!("WAL segment {} not found", segment)))?;
+        call_method(&slot.handle, "truncate", &[JsValue::from_f64(0.0)]).map_err(storage_error)?;
+        call_method(&slot.handle, "flush", &[]).map_err(storage_error)?;
+        slot.segment = None;
+        slot.size = 0;
+        Ok(())
+    }
+}
+impl Drop for OPFSStorage {
+    fn drop(&mut self) {
         let _ = call_method(&self.data_handle, "close", &[]);
         let _ = call_method(&self.wal_handle, "close", &[]);
+        for slot in &self.archive {
+            let _ = call_method(&slot.handle, "close", &[]);
+        }
+    }
+}
+#[derive(Default)]
+struct MemoryFiles {
+    data: Vec<u8>,
+    wal: Vec<u8>,
+    archive: BTreeMap<u64, Vec<u8>>,
+}
+#[derive(Clone)]
+pub struct MemoryStorage {
+    files: Rc<RefCell<MemoryFiles>>,
+    page_size: usize,
+}
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::with_page_size(DEFAULT_PAGE_SIZE)
+    }
+    pub fn with_page_size(page_size: usize) -> Self {
+        Self::with_contents(page_size, Vec::new(), Vec::new())
+    }
+    pub fn with_contents(page_size: usize, data: Vec<u8>, wal: Vec<u8>) -> Self {
+        MemoryStorage {
+            files: Rc::new(RefCell::new(MemoryFiles {
+                data,
+                wal,
+                archive: BTreeMap::new(),
+            })),
+            page_size,
+        }
+    }
+    pub fn data_bytes(&self) -> Vec<u8> {
+        self.files.borrow().data.clone()
+    }
+    pub fn wal_bytes(&self) -> Vec<u8> {
+        self.files.borrow().wal.clone()
+    }
+}
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl StorageBackend for MemoryStorage {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+    fn read_data(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+        let files = self.files.borrow();
+        let start = offset as usize;
+        if start + length > files.data.len() {
+            return Err(SikioError::IoError("Data read beyond size".into()));
+        }
+        Ok(files.data[start..start + length].to_vec())
+    }
+    fn write_page(&mut self, page_id: u64, data: &[u8]) -> Result<()> {
+        if data.len() != self.page_size {
+            return Err(SikioError::IoError(format!(
+                "Invalid page size: {}",
+                data.len()
+            )));
+        }
+        let mut files = self.files.borrow_mut();
+        let offset = page_id as usize * self.page_size;
+        if files.data.len() < offset + self.page_size {
+            files.data.resize(offset + self.page_size, 0);
+        }
+        files.data[offset..offset + self.page_size].copy_from_slice(data);
+        Ok(())
+    }
+    fn append_wal(&mut self, data: &[u8]) -> Result<u64> {
+        let mut files = self.files.borrow_mut();
+        let offset = files.wal.len() as u64;
+        files.wal.extend_from_slice(data);
+        Ok(offset)
+    }
+    fn read_wal(&self, offset: u64, length: usize) -> Result<Vec<u8>> {
+        let files = self.files.borrow();
+        let start = offset as usize;
+        if start + length > files.wal.len() {
+            return Err(SikioError::IoError("WAL read beyond size".into()));
+        }
+        Ok(files.wal[start..start + length].to_vec())
+    }
+    fn flush_data(&mut self) -> Result<()> {
+        Ok(())
+    }
+    fn flush_wal(&mut self) -> Result<()> {
+        Ok(())
+    }
+    fn truncate_wal_to(&mut self, length: u64) -> Result<()> {
+        self.files.borrow_mut().wal.truncate(length as usize);
+        Ok(())
+    }
+    fn truncate_data(&mut self, length: u64) -> Result<()> {
+        self.files.borrow_mut().data.truncate(length as usize);
+        Ok(())
+    }
+    fn data_size(&self) -> u64 {
+        self.files.borrow().data.len() as u64
+    }
+    fn wal_size(&self) -> u64 {
+        self.files.borrow().wal.len() as u64
+    }
+    fn archive_wal(&mut self, segment: u64) -> Result<()> {
+        let mut files = self.files.borrow_mut();
+        let wal = files.wal.clone();
+        files.archive.insert(segment, wal);
+        Ok(())
+    }
+    fn archived_wal_segments(&self) -> Result<Vec<u64>> {
+        Ok(self.files.borrow().archive.keys().copied().collect())
+    }
+    fn archived_wal_size(&self, segment: u64) -> Result<u64> {
+        self.files
+            .borrow()
+            .archive
+            .get(&segment)
+            .map(|wal| wal.len() as u64)
+            .ok_or_else(|| SikioError::IoError(format!("WAL segment {} not found", segment)))
+    }
+    fn read_archived_wal(&self, segment: u64, offset: u64, length: usize) -> Result<Vec<u8>> {
+        let start = offset as usize;
+        if start + length > self.archived_wal_size(segment)? as usize {
+            return Err(SikioError::IoError("WAL read beyond size".into()));
+        }
+        Ok(self.files.borrow().archive[&segment][start..start + length].to_vec())
+    }
+    fn remove_archived_wal(&mut self, segment: u64) -> Result<()> {
+        self.files.borrow_mut().archive.remove(&segment);
+        Ok(())
+    }
+}
+pub(crate) fn storage_error(e: JsValue) -> SikioError {
+    let name = Reflect::get(&e, &"name".into())
+        .ok()
+        .and_then(|n| n.as_string());
+    if name.as_deref() == Some("QuotaExceededError") {
+        SikioError::QuotaExceeded
+    } else {
+        SikioError::IoError(format!("{:?}", e))
     }
 }
 fn create_at_options(offset: u64) -> JsValue {
@@ -114,7 +437,21 @@ fn create_at_options(offset: u64) -> JsValue {
     let _ = Reflect::set(&options, &"at".into(), &JsValue::from_f64(offset as f64));
     options.into()
 }
-fn call_method(
+fn read_handle(handle: &JsValue, offset: u64, length: usize) -> Result<Vec<u8>> {
+    let array = Uint8Array::new_with_length(length as u32);
+    let options = create_at_options(offset);
+    call_method(handle, "read", &[array.clone().into(), options]).map_err(storage_error)?;
+    let mut buffer = vec![0u8; length];
+    array.copy_to(&mut buffer);
+    Ok(buffer)
+}
+fn write_handle(handle: &JsValue, offset: u64, data: &[u8]) -> Result<()> {
+    let array = Uint8Array::from(data);
+    let options = create_at_options(offset);
+    call_method(handle, "write", &[array.into(), options]).map_err(storage_error)?;
+    Ok(())
+}
+pub(crate) fn call_method(
     obj: &JsValue,
     method: &str,
     args: &[JsValue],
@@ -137,7 +474,21 @@ fn call_method_number(
         .as_f64()
         .ok_or_else(|| JsValue::from_str("Expected number"))
 }
-async fn get_opfs_root() -> std::result::Result<FileSystemDirectoryHandle, JsValue> {
+pub(crate) async fn storage_estimate() -> std::result::Result<(f64, f64), JsValue> {
+    let global = js_sys::global();
+    let navigator = Reflect::get(&global, &"navigator".into())?;
+    let storage = Reflect::get(&navigator, &"storage".into())?;
+    let promise = call_method(&storage, "estimate", &[])?;
+    let estimate = JsFuture::from(js_sys::Promise::from(promise)).await?;
+    let usage = Reflect::get(&estimate, &"usage".into())?
+        .as_f64()
+        .unwrap_or(0.0);
+    let quota = Reflect::get(&estimate, &"quota".into())?
+        .as_f64()
+        .unwrap_or(0.0);
+    Ok((usage, quota))
+}
+pub(crate) async fn get_opfs_root() -> std::result::Result<FileSystemDirectoryHandle, JsValue> {
     let global = js_sys::global();
     let navigator = Reflect::get(&global, &"navigator".into())?;
     let storage = Reflect::get(&navigator, &"storage".into())?;
@@ -147,7 +498,7 @@ async fn get_opfs_root() -> std::result::Result<FileSystemDirectoryHandle, JsVal
     let result = JsFuture::from(js_sys::Promise::from(promise)).await?;
     Ok(result.unchecked_into())
 }
-async fn get_or_create_directory(
+pub(crate) async fn get_or_create_directory(
     parent: &FileSystemDirectoryHandle,
     name: &str,
 ) -> std::result::Result<FileSystemDirectoryHandle, JsValue> {
@@ -162,7 +513,7 @@ async fn get_or_create_directory(
     let result = JsFuture::from(js_sys::Promise::from(promise)).await?;
     Ok(result.unchecked_into())
 }
-async fn get_or_create_file(
+pub(crate) async fn get_or_create_file(
     parent: &FileSystemDirectoryHandle,
     name: &str,
 ) -> std::result::Result<FileSystemFileHandle, JsValue> {
@@ -177,6 +528,26 @@ async fn get_or_create_file(
     let result = JsFuture::from(js_sys::Promise::from(promise)).await?;
     Ok(result.unchecked_into())
 }
+async fn get_file(
+    parent: &FileSystemDirectoryHandle,
+    name: &str,
+) -> std::result::Result<Option<FileSystemFileHandle>, JsValue> {
+    let func = Reflect::get(parent, &"getFileHandle".into())?;
+    let func: Function = func.dyn_into()?;
+    let args = js_sys::Array::new();
+    args.push(&JsValue::from_str(name));
+    let promise = Reflect::apply(&func, parent, &args)?;
+    match JsFuture::from(js_sys::Promise::from(promise)).await {
+        Ok(file) => Ok(Some(file.unchecked_into())),
+        Err(e) => {
+            let name = Reflect::get(&e, &"name".into())?.as_string();
+            match name.as_deref() {
+                Some("NotFoundError") => Ok(None),
+                _ => Err(e),
+            }
+        }
+    }
+}
 async fn create_sync_handle(file: &FileSystemFileHandle) -> std::result::Result<JsValue, JsValue> {
     let func = Reflect::get(file, &"createSyncAccessHandle".into())?;
     let func: Function = func.dyn_into()?;
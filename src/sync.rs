@@ -1,4 +1,5 @@
 use crate::error::{Result, SikioError};
+use crate::time::now_ms;
 const LWW_HEADER_SIZE: usize = 12;
 pub struct HybridLogicalClock {
     logical: u64,
@@ -7,17 +8,17 @@ pub struct HybridLogicalClock {
 impl HybridLogicalClock {
     pub fn new(node_id: u32) -> Self {
         HybridLogicalClock {
-            logical: js_sys::Date::now() as u64,
+            logical: now_ms(),
             node_id,
         }
     }
     pub fn tick(&mut self) -> u64 {
-        let now = js_sys::Date::now() as u64;
+        let now = now_ms();
         self.logical = self.logical.max(now) + 1;
         self.logical
     }
     pub fn update(&mut self, received: u64) {
-        let now = js_sys::Date::now() as u64;
+        let now = now_ms();
         self.logical = self.logical.max(now).max(received) + 1;
     }
     pub fn now(&self) -> u64 {
@@ -147,6 +148,11 @@ impl SyncDelta {
         Ok(SyncDelta { entries })
     }
 }
+impl Default for SyncDelta {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 pub fn merge_lww(local: Option<&LWWValue>, remote: &LWWValue) -> bool {
     match local {
         Some(l) => remote.wins_over(l),
@@ -3,8 +3,11 @@ use crate::cache::PageCache;
 use crate::compression::decompress;
 use crate::error::{Result, SikioError};
 use crate::page::{validate_key_value, OverflowPage, Page};
-use crate::storage::OPFSStorage;
+use crate::storage::StorageBackend;
+use crate::time::now_ms;
 use crate::wal::WalEntry;
+use std::collections::BTreeMap;
+use std::ops::Bound;
 
 const VAL_TYPE_RAW: u8 = 0x00;
 const OVERFLOW_MARKER_PREFIX: u8 = 0xFF;
@@ -15,8 +18,27 @@ pub enum TransactionOp {
     Delete { key: Vec<u8> },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Savepoint {
+    depth: usize,
+    ops: usize,
+    reads: usize,
+    ranges: usize,
+}
+
+impl Savepoint {
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
 pub struct WriteTransaction {
     ops: Vec<TransactionOp>,
+    pending: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    reads: BTreeMap<Vec<u8>, u64>,
+    read_order: Vec<Vec<u8>>,
+    ranges: Vec<(Vec<u8>, Vec<u8>, u64)>,
+    savepoints: Vec<Savepoint>,
     wal_buffer: Vec<u8>,
     sequence_start: u64,
     committed: bool,
@@ -27,6 +49,11 @@ impl WriteTransaction {
     pub fn new(current_sequence: u64) -> Self {
         WriteTransaction {
             ops: Vec::new(),
+            pending: BTreeMap::new(),
+            reads: BTreeMap::new(),
+            read_order: Vec::new(),
+            ranges: Vec::new(),
+            savepoints: Vec::new(),
             wal_buffer: Vec::with_capacity(4096),
             sequence_start: current_sequence,
             committed: false,
@@ -41,6 +68,7 @@ impl WriteTransaction {
         validate_key_value(&key, &value)?;
 
         let wrapped = wrap_raw_value(&value);
+        self.pending.insert(key.clone(), Some(wrapped.clone()));
         self.ops.push(TransactionOp::Put {
             key,
             value: wrapped,
@@ -52,10 +80,162 @@ impl WriteTransaction {
         if self.committed || self.aborted {
             return Err(SikioError::Corrupted("Transaction not active".into()));
         }
+        self.pending.insert(key.clone(), None);
         self.ops.push(TransactionOp::Delete { key });
         Ok(())
     }
 
+    pub fn savepoint(&mut self) -> Result<Savepoint> {
+        if !self.is_active() {
+            return Err(SikioError::Corrupted("Transaction not active".into()));
+        }
+        let savepoint = Savepoint {
+            depth: self.savepoints.len(),
+            ops: self.ops.len(),
+            reads: self.read_order.len(),
+            ranges: self.ranges.len(),
+        };
+        self.savepoints.push(savepoint);
+        Ok(savepoint)
+    }
+
+    pub fn rollback_to(&mut self, savepoint: Savepoint) -> Result<()> {
+        self.check_savepoint(savepoint)?;
+        self.savepoints.truncate(savepoint.depth + 1);
+        self.ops.truncate(savepoint.ops);
+        for key in self.read_order.drain(savepoint.reads..) {
+            self.reads.remove(&key);
+        }
+        self.ranges.truncate(savepoint.ranges);
+        self.pending.clear();
+        for op in &self.ops {
+            match op {
+                TransactionOp::Put { key, value } => {
+                    self.pending.insert(key.clone(), Some(value.clone()));
+                }
+                TransactionOp::Delete { key } => {
+                    self.pending.insert(key.clone(), None);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn release(&mut self, savepoint: Savepoint) -> Result<()> {
+        self.check_savepoint(savepoint)?;
+        self.savepoints.truncate(savepoint.depth);
+        Ok(())
+    }
+
+    pub(crate) fn savepoint_at(&self, depth: usize) -> Option<Savepoint> {
+        self.savepoints.get(depth).copied()
+    }
+
+    fn check_savepoint(&self, savepoint: Savepoint) -> Result<()> {
+        if !self.is_active() {
+            return Err(SikioError::Corrupted("Transaction not active".into()));
+        }
+        if self.savepoint_at(savepoint.depth) != Some(savepoint) {
+            return Err(SikioError::Corrupted("Savepoint not active".into()));
+        }
+        Ok(())
+    }
+
+    pub fn record_read(&mut self, key: &[u8], version: u64) {
+        if !self.pending.contains_key(key) && !self.reads.contains_key(key) {
+            self.reads.insert(key.to_vec(), version);
+            self.read_order.push(key.to_vec());
+        }
+    }
+
+    pub(crate) fn reads(&self) -> impl Iterator<Item = (&[u8], u64)> {
+        self.reads
+            .iter()
+            .map(|(key, version)| (key.as_slice(), *version))
+    }
+
+    pub fn record_range(&mut self, start_key: &[u8], end_key: &[u8], version: u64) {
+        self.ranges
+            .push((start_key.to_vec(), end_key.to_vec(), version));
+    }
+
+    pub(crate) fn ranges(&self) -> impl Iterator<Item = (&[u8], &[u8], u64)> {
+        self.ranges
+            .iter()
+            .map(|(start, end, version)| (start.as_slice(), end.as_slice(), *version))
+    }
+
+    pub fn get(
+        &self,
+        key: &[u8],
+        root_page_id: u64,
+        storage: &dyn StorageBackend,
+        cache: &mut PageCache,
+    ) -> Result<Option<Vec<u8>>> {
+        match self.pending.get(key) {
+            Some(Some(wrapped)) => extract_value(wrapped),
+            Some(None) => Ok(None),
+            None => ReadTransaction::new(root_page_id).get(key, storage, cache),
+        }
+    }
+
+    pub fn scan(
+        &self,
+        start_key: &[u8],
+        end_key: &[u8],
+        limit: usize,
+        root_page_id: u64,
+        storage: &dyn StorageBackend,
+        cache: &mut PageCache,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        use crate::cursor::{cursor_next, cursor_seek, CursorState};
+
+        let mut results = Vec::new();
+        if start_key > end_key {
+            return Ok(results);
+        }
+
+        let mut pending = self
+            .pending
+            .range::<[u8], _>((Bound::Included(start_key), Bound::Included(end_key)))
+            .peekable();
+        let mut state = CursorState::new();
+        cursor_seek(&mut state, start_key, root_page_id, storage, cache)?;
+
+        while results.len() < limit {
+            let committed = match (state.key(), state.value()) {
+                (Some(key), Some(value)) if state.valid() && key <= end_key => Some((key, value)),
+                _ => None,
+            };
+            let take_pending = match (pending.peek(), committed) {
+                (None, None) => break,
+                (Some((pending_key, _)), Some((key, _))) => pending_key.as_slice() <= key,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+            };
+
+            if take_pending {
+                if let Some((key, wrapped)) = pending.next() {
+                    if committed.is_some_and(|(committed_key, _)| committed_key == key.as_slice()) {
+                        cursor_next(&mut state, storage, cache)?;
+                    }
+                    if let Some(wrapped) = wrapped {
+                        if let Some(value) = extract_value(wrapped)? {
+                            results.push((key.clone(), value));
+                        }
+                    }
+                }
+            } else if let Some((key, value)) = committed {
+                if let Some(processed) = process_stored_value(value, storage)? {
+                    results.push((key.to_vec(), processed));
+                }
+                cursor_next(&mut state, storage, cache)?;
+            }
+        }
+
+        Ok(results)
+    }
+
     pub fn is_active(&self) -> bool {
         !self.committed && !self.aborted
     }
@@ -89,17 +269,35 @@ impl WriteTransaction {
         sequence
     }
 
+    pub(crate) fn ops(&self) -> &[TransactionOp] {
+        &self.ops
+    }
+
+    pub(crate) fn retain_ops(&mut self, keep: &[bool]) {
+        let mut keep = keep.iter();
+        self.ops.retain(|_| keep.next().copied().unwrap_or(true));
+    }
+
     pub(crate) fn wal_bytes(&self) -> &[u8] {
         &self.wal_buffer
     }
 
     pub(crate) fn take_ops(&mut self) -> Vec<TransactionOp> {
         self.committed = true;
+        self.pending.clear();
+        self.reads.clear();
+        self.read_order.clear();
+        self.ranges.clear();
         std::mem::take(&mut self.ops)
     }
 
     pub fn abort(&mut self) {
         self.ops.clear();
+        self.pending.clear();
+        self.reads.clear();
+        self.read_order.clear();
+        self.ranges.clear();
+        self.savepoints.clear();
         self.wal_buffer.clear();
         self.aborted = true;
     }
@@ -122,7 +320,7 @@ impl ReadTransaction {
     pub fn new(root_page_id: u64) -> Self {
         ReadTransaction {
             root_page_id,
-            created_at: js_sys::Date::now() as u64,
+            created_at: now_ms(),
         }
     }
 
@@ -137,7 +335,7 @@ impl ReadTransaction {
     pub fn get(
         &self,
         key: &[u8],
-        storage: &OPFSStorage,
+        storage: &dyn StorageBackend,
         cache: &mut PageCache,
     ) -> Result<Option<Vec<u8>>> {
         if self.root_page_id == 0 {
@@ -156,10 +354,14 @@ impl ReadTransaction {
                     return process_stored_value(stored_value, storage);
                 }
                 return Ok(None);
-            } else if pos < node.children.len() {
-                current_page_id = node.children[pos];
-            } else {
-                return Ok(None);
+            }
+            let child = match node.keys.get(pos) {
+                Some(separator) if separator.as_slice() == key => pos + 1,
+                _ => pos,
+            };
+            match node.children.get(child) {
+                Some(&child_id) => current_page_id = child_id,
+                None => return Ok(None),
             }
         }
     }
@@ -169,7 +371,7 @@ impl ReadTransaction {
         start_key: &[u8],
         end_key: &[u8],
         limit: usize,
-        storage: &OPFSStorage,
+        storage: &dyn StorageBackend,
         cache: &mut PageCache,
     ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
         use crate::cursor::{cursor_next, cursor_seek, CursorState};
@@ -202,7 +404,11 @@ fn wrap_raw_value(value: &[u8]) -> Vec<u8> {
     wrapped
 }
 
-fn load_node(page_id: u64, storage: &OPFSStorage, cache: &mut PageCache) -> Result<BTreeNode> {
+fn load_node(
+    page_id: u64,
+    storage: &dyn StorageBackend,
+    cache: &mut PageCache,
+) -> Result<BTreeNode> {
     if let Some(page) = cache.get(page_id) {
         return BTreeNode::from_page(page);
     }
@@ -213,7 +419,10 @@ fn load_node(page_id: u64, storage: &OPFSStorage, cache: &mut PageCache) -> Resu
     Ok(node)
 }
 
-fn process_stored_value(stored_value: &[u8], storage: &OPFSStorage) -> Result<Option<Vec<u8>>> {
+fn process_stored_value(
+    stored_value: &[u8],
+    storage: &dyn StorageBackend,
+) -> Result<Option<Vec<u8>>> {
     if stored_value.is_empty() {
         return Ok(None);
     }
@@ -260,7 +469,7 @@ fn extract_value(data: &[u8]) -> Result<Option<Vec<u8>>> {
                     .try_into()
                     .map_err(|_| SikioError::Corrupted("Invalid TTL expiry".into()))?,
             );
-            let now = js_sys::Date::now() as u64;
+            let now = now_ms();
             if now > expiry {
                 return Ok(None);
             }
@@ -273,7 +482,7 @@ fn extract_value(data: &[u8]) -> Result<Option<Vec<u8>>> {
 fn read_overflow_chain(
     first_page_id: u64,
     total_len: usize,
-    storage: &OPFSStorage,
+    storage: &dyn StorageBackend,
 ) -> Result<Vec<u8>> {
     let mut data = Vec::with_capacity(total_len);
     let mut current_page = first_page_id;
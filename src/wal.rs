@@ -1,5 +1,10 @@
 use crate::error::{Result, SikioError};
+use crate::storage::StorageBackend;
+use crate::time::now_ms;
 pub const WAL_ENTRY_HEADER_SIZE: usize = 24;
+const COMMIT_TIME_SIZE: usize = 8;
+const WAL_READ_CHUNK_SIZE: usize = 1024 * 1024;
+pub const RECOVERY_REPORT_MAX_ENTRIES: usize = 1024;
 const SEQUENCE_START: usize = 0;
 const SEQUENCE_END: usize = 8;
 const OPERATION_OFFSET: usize = 8;
@@ -15,6 +20,7 @@ pub enum WalOperation {
     Delete,
     Commit,
     Checkpoint,
+    PageImage,
 }
 impl From<WalOperation> for u8 {
     fn from(op: WalOperation) -> Self {
@@ -23,6 +29,7 @@ impl From<WalOperation> for u8 {
             WalOperation::Delete => 2,
             WalOperation::Commit => 3,
             WalOperation::Checkpoint => 4,
+            WalOperation::PageImage => 5,
         }
     }
 }
@@ -34,6 +41,7 @@ impl TryFrom<u8> for WalOperation {
             2 => Ok(WalOperation::Delete),
             3 => Ok(WalOperation::Commit),
             4 => Ok(WalOperation::Checkpoint),
+            5 => Ok(WalOperation::PageImage),
             _ => Err(SikioError::WalCorrupted {
                 sequence: 0,
                 reason: format!("Unknown operation: {}", value),
@@ -77,7 +85,7 @@ impl WalEntry {
             sequence,
             operation: WalOperation::Commit,
             key: Vec::new(),
-            value: None,
+            value: Some(now_ms().to_le_bytes().to_vec()),
             checksum: 0,
         };
         entry.compute_checksum();
@@ -94,6 +102,33 @@ impl WalEntry {
         entry.compute_checksum();
         entry
     }
+    pub fn new_page_image(sequence: u64, page_id: u64, image: Vec<u8>) -> Self {
+        let mut entry = WalEntry {
+            sequence,
+            operation: WalOperation::PageImage,
+            key: page_id.to_le_bytes().to_vec(),
+            value: Some(image),
+            checksum: 0,
+        };
+        entry.compute_checksum();
+        entry
+    }
+    pub fn page_id(&self) -> Option<u64> {
+        match self.operation {
+            WalOperation::PageImage => {
+                Some(u64::from_le_bytes(self.key.as_slice().try_into().ok()?))
+            }
+            _ => None,
+        }
+    }
+    pub fn commit_time(&self) -> Option<u64> {
+        match (self.operation, &self.value) {
+            (WalOperation::Commit, Some(value)) => {
+                Some(u64::from_le_bytes(value.as_slice().try_into().ok()?))
+            }
+            _ => None,
+        }
+    }
     fn compute_checksum(&mut self) {
         self.checksum = Self::calculate_checksum(
             self.sequence,
@@ -224,7 +259,23 @@ impl WalEntry {
                     });
                 }
             }
-            WalOperation::Commit | WalOperation::Checkpoint => {
+            WalOperation::PageImage => {
+                if key_len != 8 || value_len == 0 {
+                    return Err(SikioError::WalCorrupted {
+                        sequence,
+                        reason: "Page image entry malformed".into(),
+                    });
+                }
+            }
+            WalOperation::Commit => {
+                if key_len != 0 || (value_len != 0 && value_len != COMMIT_TIME_SIZE) {
+                    return Err(SikioError::WalCorrupted {
+                        sequence,
+                        reason: "Control entry has unexpected payload".into(),
+                    });
+                }
+            }
+            WalOperation::Checkpoint => {
                 if key_len != 0 || value_len != 0 {
                     return Err(SikioError::WalCorrupted {
                         sequence,
@@ -278,10 +329,12 @@ impl WalEntry {
         buffer.push(u8::from(WalOperation::Commit));
         buffer.extend_from_slice(&[0u8; 3]);
         buffer.extend_from_slice(&0u32.to_le_bytes());
-        buffer.extend_from_slice(&0u32.to_le_bytes());
+        buffer.extend_from_slice(&(COMMIT_TIME_SIZE as u32).to_le_bytes());
         let checksum_offset = buffer.len();
         buffer.extend_from_slice(&[0u8; 4]);
-        let checksum = Self::calculate_checksum(sequence, WalOperation::Commit, &[], None);
+        let time = now_ms().to_le_bytes();
+        buffer.extend_from_slice(&time);
+        let checksum = Self::calculate_checksum(sequence, WalOperation::Commit, &[], Some(&time));
         let checksum_bytes = checksum.to_le_bytes();
         buffer[checksum_offset] = checksum_bytes[0];
         buffer[checksum_offset + 1] = checksum_bytes[1];
@@ -317,6 +370,33 @@ impl WalEntry {
         buffer[checksum_offset + 3] = checksum_bytes[3];
     }
 }
+fn header_field(header: &[u8], start: usize, end: usize) -> Result<u64> {
+    let field = header
+        .get(start..end)
+        .ok_or_else(|| truncated("Truncated header"))?;
+    let mut bytes = [0u8; 8];
+    bytes[..field.len()].copy_from_slice(field);
+    Ok(u64::from_le_bytes(bytes))
+}
+fn read_entry_size(header: &[u8]) -> Result<usize> {
+    let sequence = header_field(header, SEQUENCE_START, SEQUENCE_END)?;
+    let key_len = header_field(header, KEY_LEN_START, KEY_LEN_END)?;
+    let value_len = header_field(header, VALUE_LEN_START, VALUE_LEN_END)?;
+    (WAL_ENTRY_HEADER_SIZE as u64)
+        .checked_add(key_len)
+        .and_then(|v| v.checked_add(value_len))
+        .and_then(|v| usize::try_from(v).ok())
+        .ok_or_else(|| SikioError::WalCorrupted {
+            sequence,
+            reason: "Entry size overflow".into(),
+        })
+}
+fn truncated(reason: &str) -> SikioError {
+    SikioError::WalCorrupted {
+        sequence: 0,
+        reason: reason.into(),
+    }
+}
 pub struct WalReader<'a> {
     data: &'a [u8],
     position: usize,
@@ -325,6 +405,18 @@ impl<'a> WalReader<'a> {
     pub fn new(data: &'a [u8]) -> Self {
         WalReader { data, position: 0 }
     }
+    fn read_next(&mut self) -> Result<WalEntry> {
+        if self.position + WAL_ENTRY_HEADER_SIZE > self.data.len() {
+            return Err(truncated("Truncated header"));
+        }
+        let entry_size = read_entry_size(&self.data[self.position..])?;
+        if self.position + entry_size > self.data.len() {
+            return Err(truncated("Truncated entry"));
+        }
+        let entry_bytes = &self.data[self.position..self.position + entry_size];
+        self.position += entry_size;
+        WalEntry::from_bytes(entry_bytes)
+    }
 }
 impl<'a> Iterator for WalReader<'a> {
     type Item = Result<WalEntry>;
@@ -332,61 +424,115 @@ impl<'a> Iterator for WalReader<'a> {
         if self.position >= self.data.len() {
             return None;
         }
-        if self.position + WAL_ENTRY_HEADER_SIZE > self.data.len() {
-            return Some(Err(SikioError::WalCorrupted {
-                sequence: 0,
-                reason: "Truncated header".into(),
-            }));
+        Some(self.read_next())
+    }
+}
+pub struct WalCursor {
+    segment: Option<u64>,
+    buffer: Vec<u8>,
+    buffer_offset: u64,
+    position: u64,
+    end: u64,
+}
+impl WalCursor {
+    pub fn new(end: u64) -> Self {
+        WalCursor::at(0, end)
+    }
+    pub fn archived(segment: u64, end: u64) -> Self {
+        WalCursor {
+            segment: Some(segment),
+            ..WalCursor::at(0, end)
         }
-        let header = &self.data[self.position..self.position + WAL_ENTRY_HEADER_SIZE];
-        let sequence = match header[SEQUENCE_START..SEQUENCE_END].try_into() {
-            Ok(b) => u64::from_le_bytes(b),
-            Err(_) => {
-                return Some(Err(SikioError::WalCorrupted {
-                    sequence: 0,
-                    reason: "Invalid sequence bytes".into(),
-                }))
-            }
-        };
-        let key_len = match header[KEY_LEN_START..KEY_LEN_END].try_into() {
-            Ok(b) => u32::from_le_bytes(b) as usize,
-            Err(_) => {
-                return Some(Err(SikioError::WalCorrupted {
-                    sequence,
-                    reason: "Invalid key length bytes".into(),
-                }))
-            }
+    }
+    pub fn at(position: u64, end: u64) -> Self {
+        WalCursor {
+            segment: None,
+            buffer: Vec::new(),
+            buffer_offset: position,
+            position,
+            end,
+        }
+    }
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+    pub fn next_entry(&mut self, storage: &dyn StorageBackend) -> Option<Result<WalEntry>> {
+        if self.position >= self.end {
+            return None;
+        }
+        Some(self.read_next(storage))
+    }
+    pub fn more_entries_follow(&mut self, storage: &dyn StorageBackend) -> bool {
+        let entry_size = match self.fill(storage, WAL_ENTRY_HEADER_SIZE, "Truncated header") {
+            Ok(header) => read_entry_size(header),
+            Err(e) => Err(e),
         };
-        let value_len = match header[VALUE_LEN_START..VALUE_LEN_END].try_into() {
-            Ok(b) => u32::from_le_bytes(b) as usize,
-            Err(_) => {
-                return Some(Err(SikioError::WalCorrupted {
-                    sequence,
-                    reason: "Invalid value length bytes".into(),
-                }))
+        match entry_size {
+            Ok(size) => {
+                let mut next = WalCursor {
+                    segment: self.segment,
+                    ..WalCursor::at(self.position + size as u64, self.end)
+                };
+                matches!(next.next_entry(storage), Some(Ok(_)))
             }
-        };
-        let entry_size = match WAL_ENTRY_HEADER_SIZE
-            .checked_add(key_len)
-            .and_then(|v| v.checked_add(value_len))
-        {
-            Some(v) => v,
-            None => {
-                return Some(Err(SikioError::WalCorrupted {
-                    sequence,
-                    reason: "Entry size overflow".into(),
-                }))
+            Err(_) => false,
+        }
+    }
+    fn read_next(&mut self, storage: &dyn StorageBackend) -> Result<WalEntry> {
+        let header = self.fill(storage, WAL_ENTRY_HEADER_SIZE, "Truncated header")?;
+        let entry_size = read_entry_size(header)?;
+        let entry = WalEntry::from_bytes(self.fill(storage, entry_size, "Truncated entry")?);
+        self.position += entry_size as u64;
+        entry
+    }
+    fn fill(&mut self, storage: &dyn StorageBackend, len: usize, reason: &str) -> Result<&[u8]> {
+        if self.position + len as u64 > self.end {
+            return Err(truncated(reason));
+        }
+        let start = self.position.saturating_sub(self.buffer_offset) as usize;
+        if self.position < self.buffer_offset || start + len > self.buffer.len() {
+            let length = (self.end - self.position).min(WAL_READ_CHUNK_SIZE.max(len) as u64);
+            self.buffer = match self.segment {
+                Some(segment) => {
+                    storage.read_archived_wal(segment, self.position, length as usize)?
+                }
+                None => storage.read_wal(self.position, length as usize)?,
+            };
+            self.buffer_offset = self.position;
+            if self.buffer.len() < len {
+                return Err(truncated(reason));
             }
-        };
-        if self.position + entry_size > self.data.len() {
-            return Some(Err(SikioError::WalCorrupted {
-                sequence,
-                reason: "Truncated entry".into(),
-            }));
+            return Ok(&self.buffer[..len]);
         }
-        let entry_bytes = &self.data[self.position..self.position + entry_size];
-        self.position += entry_size;
-        Some(WalEntry::from_bytes(entry_bytes))
+        Ok(&self.buffer[start..start + len])
+    }
+}
+#[derive(Debug, Clone, Default)]
+pub struct RecoveryReport {
+    pub transactions_replayed: u64,
+    pub ops_replayed: u64,
+    pub ops_discarded: u64,
+    pub stopped_at: Option<u64>,
+    pub stop_reason: Option<SikioError>,
+    pub bytes_discarded: u64,
+    pub mid_log: bool,
+    pub free_list_error: Option<SikioError>,
+    pub replayed_commits: Vec<u64>,
+    pub discarded_keys: Vec<Vec<u8>>,
+}
+impl RecoveryReport {
+    pub fn record_commit(&mut self, lsn: u64, ops: usize) {
+        self.transactions_replayed += 1;
+        self.ops_replayed += ops as u64;
+        if self.replayed_commits.len() < RECOVERY_REPORT_MAX_ENTRIES {
+            self.replayed_commits.push(lsn);
+        }
+    }
+    pub fn record_discarded(&mut self, ops: &[WalEntry]) {
+        self.ops_discarded += ops.len() as u64;
+        let room = RECOVERY_REPORT_MAX_ENTRIES - self.discarded_keys.len();
+        let keys = ops.iter().take(room).map(|op| op.key.clone());
+        self.discarded_keys.extend(keys);
     }
 }
 
@@ -426,6 +572,7 @@ mod tests {
         let e3 = WalEntry::from_bytes(&buf).unwrap();
         assert_eq!(e3.operation, WalOperation::Commit);
         assert_eq!(e3.sequence, 3);
+        assert!(e3.commit_time().is_some());
 
         buf.clear();
         WalEntry::serialize_checkpoint(4, &mut buf);
@@ -434,6 +581,41 @@ mod tests {
         assert_eq!(e4.sequence, 4);
     }
 
+    #[test]
+    fn roundtrip_page_image_entry() {
+        let entry = WalEntry::new_page_image(5, 42, vec![9u8; 4096]);
+        let decoded = WalEntry::from_bytes(&entry.to_bytes()).unwrap();
+        assert_eq!(decoded.operation, WalOperation::PageImage);
+        assert_eq!(decoded.page_id(), Some(42));
+        assert_eq!(decoded.value, Some(vec![9u8; 4096]));
+    }
+
+    #[test]
+    fn cursor_streams_entries_across_chunks() {
+        use crate::storage::MemoryStorage;
+        let mut storage = MemoryStorage::new();
+        let mut buf = Vec::new();
+        for page_id in 0..4u64 {
+            WalEntry::new_page_image(page_id + 1, page_id, vec![page_id as u8; 600 * 1024])
+                .write_to_buffer(&mut buf);
+            WalEntry::serialize_commit(page_id + 1, &mut buf);
+        }
+        storage.append_wal(&buf).unwrap();
+        storage.append_wal(&[0u8; 10]).unwrap();
+
+        let mut cursor = WalCursor::new(storage.wal_size());
+        let mut images = 0;
+        while let Some(Ok(entry)) = cursor.next_entry(&storage) {
+            if entry.operation == WalOperation::PageImage {
+                let page_id = entry.page_id().unwrap();
+                assert_eq!(entry.value, Some(vec![page_id as u8; 600 * 1024]));
+                images += 1;
+            }
+        }
+        assert_eq!(images, 4);
+        assert_eq!(cursor.position(), buf.len() as u64);
+    }
+
     #[test]
     fn detects_checksum_mismatch() {
         let entry = WalEntry::new_put(10, b"k".to_vec(), b"v".to_vec());